@@ -33,9 +33,14 @@ impl CgiEnvironment {
         env.set("GATEWAY_INTERFACE", "CGI/1.1");
         env.set("SERVER_PROTOCOL", "HTTP/1.1");
         
-        // Request URI and path info
-        let request_uri = request.path();
-        env.set("REQUEST_URI", request_uri);
+        // REQUEST_URI is the target as sent; PATH_INFO comes from the
+        // decoded, normalized path (RFC 3875 section 4.1.5)
+        let request_uri = match &request.query_string {
+            Some(query) => format!("{}?{}", request.raw_path(), query),
+            None => request.raw_path().to_string(),
+        };
+        env.set("REQUEST_URI", &request_uri);
+        let path = request.path();
         
         // Calculate SCRIPT_NAME and PATH_INFO
         if let Ok(script_relative) = script_path.strip_prefix(document_root) {
@@ -43,7 +48,7 @@ impl CgiEnvironment {
             env.set("SCRIPT_NAME", &script_name);
             
             // PATH_INFO is the part of the URI after the script name
-            if let Some(path_info) = request_uri.strip_prefix(&script_name) {
+            if let Some(path_info) = path.strip_prefix(&script_name) {
                 if !path_info.is_empty() {
                     env.set("PATH_INFO", path_info);
                     
@@ -75,20 +80,21 @@ impl CgiEnvironment {
             env.set("CONTENT_LENGTH", &body.len().to_string());
         }
         
-        // HTTP headers (convert to HTTP_* format)
-        // Note: This would need to be implemented when HttpRequest has a headers() method
-        // For now, we'll handle the most common headers individually
-        if let Some(user_agent) = request.get_header("User-Agent") {
-            env.set("HTTP_USER_AGENT", user_agent);
-        }
-        if let Some(accept) = request.get_header("Accept") {
-            env.set("HTTP_ACCEPT", accept);
-        }
-        if let Some(accept_encoding) = request.get_header("Accept-Encoding") {
-            env.set("HTTP_ACCEPT_ENCODING", accept_encoding);
-        }
-        if let Some(accept_language) = request.get_header("Accept-Language") {
-            env.set("HTTP_ACCEPT_LANGUAGE", accept_language);
+        // HTTP headers as HTTP_* variables (RFC 3875 section 4.1.18). Repeated
+        // fields become one variable; cookies are joined the way a single
+        // Cookie header lists them. Proxy is skipped so a client cannot set
+        // HTTP_PROXY for the script ("httpoxy").
+        for (name, _) in request.headers.iter() {
+            let variable = format!("HTTP_{}", name.to_uppercase().replace('-', "_"));
+            if matches!(variable.as_str(), "HTTP_CONTENT_TYPE" | "HTTP_CONTENT_LENGTH" | "HTTP_PROXY")
+                || env.get(&variable).is_some()
+            {
+                continue;
+            }
+            
+            let separator = if name.eq_ignore_ascii_case("cookie") { "; " } else { ", " };
+            let values: Vec<&str> = request.headers.get_all(name).collect();
+            env.set(&variable, &values.join(separator));
         }
         
         // Remote address (simplified - in production would get from connection)
@@ -187,10 +193,12 @@ mod tests {
     #[test]
     fn test_from_request() {
         let mut request = HttpRequest::new();
-        request.set_method(Method::GET);
-        request.set_path("/cgi-bin/test.py/path/info?query=value".to_string());
-        request.set_header("Host".to_string(), "example.com".to_string());
-        request.set_header("User-Agent".to_string(), "TestAgent/1.0".to_string());
+        request.method = Method::GET;
+        request.path = "/cgi-bin/test.py/path/info".to_string();
+        request.raw_path = request.path.clone();
+        request.query_string = Some("query=value".to_string());
+        request.headers.insert("host", "example.com");
+        request.headers.insert("user-agent", "TestAgent/1.0");
         
         let script_path = PathBuf::from("/var/www/cgi-bin/test.py");
         let document_root = PathBuf::from("/var/www");
@@ -213,6 +221,27 @@ mod tests {
         assert_eq!(env.get("HTTP_USER_AGENT"), Some("TestAgent/1.0"));
     }
     
+    #[test]
+    fn test_request_uri_and_path_info() {
+        let mut request = HttpRequest::new();
+        request.raw_path = "/cgi-bin/test.py/caf%C3%A9//x".to_string();
+        request.path = "/cgi-bin/test.py/café/x".to_string();
+        request.query_string = Some("q=%20".to_string());
+        
+        let env = CgiEnvironment::from_request(
+            &request,
+            &PathBuf::from("/var/www/cgi-bin/test.py"),
+            &PathBuf::from("/var/www"),
+            "example.com",
+            80,
+        );
+        
+        assert_eq!(env.get("REQUEST_URI"), Some("/cgi-bin/test.py/caf%C3%A9//x?q=%20"));
+        assert_eq!(env.get("SCRIPT_NAME"), Some("/cgi-bin/test.py"));
+        assert_eq!(env.get("PATH_INFO"), Some("/café/x"));
+        assert_eq!(env.get("QUERY_STRING"), Some("q=%20"));
+    }
+    
     #[test]
     fn test_to_env_strings() {
         let mut env = CgiEnvironment::new();
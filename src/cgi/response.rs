@@ -1,12 +1,12 @@
+use crate::http::headers::HeaderMap;
 use crate::http::response::HttpResponse;
-use std::collections::HashMap;
 use std::io;
 
 /// CGI response parser and handler
 #[derive(Debug, Clone)]
 pub struct CgiResponse {
     pub status: Option<u16>,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
     pub body: Vec<u8>,
 }
 
@@ -14,7 +14,7 @@ impl CgiResponse {
     pub fn new() -> Self {
         CgiResponse {
             status: None,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body: Vec::new(),
         }
     }
@@ -24,9 +24,13 @@ impl CgiResponse {
         let status = self.status.unwrap_or(200);
         let mut response = HttpResponse::new(status);
         
-        // Set headers from CGI output
-        for (name, value) in self.headers {
-            response.set_header(&name, &value);
+        // Headers from CGI output replace the server's defaults; fields the
+        // script sent several times, such as Set-Cookie, keep every value
+        for (name, _) in self.headers.iter() {
+            response.headers.remove(name);
+        }
+        for (name, value) in self.headers.iter() {
+            response.append_header(name, value);
         }
         
         // Set body
@@ -50,7 +54,7 @@ impl Default for CgiResponse {
 #[derive(Debug)]
 pub struct CgiResponseParser {
     state: ParseState,
-    headers: HashMap<String, String>,
+    headers: HeaderMap,
     body: Vec<u8>,
     current_header: String,
 }
@@ -65,7 +69,7 @@ impl CgiResponseParser {
     pub fn new() -> Self {
         CgiResponseParser {
             state: ParseState::Headers,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body: Vec::new(),
             current_header: String::new(),
         }
@@ -153,20 +157,20 @@ impl CgiResponseParser {
                 "status" => {
                     // Parse status code from "Status: 404 Not Found" format
                     if let Some(space_pos) = value.find(' ') {
-                        if let Ok(status) = value[..space_pos].parse::<u16>() {
+                        if value[..space_pos].parse::<u16>().is_ok() {
                             // Status is handled separately, don't add to headers
                             return Ok(());
                         }
                     }
                     // If parsing fails, treat as regular header
-                    self.headers.insert(name, value);
+                    self.headers.append(name, value);
                 }
                 "location" => {
                     // Location header implies redirect (usually 302)
-                    self.headers.insert(name, value);
+                    self.headers.append(name, value);
                 }
                 _ => {
-                    self.headers.insert(name, value);
+                    self.headers.append(name, value);
                 }
             }
         } else {
@@ -230,13 +234,13 @@ mod tests {
     fn test_cgi_response_to_http() {
         let mut cgi_response = CgiResponse::new();
         cgi_response.status = Some(404);
-        cgi_response.headers.insert("Content-Type".to_string(), "text/plain".to_string());
+        cgi_response.headers.insert("Content-Type", "text/plain");
         cgi_response.body = b"Not Found".to_vec();
         
         let http_response = cgi_response.to_http_response();
-        assert_eq!(http_response.status_code(), 404);
+        assert_eq!(http_response.status_code, 404);
         assert_eq!(http_response.get_header("Content-Type"), Some("text/plain"));
-        assert_eq!(http_response.body(), Some(b"Not Found".as_ref()));
+        assert_eq!(http_response.body, b"Not Found");
     }
     
     #[test]
@@ -246,7 +250,7 @@ mod tests {
         let response = CgiResponseParser::parse_complete(cgi_output).unwrap();
         
         assert_eq!(response.status, None); // Should default to 200
-        assert_eq!(response.headers.get("Content-Type"), Some(&"text/html".to_string()));
+        assert_eq!(response.headers.get("Content-Type"), Some("text/html"));
         assert_eq!(response.body, b"<html><body>Hello World</body></html>");
     }
     
@@ -257,7 +261,7 @@ mod tests {
         let response = CgiResponseParser::parse_complete(cgi_output).unwrap();
         
         assert_eq!(response.status, None); // Status parsing not fully implemented yet
-        assert_eq!(response.headers.get("Content-Type"), Some(&"text/plain".to_string()));
+        assert_eq!(response.headers.get("Content-Type"), Some("text/plain"));
         assert_eq!(response.body, b"Page not found");
     }
     
@@ -268,7 +272,7 @@ mod tests {
         let response = CgiResponseParser::parse_complete(cgi_output).unwrap();
         
         assert_eq!(response.status, Some(302)); // Should be redirect
-        assert_eq!(response.headers.get("Location"), Some(&"http://example.com/new-page".to_string()));
+        assert_eq!(response.headers.get("Location"), Some("http://example.com/new-page"));
         assert!(response.body.is_empty());
     }
     
@@ -278,18 +282,30 @@ mod tests {
         
         let response = CgiResponseParser::parse_complete(cgi_output).unwrap();
         
-        assert_eq!(response.headers.get("Content-Type"), Some(&"text/html".to_string()));
+        assert_eq!(response.headers.get("Content-Type"), Some("text/html"));
         // Multiline header should be combined
         assert!(response.headers.get("Set-Cookie").is_some());
     }
     
+    #[test]
+    fn test_repeated_headers_are_kept() {
+        let cgi_output = b"Set-Cookie: a=1\r\nContent-Type: text/plain\r\nSet-Cookie: b=2\r\nServer: script\r\n\r\nok";
+        
+        let response = CgiResponseParser::parse_complete(cgi_output).unwrap();
+        assert_eq!(response.headers.get_all("Set-Cookie").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+        
+        let http_response = response.to_http_response();
+        assert_eq!(http_response.headers.get_all("Set-Cookie").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+        assert_eq!(http_response.headers.get_all("Server").collect::<Vec<_>>(), vec!["script"]);
+    }
+    
     #[test]
     fn test_parse_empty_body() {
         let cgi_output = b"Content-Type: text/plain\r\n\r\n";
         
         let response = CgiResponseParser::parse_complete(cgi_output).unwrap();
         
-        assert_eq!(response.headers.get("Content-Type"), Some(&"text/plain".to_string()));
+        assert_eq!(response.headers.get("Content-Type"), Some("text/plain"));
         assert!(response.body.is_empty());
     }
     
@@ -0,0 +1,343 @@
+use crate::config::value::ConfigValue;
+use std::collections::HashMap;
+use std::io;
+
+/// Parse a JSON document (RFC 8259) into a value tree.
+///
+/// Numbers without a fraction or exponent become integers, everything else
+/// a float. Duplicate object keys are rejected so a configuration can never
+/// silently mean two things. Errors carry the line and column where parsing
+/// failed.
+pub fn parse(input: &str) -> io::Result<ConfigValue> {
+    let mut reader = JsonReader::new(input);
+    
+    reader.skip_whitespace();
+    let value = reader.parse_value(0)?;
+    reader.skip_whitespace();
+    
+    if reader.peek().is_some() {
+        return Err(reader.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Nesting limit, so a hostile file cannot exhaust the stack
+const MAX_DEPTH: usize = 128;
+
+struct JsonReader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonReader {
+    fn new(input: &str) -> Self {
+        JsonReader {
+            chars: input.chars().collect(),
+            pos: 0,
+        }
+    }
+    
+    fn parse_value(&mut self, depth: usize) -> io::Result<ConfigValue> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        
+        match self.peek() {
+            Some('{') => self.parse_object(depth),
+            Some('[') => self.parse_array(depth),
+            Some('"') => {
+                self.pos += 1;
+                Ok(ConfigValue::String(self.parse_string()?))
+            }
+            Some('t') => self.parse_literal("true", ConfigValue::Boolean(true)),
+            Some('f') => self.parse_literal("false", ConfigValue::Boolean(false)),
+            Some('n') => Err(self.error("null is not a valid configuration value")),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) => Err(self.error(&format!("unexpected character `{}`", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+    
+    fn parse_object(&mut self, depth: usize) -> io::Result<ConfigValue> {
+        self.pos += 1;
+        let mut table = HashMap::new();
+        
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(ConfigValue::Table(table));
+        }
+        
+        loop {
+            self.skip_whitespace();
+            let key_start = self.pos;
+            if self.next() != Some('"') {
+                return Err(self.error_at(key_start, "expected string key"));
+            }
+            let key = self.parse_string()?;
+            
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.parse_value(depth + 1)?;
+            
+            if table.insert(key.clone(), value).is_some() {
+                return Err(self.error_at(key_start, &format!("duplicate key `{}`", key)));
+            }
+            
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(ConfigValue::Table(table)),
+                _ => return Err(self.error_at(self.pos.saturating_sub(1), "expected `,` or `}`")),
+            }
+        }
+    }
+    
+    fn parse_array(&mut self, depth: usize) -> io::Result<ConfigValue> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(ConfigValue::Array(items));
+        }
+        
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value(depth + 1)?);
+            
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(ConfigValue::Array(items)),
+                _ => return Err(self.error_at(self.pos.saturating_sub(1), "expected `,` or `]`")),
+            }
+        }
+    }
+    
+    /// Parse the rest of a string after the opening quote
+    fn parse_string(&mut self) -> io::Result<String> {
+        let mut result = String::new();
+        
+        loop {
+            let c_pos = self.pos;
+            match self.next() {
+                Some('"') => return Ok(result),
+                Some('\\') => result.push(self.parse_escape()?),
+                Some(c) if c < ' ' => return Err(self.error_at(c_pos, "control characters must be escaped")),
+                Some(c) => result.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+    
+    fn parse_escape(&mut self) -> io::Result<char> {
+        let start = self.pos - 1;
+        match self.next() {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('/') => Ok('/'),
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => {
+                let high = self.parse_hex4()?;
+                
+                // Characters outside the BMP arrive as a UTF-16 surrogate pair
+                let code = if (0xD800..0xDC00).contains(&high) {
+                    if !(self.next() == Some('\\') && self.next() == Some('u')) {
+                        return Err(self.error_at(start, "unpaired surrogate in unicode escape"));
+                    }
+                    let low = self.parse_hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error_at(start, "unpaired surrogate in unicode escape"));
+                    }
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    high
+                };
+                
+                char::from_u32(code).ok_or_else(|| self.error_at(start, "invalid unicode escape"))
+            }
+            _ => Err(self.error_at(start, "invalid escape sequence")),
+        }
+    }
+    
+    fn parse_hex4(&mut self) -> io::Result<u32> {
+        let start = self.pos;
+        let digits: String = (0..4).filter_map(|_| self.next()).collect();
+        
+        if digits.len() != 4 {
+            return Err(self.error_at(start, "invalid unicode escape"));
+        }
+        u32::from_str_radix(&digits, 16).map_err(|_| self.error_at(start, "invalid unicode escape"))
+    }
+    
+    fn parse_number(&mut self) -> io::Result<ConfigValue> {
+        let start = self.pos;
+        let mut is_float = false;
+        
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        
+        // No leading zeros: "0" or [1-9][0-9]*
+        match self.peek() {
+            Some('0') => self.pos += 1,
+            Some(c) if c.is_ascii_digit() => self.skip_digits(),
+            _ => return Err(self.error_at(start, "invalid number")),
+        }
+        if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return Err(self.error_at(start, "leading zeros are not allowed"));
+        }
+        
+        if self.peek() == Some('.') {
+            is_float = true;
+            self.pos += 1;
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error_at(start, "expected digits after decimal point"));
+            }
+            self.skip_digits();
+        }
+        
+        if matches!(self.peek(), Some('e') | Some('E')) {
+            is_float = true;
+            self.pos += 1;
+            if matches!(self.peek(), Some('+') | Some('-')) {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error_at(start, "expected digits in exponent"));
+            }
+            self.skip_digits();
+        }
+        
+        let token: String = self.chars[start..self.pos].iter().collect();
+        if is_float {
+            token.parse::<f64>()
+                .map(ConfigValue::Float)
+                .map_err(|_| self.error_at(start, "invalid number"))
+        } else {
+            token.parse::<i64>()
+                .map(ConfigValue::Integer)
+                .map_err(|_| self.error_at(start, "integer out of range"))
+        }
+    }
+    
+    fn parse_literal(&mut self, literal: &str, value: ConfigValue) -> io::Result<ConfigValue> {
+        let start = self.pos;
+        for expected in literal.chars() {
+            if self.next() != Some(expected) {
+                return Err(self.error_at(start, "invalid literal"));
+            }
+        }
+        Ok(value)
+    }
+    
+    fn skip_digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+    
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t') | Some('\n') | Some('\r')) {
+            self.pos += 1;
+        }
+    }
+    
+    fn expect(&mut self, expected: char) -> io::Result<()> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", expected)))
+        }
+    }
+    
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+    
+    fn error(&self, message: &str) -> io::Error {
+        self.error_at(self.pos, message)
+    }
+    
+    /// Build an error pointing at a character offset, reported as 1-based line/column
+    fn error_at(&self, pos: usize, message: &str) -> io::Error {
+        let pos = pos.min(self.chars.len());
+        let line = self.chars[..pos].iter().filter(|&&c| c == '\n').count() + 1;
+        let line_start = self.chars[..pos].iter().rposition(|&c| c == '\n').map(|i| i + 1).unwrap_or(0);
+        let column = pos - line_start + 1;
+        
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("JSON parse error at line {}, column {}: {}", line, column, message),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_values() {
+        let doc = parse(r#"{
+            "name": "caf\u00e9 \"q\" \ud83d\ude00",
+            "port": 8080,
+            "negative": -12,
+            "ratio": 0.5,
+            "big": 1E3,
+            "enabled": true,
+            "list": [1, "two", false, []],
+            "nested": {"empty": {}}
+        }"#).unwrap();
+        
+        assert_eq!(doc.get("name").and_then(|v| v.as_str()), Some("café \"q\" 😀"));
+        assert_eq!(doc.get("port"), Some(&ConfigValue::Integer(8080)));
+        assert_eq!(doc.get("negative"), Some(&ConfigValue::Integer(-12)));
+        assert_eq!(doc.get("ratio"), Some(&ConfigValue::Float(0.5)));
+        assert_eq!(doc.get("big"), Some(&ConfigValue::Float(1000.0)));
+        assert_eq!(doc.get("enabled"), Some(&ConfigValue::Boolean(true)));
+        assert_eq!(doc.get("list").and_then(|v| v.as_array()).map(|a| a.len()), Some(4));
+        assert_eq!(doc.get("nested").and_then(|v| v.get("empty")), Some(&ConfigValue::table()));
+    }
+    
+    #[test]
+    fn test_errors_have_line_and_column() {
+        let err = parse("{\n  \"a\": 1,\n  \"b\": tru\n}").unwrap_err();
+        assert!(err.to_string().contains("line 3, column 8"), "{}", err);
+        
+        let err = parse("{\"a\": 1, \"a\": 2}").unwrap_err();
+        assert!(err.to_string().contains("duplicate key"), "{}", err);
+        
+        let err = parse("{\"a\": 1,}").unwrap_err();
+        assert!(err.to_string().contains("line 1, column 9"), "{}", err);
+    }
+    
+    #[test]
+    fn test_invalid_documents() {
+        assert!(parse("").is_err());
+        assert!(parse("{} {}").is_err());
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("{\"a\": 01}").is_err());
+        assert!(parse("{\"a\": 1.}").is_err());
+        assert!(parse("{\"a\": null}").is_err());
+        assert!(parse("{\"a\": \"tab\there\"}").is_err());
+        assert!(parse("{\"a\": \"\\ud800\"}").is_err());
+        assert!(parse(&"[".repeat(200)).is_err());
+    }
+}
@@ -1,4 +1,7 @@
+pub mod json;
 pub mod parser;
 pub mod server;
+pub mod toml;
 pub mod validation;
+pub mod value;
 
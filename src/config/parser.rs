@@ -1,4 +1,7 @@
 use crate::config::server::*;
+use crate::config::json;
+use crate::config::toml;
+use crate::config::value::ConfigValue;
 use std::collections::HashMap;
 use std::fs;
 use std::io;
@@ -12,7 +15,7 @@ pub enum ConfigFormat {
     Toml,
     /// JSON format
     Json,
-    /// Auto-detect from file extension
+    /// Detect from the file extension, or for content, from a leading `{`
     Auto,
 }
 
@@ -46,9 +49,13 @@ impl ConfigParser {
             ConfigFormat::Toml => self.parse_toml(content),
             ConfigFormat::Json => self.parse_json(content),
             ConfigFormat::Auto => {
-                // Try TOML first, then JSON
-                self.parse_toml(content)
-                    .or_else(|_| self.parse_json(content))
+                // A JSON configuration is an object, which no TOML document
+                // starts with; everything else is TOML and gets its errors
+                if content.trim_start().starts_with('{') {
+                    self.parse_json(content)
+                } else {
+                    self.parse_toml(content)
+                }
             }
         }
     }
@@ -64,139 +71,132 @@ impl ConfigParser {
     
     /// Parse TOML configuration
     fn parse_toml(&self, content: &str) -> io::Result<ServerConfig> {
-        // Simple TOML-like parser (in production, use a proper TOML library)
+        let document = toml::parse(content)?;
+        self.build_config(&document)
+    }
+    
+    /// Parse JSON configuration
+    fn parse_json(&self, content: &str) -> io::Result<ServerConfig> {
+        let document = json::parse(content)?;
+        self.build_config(&document)
+    }
+    
+    /// Map a parsed TOML or JSON document onto `ServerConfig`.
+    /// Sections are applied in dependency order so that `[global]` and `[cgi]`
+    /// defaults are known before virtual hosts and routes are built.
+    fn build_config(&self, document: &ConfigValue) -> io::Result<ServerConfig> {
+        let root = document.as_table()
+            .ok_or_else(|| invalid_data("Configuration root must be a table".to_string()))?;
+        
         let mut config = ServerConfig::default();
-        let mut current_section = String::new();
-        let mut current_vhost: Option<VirtualHostConfig> = None;
-        let mut current_route: Option<RouteConfig> = None;
+        let mut defaults = VhostDefaults::default();
+        let mut listeners = Vec::new();
+        let mut explicit_default_host = false;
         
-        for line in content.lines() {
-            let line = line.trim();
-            
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
-                continue;
+        if let Some(server) = self.section(root, "server")? {
+            for (key, value) in server {
+                self.set_server_value(&mut config, &mut listeners, &mut explicit_default_host, key, value)?;
             }
-            
-            // Handle sections
-            if line.starts_with('[') && line.ends_with(']') {
-                // Save previous vhost/route if any
-                if let Some(route) = current_route.take() {
-                    if let Some(ref mut vhost) = current_vhost {
-                        vhost.routes.push(route);
-                    }
-                }
-                if let Some(vhost) = current_vhost.take() {
-                    config.virtual_hosts.push(vhost);
+            self.set_server_identity(&mut config.global, server)?;
+        }
+        if let Some(global) = self.section(root, "global")? {
+            for (key, value) in global {
+                self.set_global_value(&mut config.global, &mut defaults, key, value)?;
+            }
+        }
+        if let Some(timeouts) = self.section(root, "timeouts")? {
+            for (key, value) in timeouts {
+                self.set_timeout_value(&mut config.global.timeouts, key, value)?;
+            }
+        }
+        for name in ["uploads", "upload"] {
+            if let Some(uploads) = self.section(root, name)? {
+                for (key, value) in uploads {
+                    self.set_upload_value(&mut config.global.uploads, key, value)?;
                 }
-                
-                current_section = line[1..line.len()-1].to_string();
-                
-                // Start new vhost or route
-                if current_section.starts_with("vhost.") {
-                    current_vhost = Some(VirtualHostConfig::default());
-                } else if current_section.starts_with("route.") {
-                    current_route = Some(RouteConfig::default());
+            }
+        }
+        for name in ["sessions", "session"] {
+            if let Some(sessions) = self.section(root, name)? {
+                for (key, value) in sessions {
+                    self.set_session_value(&mut config.global.sessions, key, value)?;
                 }
-                continue;
             }
-            
-            // Handle key-value pairs
-            if let Some(eq_pos) = line.find('=') {
-                let key = line[..eq_pos].trim();
-                let value = line[eq_pos + 1..].trim();
-                let value = self.parse_value(value);
-                
-                self.set_config_value(&mut config, &current_section, key, &value, 
-                                    &mut current_vhost, &mut current_route)?;
+        }
+        if let Some(cgi) = self.section(root, "cgi")? {
+            for (key, value) in cgi {
+                self.set_cgi_value(&mut config.global.cgi, key, value)?;
             }
         }
+        if let Some(logging) = self.section(root, "logging")? {
+            self.set_logging_values(&mut config.global.logging, logging)?;
+        }
+        if let Some(security) = self.section(root, "security")? {
+            self.set_security_values(&mut config.global.security, security)?;
+        }
         
-        // Save final vhost/route
-        if let Some(route) = current_route {
-            if let Some(ref mut vhost) = current_vhost {
-                vhost.routes.push(route);
-            }
+        for (i, listener) in self.array_of_tables(root, "listener")?.iter().enumerate() {
+            listeners.push(self.build_listener(listener, &format!("listener[{}]", i))?);
         }
-        if let Some(vhost) = current_vhost {
-            config.virtual_hosts.push(vhost);
+        if !listeners.is_empty() {
+            config.listeners = listeners;
         }
         
-        Ok(config)
-    }
-    
-    /// Parse JSON configuration
-    fn parse_json(&self, content: &str) -> io::Result<ServerConfig> {
-        // Simple JSON parser (in production, use serde_json)
-        // For now, return default config with error
-        Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "JSON parsing not fully implemented - use TOML format",
-        ))
-    }
-    
-    /// Parse configuration value
-    fn parse_value(&self, value: &str) -> String {
-        // Remove quotes if present
-        if (value.starts_with('"') && value.ends_with('"')) ||
-           (value.starts_with('\'') && value.ends_with('\'')) {
-            value[1..value.len()-1].to_string()
-        } else {
-            value.to_string()
+        let vhost_tables = self.array_of_tables(root, "vhost")?;
+        if !vhost_tables.is_empty() {
+            config.virtual_hosts.clear();
+            let mut marked_default = None;
+            
+            for (i, table) in vhost_tables.iter().enumerate() {
+                let field = format!("vhost[{}]", i);
+                let vhost = self.build_vhost(table, &config.global, &defaults, &field)?;
+                
+                if let Some(value) = table.get("default") {
+                    if self.expect_bool(value, &format!("{}.default", field))? && marked_default.is_none() {
+                        marked_default = Some(vhost.server_name.clone());
+                    }
+                }
+                config.virtual_hosts.push(vhost);
+            }
+            
+            // An explicit [server] default_host wins over `default = true`
+            if !explicit_default_host {
+                config.default_host = marked_default
+                    .or_else(|| config.virtual_hosts.first().map(|v| v.server_name.clone()));
+            }
         }
+        
+        Ok(config)
     }
     
-    /// Set configuration value based on section and key
-    fn set_config_value(
+    /// Set a `[server]` value
+    fn set_server_value(
         &self,
         config: &mut ServerConfig,
-        section: &str,
+        listeners: &mut Vec<ListenerConfig>,
+        explicit_default_host: &mut bool,
         key: &str,
-        value: &str,
-        current_vhost: &mut Option<VirtualHostConfig>,
-        current_route: &mut Option<RouteConfig>,
+        value: &ConfigValue,
     ) -> io::Result<()> {
-        match section {
-            "server" => self.set_server_value(config, key, value)?,
-            "global" => self.set_global_value(&mut config.global, key, value)?,
-            "timeouts" => self.set_timeout_value(&mut config.global.timeouts, key, value)?,
-            "uploads" => self.set_upload_value(&mut config.global.uploads, key, value)?,
-            "sessions" => self.set_session_value(&mut config.global.sessions, key, value)?,
-            "cgi" => self.set_cgi_value(&mut config.global.cgi, key, value)?,
-            "logging" => self.set_logging_value(&mut config.global.logging, key, value)?,
-            "security" => self.set_security_value(&mut config.global.security, key, value)?,
-            s if s.starts_with("vhost.") => {
-                if let Some(ref mut vhost) = current_vhost {
-                    self.set_vhost_value(vhost, key, value)?;
-                }
-            }
-            s if s.starts_with("route.") => {
-                if let Some(ref mut route) = current_route {
-                    self.set_route_value(route, key, value)?;
-                }
-            }
-            _ => {
-                // Unknown section, ignore
-            }
-        }
-        Ok(())
-    }
-    
-    /// Set server-level configuration value
-    fn set_server_value(&self, config: &mut ServerConfig, key: &str, value: &str) -> io::Result<()> {
+        let field = format!("server.{}", key);
         match key {
-            "default_host" => config.default_host = Some(value.to_string()),
+            "default_host" => {
+                config.default_host = Some(self.expect_str(value, &field)?.to_string());
+                *explicit_default_host = true;
+            }
             "listen" => {
-                // Parse "address:port" format
-                if let Some(colon_pos) = value.find(':') {
-                    let address = value[..colon_pos].to_string();
-                    let port = value[colon_pos + 1..].parse::<u16>()
-                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid port"))?;
+                // "address:port", or an array of them
+                for addr in self.expect_string_list(value, &field)? {
+                    let (address, port) = addr.rsplit_once(':')
+                        .ok_or_else(|| invalid_data(format!("Invalid value for {}: expected address:port", field)))?;
+                    let port = port.parse::<u16>()
+                        .map_err(|_| invalid_data(format!("Invalid port in {}: {}", field, addr)))?;
                     
-                    config.listeners.push(ListenerConfig {
-                        address,
+                    listeners.push(ListenerConfig {
+                        address: address.to_string(),
                         port,
-                        default: config.listeners.is_empty(),
+                        default: listeners.is_empty(),
+                        name: None,
                     });
                 }
             }
@@ -205,158 +205,559 @@ impl ConfigParser {
         Ok(())
     }
     
-    /// Set global configuration value
-    fn set_global_value(&self, global: &mut GlobalConfig, key: &str, value: &str) -> io::Result<()> {
+    /// Build the `Server` header from `[server]` name and version, which
+    /// are read together so neither depends on the other's position
+    fn set_server_identity(&self, global: &mut GlobalConfig, server: &HashMap<String, ConfigValue>) -> io::Result<()> {
+        let name = server.get("name").map(|value| self.expect_str(value, "server.name")).transpose()?;
+        let version = server.get("version").map(|value| self.expect_str(value, "server.version")).transpose()?;
+        
+        global.server_name = match (name, version) {
+            (Some(name), Some(version)) => format!("{}/{}", name, version),
+            (Some(name), None) => name.to_string(),
+            (None, Some(version)) => {
+                let name = global.server_name.split('/').next().unwrap_or("").to_string();
+                format!("{}/{}", name, version)
+            }
+            (None, None) => return Ok(()),
+        };
+        Ok(())
+    }
+    
+    /// Set a `[global]` value
+    fn set_global_value(&self, global: &mut GlobalConfig, defaults: &mut VhostDefaults, key: &str, value: &ConfigValue) -> io::Result<()> {
+        let field = format!("global.{}", key);
         match key {
-            "server_name" => global.server_name = value.to_string(),
-            "workers" => global.workers = value.parse().unwrap_or(1),
+            "server_name" => global.server_name = self.expect_str(value, &field)?.to_string(),
+            "workers" => global.workers = self.expect_unsigned(value, &field)? as usize,
+            "max_body_size" => defaults.max_body_size = Some(self.expect_size(value, &field)?),
+            "directory_listing" => defaults.directory_listing = Some(self.expect_bool(value, &field)?),
+            "index_files" => defaults.index_files = Some(self.expect_string_list(value, &field)?),
+            "read_header_timeout" => global.timeouts.read_header = self.expect_duration(value, &field)?,
+            "read_body_timeout" => global.timeouts.read_body = self.expect_duration(value, &field)?,
+            "write_timeout" => global.timeouts.write = self.expect_duration(value, &field)?,
+            "keep_alive_timeout" => global.timeouts.keep_alive = self.expect_duration(value, &field)?,
+            "request_timeout" => global.timeouts.request = self.expect_duration(value, &field)?,
+            "shutdown_timeout" => global.timeouts.shutdown = self.expect_duration(value, &field)?,
             _ => {}
         }
         Ok(())
     }
     
-    /// Set timeout configuration value
-    fn set_timeout_value(&self, timeouts: &mut TimeoutConfig, key: &str, value: &str) -> io::Result<()> {
-        let duration = self.parse_duration(value)?;
+    /// Set a `[timeouts]` value
+    fn set_timeout_value(&self, timeouts: &mut TimeoutConfig, key: &str, value: &ConfigValue) -> io::Result<()> {
+        let field = format!("timeouts.{}", key);
         match key {
-            "read_header" => timeouts.read_header = duration,
-            "read_body" => timeouts.read_body = duration,
-            "write" => timeouts.write = duration,
-            "keep_alive" => timeouts.keep_alive = duration,
-            "request" => timeouts.request = duration,
+            "read_header" => timeouts.read_header = self.expect_duration(value, &field)?,
+            "read_body" => timeouts.read_body = self.expect_duration(value, &field)?,
+            "write" => timeouts.write = self.expect_duration(value, &field)?,
+            "keep_alive" => timeouts.keep_alive = self.expect_duration(value, &field)?,
+            "request" => timeouts.request = self.expect_duration(value, &field)?,
+            "shutdown" => timeouts.shutdown = self.expect_duration(value, &field)?,
             _ => {}
         }
         Ok(())
     }
     
-    /// Set upload configuration value
-    fn set_upload_value(&self, uploads: &mut UploadConfig, key: &str, value: &str) -> io::Result<()> {
+    /// Set an `[upload]` value
+    fn set_upload_value(&self, uploads: &mut UploadConfig, key: &str, value: &ConfigValue) -> io::Result<()> {
+        let field = format!("upload.{}", key);
         match key {
-            "directory" => uploads.directory = PathBuf::from(value),
-            "max_file_size" => uploads.max_file_size = self.parse_size(value)?,
-            "max_total_size" => uploads.max_total_size = self.parse_size(value)?,
+            "directory" | "upload_directory" => uploads.directory = PathBuf::from(self.expect_str(value, &field)?),
+            "max_file_size" => uploads.max_file_size = self.expect_size(value, &field)?,
+            "max_total_size" => uploads.max_total_size = self.expect_size(value, &field)?,
+            "temp_directory" => uploads.temp_directory = Some(PathBuf::from(self.expect_str(value, &field)?)),
             "allowed_extensions" => {
-                uploads.allowed_extensions = Some(
-                    value.split(',').map(|s| s.trim().to_string()).collect()
-                );
+                // An empty list allows every extension
+                let extensions = self.expect_string_list(value, &field)?;
+                uploads.allowed_extensions = if extensions.is_empty() { None } else { Some(extensions) };
             }
             _ => {}
         }
         Ok(())
     }
     
-    /// Set session configuration value
-    fn set_session_value(&self, sessions: &mut SessionConfig, key: &str, value: &str) -> io::Result<()> {
+    /// Set a `[session]` value
+    fn set_session_value(&self, sessions: &mut SessionConfig, key: &str, value: &ConfigValue) -> io::Result<()> {
+        let field = format!("session.{}", key);
         match key {
-            "cookie_name" => sessions.cookie_name = value.to_string(),
-            "expiration" => sessions.expiration = self.parse_duration(value)?,
-            "secure_cookies" => sessions.secure_cookies = self.parse_bool(value),
-            "http_only" => sessions.http_only = self.parse_bool(value),
-            "same_site" => sessions.same_site = value.to_string(),
-            "cleanup_interval" => sessions.cleanup_interval = self.parse_duration(value)?,
-            "max_sessions" => sessions.max_sessions = value.parse().unwrap_or(10000),
+            "cookie_name" => sessions.cookie_name = self.expect_str(value, &field)?.to_string(),
+            "expiration" | "timeout" => sessions.expiration = self.expect_duration(value, &field)?,
+            "secure_cookies" => sessions.secure_cookies = self.expect_bool(value, &field)?,
+            "http_only" | "http_only_cookies" => sessions.http_only = self.expect_bool(value, &field)?,
+            "same_site" => sessions.same_site = self.expect_str(value, &field)?.to_string(),
+            "cleanup_interval" => sessions.cleanup_interval = self.expect_duration(value, &field)?,
+            "max_sessions" => sessions.max_sessions = self.expect_unsigned(value, &field)? as usize,
             _ => {}
         }
         Ok(())
     }
     
-    /// Set CGI configuration value
-    fn set_cgi_value(&self, cgi: &mut CgiConfig, key: &str, value: &str) -> io::Result<()> {
+    /// Set a `[cgi]` value
+    fn set_cgi_value(&self, cgi: &mut CgiConfig, key: &str, value: &ConfigValue) -> io::Result<()> {
+        let field = format!("cgi.{}", key);
         match key {
-            "enabled" => cgi.enabled = self.parse_bool(value),
-            "directory" => cgi.directory = PathBuf::from(value),
-            "timeout" => cgi.timeout = self.parse_duration(value)?,
-            "max_output_size" => cgi.max_output_size = self.parse_size(value)?,
-            k if k.starts_with("interpreter.") => {
-                let ext = &k[12..]; // Remove "interpreter." prefix
-                cgi.interpreters.insert(ext.to_string(), value.to_string());
-            }
+            "enabled" => cgi.enabled = self.expect_bool(value, &field)?,
+            "directory" | "cgi_directory" => cgi.directory = PathBuf::from(self.expect_str(value, &field)?),
+            "timeout" => cgi.timeout = self.expect_duration(value, &field)?,
+            "max_output_size" => cgi.max_output_size = self.expect_size(value, &field)?,
+            // [cgi.interpreters] table, or `interpreter.py = "..."` dotted keys
+            "interpreters" | "interpreter" => cgi.interpreters = self.expect_string_map(value, &field)?,
             _ => {}
         }
         Ok(())
     }
     
-    /// Set logging configuration value
-    fn set_logging_value(&self, logging: &mut LoggingConfig, key: &str, value: &str) -> io::Result<()> {
-        match key {
-            "level" => logging.level = value.to_string(),
-            "access_log" => logging.access_log = Some(PathBuf::from(value)),
-            "error_log" => logging.error_log = Some(PathBuf::from(value)),
-            _ => {}
+    /// Apply the `[logging]` section. File paths may be given directly
+    /// (`access_log = "access.log"`) or as a flag plus path
+    /// (`access_log = true`, `access_log_path = "..."`).
+    fn set_logging_values(&self, logging: &mut LoggingConfig, section: &HashMap<String, ConfigValue>) -> io::Result<()> {
+        if let Some(value) = section.get("level").or_else(|| section.get("log_level")) {
+            logging.level = self.expect_str(value, "logging.level")?.to_string();
+        }
+        logging.access_log = self.log_path(section, "access_log")?.or(logging.access_log.take());
+        logging.error_log = self.log_path(section, "error_log")?.or(logging.error_log.take());
+        
+        if section.get("access_log").and_then(|v| v.as_bool()) == Some(false) {
+            logging.access_log = None;
+        }
+        if section.get("error_log").and_then(|v| v.as_bool()) == Some(false) {
+            logging.error_log = None;
         }
         Ok(())
     }
     
-    /// Set security configuration value
-    fn set_security_value(&self, security: &mut SecurityConfig, key: &str, value: &str) -> io::Result<()> {
-        match key {
-            "hide_version" => security.hide_version = self.parse_bool(value),
-            "max_header_size" => security.max_header_size = self.parse_size(value)?,
-            "max_headers" => security.max_headers = value.parse().unwrap_or(100),
-            _ => {}
+    /// Resolve one log file setting from `<name>` and `<name>_path`
+    fn log_path(&self, section: &HashMap<String, ConfigValue>, name: &str) -> io::Result<Option<PathBuf>> {
+        let field = format!("logging.{}", name);
+        let path_key = format!("{}_path", name);
+        
+        let path = match section.get(&path_key) {
+            Some(value) => Some(self.expect_str(value, &format!("logging.{}", path_key))?.to_string()),
+            None => None,
+        };
+        
+        match section.get(name) {
+            Some(ConfigValue::String(path)) => Ok(Some(PathBuf::from(path))),
+            Some(ConfigValue::Boolean(enabled)) => Ok(path.filter(|_| *enabled).map(PathBuf::from)),
+            Some(other) => Err(type_error(&field, "string or boolean", other)),
+            None => Ok(path.map(PathBuf::from)),
+        }
+    }
+    
+    /// Apply the `[security]` section
+    fn set_security_values(&self, security: &mut SecurityConfig, section: &HashMap<String, ConfigValue>) -> io::Result<()> {
+        const HEADER_KEYS: [(&str, &str); 5] = [
+            ("x_frame_options", "X-Frame-Options"),
+            ("x_content_type_options", "X-Content-Type-Options"),
+            ("x_xss_protection", "X-XSS-Protection"),
+            ("hsts", "Strict-Transport-Security"),
+            ("csp", "Content-Security-Policy"),
+        ];
+        
+        for (key, value) in section {
+            let field = format!("security.{}", key);
+            match key.as_str() {
+                "hide_version" => security.hide_version = self.expect_bool(value, &field)?,
+                "max_header_size" => security.max_header_size = self.expect_size(value, &field)?,
+                "max_header_section_size" => security.max_header_section_size = self.expect_size(value, &field)?,
+                "max_headers" => security.max_headers = self.expect_unsigned(value, &field)? as usize,
+                "max_request_line" => security.max_request_line = self.expect_size(value, &field)?,
+                "max_uri_length" => security.max_uri_length = self.expect_size(value, &field)?,
+                "allow_encoded_slashes" => security.allow_encoded_slashes = self.expect_bool(value, &field)?,
+                "strict_parsing" => security.strict_parsing = self.expect_bool(value, &field)?,
+                "ip_blacklist" => security.ip_blacklist = self.expect_string_list(value, &field)?,
+                "ip_whitelist" => security.ip_whitelist = Some(self.expect_string_list(value, &field)?),
+                "headers" => security.headers.extend(self.expect_string_map(value, &field)?),
+                "rate_limiting" => self.set_rate_limit_values(&mut security.rate_limiting, value)?,
+                k => {
+                    if let Some((_, header)) = HEADER_KEYS.iter().find(|(name, _)| *name == k) {
+                        security.headers.insert(header.to_string(), self.expect_str(value, &field)?.to_string());
+                    }
+                }
+            }
+        }
+        
+        if let Some(value) = section.get("enable_security_headers") {
+            if !self.expect_bool(value, "security.enable_security_headers")? {
+                security.headers.clear();
+            }
         }
         Ok(())
     }
     
-    /// Set virtual host configuration value
-    fn set_vhost_value(&self, vhost: &mut VirtualHostConfig, key: &str, value: &str) -> io::Result<()> {
-        match key {
-            "server_name" => vhost.server_name = value.to_string(),
-            "document_root" => vhost.document_root = PathBuf::from(value),
-            "max_body_size" => vhost.max_body_size = self.parse_size(value)?,
-            "access_log" => vhost.access_log = Some(PathBuf::from(value)),
-            "error_log" => vhost.error_log = Some(PathBuf::from(value)),
-            _ => {}
+    /// Apply `[security.rate_limiting]`
+    fn set_rate_limit_values(&self, rate_limit: &mut RateLimitConfig, value: &ConfigValue) -> io::Result<()> {
+        let table = value.as_table()
+            .ok_or_else(|| type_error("security.rate_limiting", "table", value))?;
+        
+        for (key, value) in table {
+            let field = format!("security.rate_limiting.{}", key);
+            match key.as_str() {
+                "enabled" => rate_limit.enabled = self.expect_bool(value, &field)?,
+                "requests_per_minute" => rate_limit.requests_per_minute = self.expect_u32(value, &field)?,
+                "burst_size" => rate_limit.burst_size = self.expect_u32(value, &field)?,
+                "ban_duration" => rate_limit.ban_duration = self.expect_duration(value, &field)?,
+                _ => {}
+            }
         }
         Ok(())
     }
     
-    /// Set route configuration value
-    fn set_route_value(&self, route: &mut RouteConfig, key: &str, value: &str) -> io::Result<()> {
-        match key {
-            "path" => route.path = value.to_string(),
-            "methods" => {
-                route.methods = value.split(',').map(|s| s.trim().to_uppercase()).collect();
+    /// Build a `[[listener]]`
+    fn build_listener(&self, table: &HashMap<String, ConfigValue>, field: &str) -> io::Result<ListenerConfig> {
+        let address = match table.get("address") {
+            Some(value) => self.expect_str(value, &format!("{}.address", field))?.to_string(),
+            None => "127.0.0.1".to_string(),
+        };
+        
+        let port_field = format!("{}.port", field);
+        let port = table.get("port")
+            .ok_or_else(|| invalid_data(format!("Missing required key {}", port_field)))?;
+        let port = u16::try_from(self.expect_unsigned(port, &port_field)?)
+            .map_err(|_| invalid_data(format!("Invalid value for {}: port out of range", port_field)))?;
+        
+        let default = match table.get("default") {
+            Some(value) => self.expect_bool(value, &format!("{}.default", field))?,
+            None => false,
+        };
+        
+        let name = match table.get("name") {
+            Some(value) => Some(self.expect_str(value, &format!("{}.name", field))?.to_string()),
+            None => None,
+        };
+        
+        Ok(ListenerConfig { address, port, default, name })
+    }
+    
+    /// Build a `[[vhost]]` with its routes, redirects, error pages, CORS and cache settings
+    fn build_vhost(
+        &self,
+        table: &HashMap<String, ConfigValue>,
+        global: &GlobalConfig,
+        defaults: &VhostDefaults,
+        field: &str,
+    ) -> io::Result<VirtualHostConfig> {
+        let mut vhost = VirtualHostConfig::default();
+        vhost.routes.clear();
+        if let Some(max_body_size) = defaults.max_body_size {
+            vhost.max_body_size = max_body_size;
+        }
+        
+        for (key, value) in table {
+            let key_field = format!("{}.{}", field, key);
+            match key.as_str() {
+                "server_name" => vhost.server_name = self.expect_str(value, &key_field)?.to_string(),
+                "document_root" => vhost.document_root = PathBuf::from(self.expect_str(value, &key_field)?),
+                "max_body_size" => vhost.max_body_size = self.expect_size(value, &key_field)?,
+                "access_log" => vhost.access_log = Some(PathBuf::from(self.expect_str(value, &key_field)?)),
+                "error_log" => vhost.error_log = Some(PathBuf::from(self.expect_str(value, &key_field)?)),
+                "error_pages" => vhost.error_pages = self.expect_error_pages(value, &key_field)?,
+                "cors" => self.set_cors_values(&mut vhost.cors, value, &key_field)?,
+                "cache" => self.set_cache_values(&mut vhost.cache, value, &key_field)?,
+                _ => {}
             }
-            "type" => {
-                route.route_type = match value {
-                    "static" => RouteType::Static {
-                        directory_listing: false,
-                        index_files: vec!["index.html".to_string()],
-                        cache_control: Some("public, max-age=3600".to_string()),
-                    },
-                    "cgi" => RouteType::Cgi {
-                        script_dir: PathBuf::from("cgi-bin"),
-                        interpreters: HashMap::new(),
-                        timeout: Duration::from_secs(30),
-                    },
-                    "redirect" => RouteType::Redirect {
-                        target: "/".to_string(),
-                        status: 302,
-                    },
-                    _ => return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Unknown route type: {}", value),
-                    )),
-                };
+        }
+        
+        let static_cache_control = if vhost.cache.enabled {
+            Some(format!("public, max-age={}", vhost.cache.static_max_age))
+        } else {
+            None
+        };
+        
+        for (i, route) in self.nested_tables(table, "route", field)?.iter().enumerate() {
+            let route_field = format!("{}.route[{}]", field, i);
+            vhost.routes.push(self.build_route(route, global, defaults, &static_cache_control, &route_field)?);
+        }
+        
+        for (i, redirect) in self.nested_tables(table, "redirect", field)?.iter().enumerate() {
+            let redirect_field = format!("{}.redirect[{}]", field, i);
+            vhost.redirects.push(self.build_redirect(redirect, &redirect_field)?);
+        }
+        
+        Ok(vhost)
+    }
+    
+    /// Build a `[[vhost.route]]`
+    fn build_route(
+        &self,
+        table: &HashMap<String, ConfigValue>,
+        global: &GlobalConfig,
+        defaults: &VhostDefaults,
+        static_cache_control: &Option<String>,
+        field: &str,
+    ) -> io::Result<RouteConfig> {
+        let get_str = |key: &str| -> io::Result<Option<String>> {
+            match table.get(key) {
+                Some(value) => Ok(Some(self.expect_str(value, &format!("{}.{}", field, key))?.to_string())),
+                None => Ok(None),
+            }
+        };
+        
+        let mut route = RouteConfig {
+            path: get_str("path")?
+                .ok_or_else(|| invalid_data(format!("Missing required key {}.path", field)))?,
+            ..Default::default()
+        };
+        
+        if let Some(value) = table.get("methods") {
+            route.methods = self.expect_string_list(value, &format!("{}.methods", field))?
+                .into_iter()
+                .map(|m| m.to_uppercase())
+                .collect();
+        }
+        
+        let route_type = get_str("type")?.unwrap_or_else(|| "static".to_string());
+        route.route_type = match route_type.as_str() {
+            "static" => RouteType::Static {
+                directory_listing: match table.get("directory_listing") {
+                    Some(value) => self.expect_bool(value, &format!("{}.directory_listing", field))?,
+                    None => defaults.directory_listing.unwrap_or(false),
+                },
+                index_files: match table.get("index_files") {
+                    Some(value) => self.expect_string_list(value, &format!("{}.index_files", field))?,
+                    None => defaults.index_files.clone().unwrap_or_else(|| vec!["index.html".to_string()]),
+                },
+                cache_control: get_str("cache_control")?.or_else(|| static_cache_control.clone()),
+            },
+            "cgi" => RouteType::Cgi {
+                script_dir: get_str("script_dir")?.map(PathBuf::from).unwrap_or_else(|| global.cgi.directory.clone()),
+                interpreters: match table.get("interpreters") {
+                    Some(value) => self.expect_string_map(value, &format!("{}.interpreters", field))?,
+                    None => global.cgi.interpreters.clone(),
+                },
+                timeout: match table.get("timeout") {
+                    Some(value) => self.expect_duration(value, &format!("{}.timeout", field))?,
+                    None => global.cgi.timeout,
+                },
+            },
+            "redirect" => RouteType::Redirect {
+                target: get_str("target")?.or(get_str("to")?)
+                    .ok_or_else(|| invalid_data(format!("Missing required key {}.target", field)))?,
+                status: match table.get("status") {
+                    Some(value) => self.expect_status(value, &format!("{}.status", field))?,
+                    None => 302,
+                },
+            },
+            "session" => RouteType::Session,
+            "proxy" => RouteType::Proxy {
+                backend: get_str("backend")?
+                    .ok_or_else(|| invalid_data(format!("Missing required key {}.backend", field)))?,
+                timeout: match table.get("timeout") {
+                    Some(value) => self.expect_duration(value, &format!("{}.timeout", field))?,
+                    None => Duration::from_secs(30),
+                },
+            },
+            other => return Err(invalid_data(format!("Unknown route type in {}.type: {}", field, other))),
+        };
+        
+        for (key, value) in table {
+            let key_field = format!("{}.{}", field, key);
+            match key.as_str() {
+                "max_body_size" => route.settings.max_body_size = Some(self.expect_size(value, &key_field)?),
+                "error_pages" => route.settings.error_pages = self.expect_error_pages(value, &key_field)?,
+                "auth_required" => route.settings.auth_required = self.expect_bool(value, &key_field)?,
+                "rate_limit" => route.settings.rate_limit = Some(self.expect_u32(value, &key_field)?),
+                "headers" => route.settings.custom_headers = self.expect_string_map(value, &key_field)?,
+                "upload_dir" => route.settings.upload_dir = Some(PathBuf::from(self.expect_str(value, &key_field)?)),
+                _ => {}
+            }
+        }
+        
+        Ok(route)
+    }
+    
+    /// Build a `[[vhost.redirect]]`
+    fn build_redirect(&self, table: &HashMap<String, ConfigValue>, field: &str) -> io::Result<RedirectConfig> {
+        let required = |key: &str| -> io::Result<String> {
+            let value = table.get(key)
+                .ok_or_else(|| invalid_data(format!("Missing required key {}.{}", field, key)))?;
+            Ok(self.expect_str(value, &format!("{}.{}", field, key))?.to_string())
+        };
+        
+        let status = match (table.get("status"), table.get("type")) {
+            (Some(value), _) => self.expect_status(value, &format!("{}.status", field))?,
+            (None, Some(value)) => match self.expect_str(value, &format!("{}.type", field))? {
+                "permanent" => 301,
+                "temporary" => 302,
+                "see_other" => 303,
+                "temporary_preserve" => 307,
+                "permanent_preserve" => 308,
+                other => return Err(invalid_data(format!("Unknown redirect type in {}.type: {}", field, other))),
+            },
+            (None, None) => 302,
+        };
+        
+        Ok(RedirectConfig {
+            from: required("from")?,
+            to: required("to")?,
+            status,
+        })
+    }
+    
+    /// Apply `[vhost.cors]`
+    fn set_cors_values(&self, cors: &mut CorsConfig, value: &ConfigValue, field: &str) -> io::Result<()> {
+        let table = value.as_table().ok_or_else(|| type_error(field, "table", value))?;
+        
+        for (key, value) in table {
+            let key_field = format!("{}.{}", field, key);
+            match key.as_str() {
+                "enabled" => cors.enabled = self.expect_bool(value, &key_field)?,
+                "allowed_origins" => cors.allowed_origins = self.expect_string_list(value, &key_field)?,
+                "allowed_methods" => cors.allowed_methods = self.expect_string_list(value, &key_field)?,
+                "allowed_headers" => cors.allowed_headers = self.expect_string_list(value, &key_field)?,
+                "exposed_headers" => cors.exposed_headers = self.expect_string_list(value, &key_field)?,
+                "allow_credentials" => cors.allow_credentials = self.expect_bool(value, &key_field)?,
+                "max_age" => cors.max_age = Some(self.expect_u32(value, &key_field)?),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    
+    /// Apply `[vhost.cache]`
+    fn set_cache_values(&self, cache: &mut CacheConfig, value: &ConfigValue, field: &str) -> io::Result<()> {
+        let table = value.as_table().ok_or_else(|| type_error(field, "table", value))?;
+        
+        for (key, value) in table {
+            let key_field = format!("{}.{}", field, key);
+            match key.as_str() {
+                "enabled" => cache.enabled = self.expect_bool(value, &key_field)?,
+                "static_max_age" => cache.static_max_age = self.expect_u32(value, &key_field)?,
+                "dynamic_max_age" => cache.dynamic_max_age = self.expect_u32(value, &key_field)?,
+                "etag" => cache.etag = self.expect_bool(value, &key_field)?,
+                "last_modified" => cache.last_modified = self.expect_bool(value, &key_field)?,
+                _ => {}
             }
-            _ => {}
         }
         Ok(())
     }
     
+    /// Get a top-level section, which must be a table if present
+    fn section<'a>(&self, root: &'a HashMap<String, ConfigValue>, name: &str) -> io::Result<Option<&'a HashMap<String, ConfigValue>>> {
+        match root.get(name) {
+            Some(value) => value.as_table().map(Some).ok_or_else(|| type_error(name, "table", value)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Get a top-level array of tables (`[[name]]`)
+    fn array_of_tables<'a>(&self, root: &'a HashMap<String, ConfigValue>, name: &str) -> io::Result<Vec<&'a HashMap<String, ConfigValue>>> {
+        self.nested_tables(root, name, "")
+    }
+    
+    /// Get an array of tables nested under `parent`
+    fn nested_tables<'a>(&self, parent: &'a HashMap<String, ConfigValue>, name: &str, field: &str) -> io::Result<Vec<&'a HashMap<String, ConfigValue>>> {
+        let field = if field.is_empty() { name.to_string() } else { format!("{}.{}", field, name) };
+        match parent.get(name) {
+            Some(ConfigValue::Array(items)) => items.iter()
+                .map(|item| item.as_table().ok_or_else(|| type_error(&field, "array of tables", item)))
+                .collect(),
+            Some(other) => Err(type_error(&field, "array of tables", other)),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    fn expect_str<'a>(&self, value: &'a ConfigValue, field: &str) -> io::Result<&'a str> {
+        value.as_str().ok_or_else(|| type_error(field, "string", value))
+    }
+    
+    /// Booleans, or strings accepted by `parse_bool`
+    fn expect_bool(&self, value: &ConfigValue, field: &str) -> io::Result<bool> {
+        match value {
+            ConfigValue::Boolean(b) => Ok(*b),
+            ConfigValue::String(s) => self.parse_bool(s)
+                .ok_or_else(|| invalid_data(format!("Invalid value for {}: expected a boolean, found '{}'", field, s))),
+            other => Err(type_error(field, "boolean", other)),
+        }
+    }
+    
+    fn expect_unsigned(&self, value: &ConfigValue, field: &str) -> io::Result<u64> {
+        match value.as_integer() {
+            Some(n) if n >= 0 => Ok(n as u64),
+            Some(_) => Err(invalid_data(format!("Invalid value for {}: must not be negative", field))),
+            None => Err(type_error(field, "integer", value)),
+        }
+    }
+    
+    fn expect_u32(&self, value: &ConfigValue, field: &str) -> io::Result<u32> {
+        u32::try_from(self.expect_unsigned(value, field)?)
+            .map_err(|_| invalid_data(format!("Invalid value for {}: out of range", field)))
+    }
+    
+    fn expect_status(&self, value: &ConfigValue, field: &str) -> io::Result<u16> {
+        match u16::try_from(self.expect_unsigned(value, field)?) {
+            Ok(status) if (100..=599).contains(&status) => Ok(status),
+            _ => Err(invalid_data(format!("Invalid value for {}: not an HTTP status code", field))),
+        }
+    }
+    
+    /// Sizes are byte counts or strings like "10MB"
+    fn expect_size(&self, value: &ConfigValue, field: &str) -> io::Result<usize> {
+        match value {
+            ConfigValue::String(s) => self.parse_size(s)
+                .map_err(|_| invalid_data(format!("Invalid size for {}: {}", field, s))),
+            _ => Ok(self.expect_unsigned(value, field)? as usize),
+        }
+    }
+    
+    /// Durations are seconds or strings like "30s", "5m"
+    fn expect_duration(&self, value: &ConfigValue, field: &str) -> io::Result<Duration> {
+        match value {
+            ConfigValue::String(s) => self.parse_duration(s)
+                .map_err(|_| invalid_data(format!("Invalid duration for {}: {}", field, s))),
+            _ => Ok(Duration::from_secs(self.expect_unsigned(value, field)?)),
+        }
+    }
+    
+    /// Arrays of strings, or a comma-separated string
+    fn expect_string_list(&self, value: &ConfigValue, field: &str) -> io::Result<Vec<String>> {
+        match value {
+            ConfigValue::Array(items) => items.iter()
+                .map(|item| self.expect_str(item, field).map(|s| s.to_string()))
+                .collect(),
+            ConfigValue::String(s) => Ok(s.split(',')
+                .map(|part| part.trim().to_string())
+                .filter(|part| !part.is_empty())
+                .collect()),
+            other => Err(type_error(field, "array of strings", other)),
+        }
+    }
+    
+    /// Tables whose values are all strings
+    fn expect_string_map(&self, value: &ConfigValue, field: &str) -> io::Result<HashMap<String, String>> {
+        let table = value.as_table().ok_or_else(|| type_error(field, "table", value))?;
+        table.iter()
+            .map(|(key, value)| {
+                let s = self.expect_str(value, &format!("{}.{}", field, key))?;
+                Ok((key.clone(), s.to_string()))
+            })
+            .collect()
+    }
+    
+    /// Error page tables map status codes to file paths
+    fn expect_error_pages(&self, value: &ConfigValue, field: &str) -> io::Result<HashMap<u16, PathBuf>> {
+        self.expect_string_map(value, field)?
+            .into_iter()
+            .map(|(code, path)| {
+                let status = code.parse::<u16>()
+                    .map_err(|_| invalid_data(format!("Invalid status code in {}: {}", field, code)))?;
+                Ok((status, PathBuf::from(path)))
+            })
+            .collect()
+    }
+    
     /// Parse duration from string (e.g., "30s", "5m", "1h")
     fn parse_duration(&self, value: &str) -> io::Result<Duration> {
-        if value.ends_with('s') {
-            let secs = value[..value.len()-1].parse::<u64>()
+        if let Some(secs) = value.strip_suffix('s') {
+            let secs = secs.parse::<u64>()
                 .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid duration"))?;
             Ok(Duration::from_secs(secs))
-        } else if value.ends_with('m') {
-            let mins = value[..value.len()-1].parse::<u64>()
+        } else if let Some(mins) = value.strip_suffix('m') {
+            let mins = mins.parse::<u64>()
                 .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid duration"))?;
             Ok(Duration::from_secs(mins * 60))
-        } else if value.ends_with('h') {
-            let hours = value[..value.len()-1].parse::<u64>()
+        } else if let Some(hours) = value.strip_suffix('h') {
+            let hours = hours.parse::<u64>()
                 .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid duration"))?;
             Ok(Duration::from_secs(hours * 3600))
         } else {
@@ -393,11 +794,12 @@ impl ConfigParser {
         }
     }
     
-    /// Parse boolean from string
-    fn parse_bool(&self, value: &str) -> bool {
+    /// Parse boolean from string, `None` if it is not one
+    fn parse_bool(&self, value: &str) -> Option<bool> {
         match value.to_lowercase().as_str() {
-            "true" | "yes" | "1" | "on" => true,
-            _ => false,
+            "true" | "yes" | "1" | "on" => Some(true),
+            "false" | "no" | "0" | "off" => Some(false),
+            _ => None,
         }
     }
     
@@ -407,18 +809,15 @@ impl ConfigParser {
 # This is an example configuration file in TOML format
 
 [server]
-# Default virtual host
-default_host = "localhost"
-
-# Server listeners (can have multiple)
-listen = "127.0.0.1:8080"
-listen = "0.0.0.0:8080"
+# Server name shown in response headers
+name = "localhost"
+version = "1.0"
 
 [global]
-# Server identification
-server_name = "localhost/1.0"
-# Number of worker processes (always 1 for this server)
-workers = 1
+# Defaults for virtual hosts and static routes
+max_body_size = "10MB"
+directory_listing = false
+index_files = ["index.html", "index.htm"]
 
 [timeouts]
 # Connection timeouts
@@ -428,14 +827,14 @@ write = "5s"
 keep_alive = "10s"
 request = "30s"
 
-[uploads]
+[upload]
 # File upload settings
 directory = "./uploads"
 max_file_size = "10MB"
 max_total_size = "100MB"
-allowed_extensions = "jpg,png,gif,pdf,txt,zip"
+allowed_extensions = ["jpg", "png", "gif", "pdf", "txt", "zip"]
 
-[sessions]
+[session]
 # Session management
 cookie_name = "session_id"
 expiration = "1h"
@@ -448,72 +847,103 @@ max_sessions = 10000
 [cgi]
 # CGI script execution
 enabled = true
-directory = "./cgi-bin"
+directory = "./www/cgi-bin"
 timeout = "30s"
 max_output_size = "1MB"
 
-# CGI interpreters
-interpreter.py = "python3"
-interpreter.pl = "perl"
-interpreter.sh = "sh"
-interpreter.rb = "ruby"
-interpreter.php = "php"
+# CGI interpreters by file extension
+[cgi.interpreters]
+py = "python3"
+pl = "perl"
+sh = "sh"
+rb = "ruby"
+php = "php"
 
 [logging]
 # Logging configuration
 level = "info"
-access_log = "access.log"
-error_log = "error.log"
 
 [security]
 # Security settings
 hide_version = false
 max_header_size = "8KB"
 max_headers = 100
+x_content_type_options = "nosniff"
+
+# Server listeners (can have multiple)
+[[listener]]
+address = "127.0.0.1"
+port = 8080
+default = true
+
+[[listener]]
+address = "127.0.0.1"
+port = 8081
 
 # Virtual host configuration
-[vhost.localhost]
+[[vhost]]
 server_name = "localhost"
 document_root = "./www"
-max_body_size = "10MB"
-access_log = "localhost_access.log"
-error_log = "localhost_error.log"
+default = true
+
+[vhost.error_pages]
+404 = "./www/error_pages/404.html"
+500 = "./www/error_pages/500.html"
 
-# Route configuration for localhost
-[route.root]
+# Routes for localhost
+[[vhost.route]]
 path = "/"
-methods = "GET,POST,HEAD"
+methods = ["GET", "POST", "HEAD"]
 type = "static"
 
-[route.cgi]
+[[vhost.route]]
 path = "/cgi-bin/*"
-methods = "GET,POST"
+methods = ["GET", "POST"]
 type = "cgi"
 
-[route.uploads]
-path = "/uploads/*"
-methods = "GET,POST,DELETE"
-type = "static"
+[[vhost.route]]
+path = "/docs"
+type = "redirect"
+target = "/"
+status = 301
+
+[[vhost.redirect]]
+from = "/old-page"
+to = "/new-page"
+type = "permanent"
 
 # Another virtual host example
-[vhost.example.com]
+[[vhost]]
 server_name = "example.com"
 document_root = "./sites/example"
 max_body_size = "5MB"
 
-[route.example_root]
+[[vhost.route]]
 path = "/"
-methods = "GET,HEAD"
+methods = ["GET", "HEAD"]
 type = "static"
-
-[route.api]
-path = "/api/*"
-methods = "GET,POST,PUT,DELETE"
-type = "cgi"
+directory_listing = true
 "#.to_string()
     }
 }
 
+/// Defaults from `[global]` applied to virtual hosts and static routes
+/// that do not set their own
+#[derive(Debug, Default)]
+struct VhostDefaults {
+    max_body_size: Option<usize>,
+    directory_listing: Option<bool>,
+    index_files: Option<Vec<String>>,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn type_error(field: &str, expected: &str, found: &ConfigValue) -> io::Error {
+    invalid_data(format!("Invalid value for {}: expected {}, found {}", field, expected, found.type_name()))
+}
+
 impl Default for ConfigParser {
     fn default() -> Self {
         Self::new(ConfigFormat::Auto)
@@ -554,22 +984,32 @@ mod tests {
     fn test_parse_bool() {
         let parser = ConfigParser::default();
         
-        assert!(parser.parse_bool("true"));
-        assert!(parser.parse_bool("yes"));
-        assert!(parser.parse_bool("1"));
-        assert!(parser.parse_bool("on"));
-        assert!(!parser.parse_bool("false"));
-        assert!(!parser.parse_bool("no"));
-        assert!(!parser.parse_bool("0"));
+        assert_eq!(parser.parse_bool("true"), Some(true));
+        assert_eq!(parser.parse_bool("yes"), Some(true));
+        assert_eq!(parser.parse_bool("1"), Some(true));
+        assert_eq!(parser.parse_bool("on"), Some(true));
+        assert_eq!(parser.parse_bool("false"), Some(false));
+        assert_eq!(parser.parse_bool("no"), Some(false));
+        assert_eq!(parser.parse_bool("0"), Some(false));
+        assert_eq!(parser.parse_bool("maybe"), None);
+        
+        let config = "[[listener]]\nport = 8080\ndefault = \"maybe\"\n";
+        assert!(parser.parse_content(config, ConfigFormat::Toml).is_err());
     }
     
     #[test]
-    fn test_parse_value() {
+    fn test_server_name_and_version() {
         let parser = ConfigParser::default();
+        let server_name = |section: &str| {
+            parser.parse_content(section, ConfigFormat::Toml).unwrap().global.server_name
+        };
         
-        assert_eq!(parser.parse_value("\"hello\""), "hello");
-        assert_eq!(parser.parse_value("'world'"), "world");
-        assert_eq!(parser.parse_value("test"), "test");
+        // Every parse builds a new map, so key order varies between runs
+        for _ in 0..20 {
+            assert_eq!(server_name("[server]\nname = \"edge\"\nversion = \"1.0\"\n"), "edge/1.0");
+        }
+        assert_eq!(server_name("[server]\nname = \"edge\"\n"), "edge");
+        assert_eq!(server_name("[server]\nversion = \"2.0\"\n"), "localhost/2.0");
     }
     
     #[test]
@@ -586,7 +1026,245 @@ mod tests {
         let example = ConfigParser::generate_example_config();
         assert!(example.contains("[server]"));
         assert!(example.contains("[global]"));
-        assert!(example.contains("[vhost.localhost]"));
-        assert!(example.contains("listen = \"127.0.0.1:8080\""));
+        assert!(example.contains("[[vhost]]"));
+        assert!(example.contains("[[listener]]"));
+        
+        let config = ConfigParser::default().parse_content(&example, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.listeners.len(), 2);
+        assert_eq!(config.virtual_hosts.len(), 2);
+        assert_eq!(config.default_host.as_deref(), Some("localhost"));
+        assert_eq!(config.virtual_hosts[1].max_body_size, 5 * 1024 * 1024);
+    }
+    
+    #[test]
+    fn test_parse_shipped_server_toml() {
+        let content = include_str!("../../server.toml");
+        let config = ConfigParser::default().parse_content(content, ConfigFormat::Toml).unwrap();
+        
+        assert_eq!(config.global.server_name, "Localhost/1.0.0");
+        assert_eq!(config.global.timeouts.request, Duration::from_secs(30));
+        assert_eq!(config.global.sessions.cookie_name, "SESSIONID");
+        assert_eq!(config.global.sessions.expiration, Duration::from_secs(3600));
+        assert_eq!(config.global.cgi.directory, PathBuf::from("./www/cgi-bin"));
+        assert_eq!(config.global.cgi.interpreters.get("py").map(String::as_str), Some("/usr/bin/env python3"));
+        assert_eq!(config.global.logging.access_log, Some(PathBuf::from("./logs/access.log")));
+        assert_eq!(config.global.security.headers.get("X-Frame-Options").map(String::as_str), Some("SAMEORIGIN"));
+        
+        assert_eq!(config.listeners.len(), 1);
+        assert_eq!(config.listeners[0].port, 8080);
+        assert!(config.listeners[0].default);
+        
+        assert_eq!(config.virtual_hosts.len(), 1);
+        assert_eq!(config.default_host.as_deref(), Some("localhost"));
+        
+        let vhost = &config.virtual_hosts[0];
+        assert_eq!(vhost.max_body_size, 10485760);
+        assert_eq!(vhost.error_pages.get(&404), Some(&PathBuf::from("./www/error_pages/404.html")));
+        assert_eq!(vhost.routes.len(), 5);
+        assert_eq!(vhost.routes[0].methods, vec!["GET", "HEAD"]);
+        assert!(matches!(vhost.routes[3].route_type, RouteType::Cgi { .. }));
+        assert_eq!(vhost.routes[1].settings.max_body_size, Some(10485760));
+        
+        let statuses: Vec<u16> = vhost.redirects.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![301, 301, 302, 307, 308]);
+        assert!(vhost.cors.enabled);
+        assert_eq!(vhost.cors.max_age, Some(3600));
+        assert_eq!(vhost.cache.static_max_age, 3600);
+    }
+    
+    #[test]
+    fn test_parse_toml_route_types() {
+        let content = r#"
+            [[vhost]]
+            server_name = "a.local"
+            
+            [[vhost.route]]
+            path = "/old"
+            type = "redirect"
+            target = "/new"
+            status = 308
+            
+            [[vhost.route]]
+            path = "/cgi/*"
+            type = "cgi"
+            script_dir = "./scripts"
+            interpreters = { py = "python3" }
+            
+            [[vhost]]
+            server_name = "b.local"
+            default = true
+        "#;
+        let config = ConfigParser::default().parse_content(content, ConfigFormat::Toml).unwrap();
+        
+        assert_eq!(config.default_host.as_deref(), Some("b.local"));
+        match &config.virtual_hosts[0].routes[0].route_type {
+            RouteType::Redirect { target, status } => {
+                assert_eq!(target, "/new");
+                assert_eq!(*status, 308);
+            }
+            other => panic!("expected redirect route, got {:?}", other),
+        }
+        match &config.virtual_hosts[0].routes[1].route_type {
+            RouteType::Cgi { script_dir, interpreters, .. } => {
+                assert_eq!(script_dir, &PathBuf::from("./scripts"));
+                assert_eq!(interpreters.len(), 1);
+            }
+            other => panic!("expected cgi route, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_parse_toml_type_errors() {
+        let parser = ConfigParser::default();
+        
+        let err = parser.parse_content("[[listener]]\nport = \"8080\"", ConfigFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("listener[0].port: expected integer, found string"));
+        
+        let err = parser.parse_content("[[vhost]]\n[[vhost.route]]\npath = \"/\"\ntype = \"ftp\"", ConfigFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("Unknown route type"));
+        
+        let err = parser.parse_content("[server\nname = 1", ConfigFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+    }
+    
+    /// Serialize a value tree as JSON, for the round-trip tests
+    fn to_json(value: &ConfigValue) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        match value {
+            ConfigValue::String(s) => quote(s),
+            ConfigValue::Integer(i) => i.to_string(),
+            ConfigValue::Float(f) => format!("{:?}", f),
+            ConfigValue::Boolean(b) => b.to_string(),
+            ConfigValue::Array(items) => {
+                format!("[{}]", items.iter().map(to_json).collect::<Vec<_>>().join(", "))
+            }
+            ConfigValue::Table(table) => {
+                let fields: Vec<String> = table.iter()
+                    .map(|(key, value)| format!("{}: {}", quote(key), to_json(value)))
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+        }
+    }
+    
+    #[test]
+    fn test_parse_json_matches_toml() {
+        let toml_content = r#"
+            [server]
+            name = "edge"
+            
+            [security]
+            x_frame_options = "DENY"
+            ip_blacklist = ["10.0.0.1"]
+            
+            [security.rate_limiting]
+            enabled = true
+            requests_per_minute = 120
+            
+            [[listener]]
+            address = "0.0.0.0"
+            port = 8080
+            default = true
+            
+            [[listener]]
+            port = 8443
+            
+            [[vhost]]
+            server_name = "api.local"
+            document_root = "./www"
+            max_body_size = "2MB"
+            error_pages = { 404 = "./www/error_pages/404.html" }
+            cors = { enabled = true, allowed_origins = ["https://app.local"], max_age = 600 }
+            cache = { enabled = false, etag = false }
+            
+            [[vhost.route]]
+            path = "/"
+            methods = ["GET", "HEAD"]
+            type = "static"
+            
+            [[vhost.route]]
+            path = "/cgi-bin/*"
+            type = "cgi"
+            timeout = 10
+            
+            [[vhost.redirect]]
+            from = "/old"
+            to = "/new"
+            type = "permanent_preserve"
+        "#;
+        let json_content = r#"{
+            "server": {"name": "edge"},
+            "security": {
+                "x_frame_options": "DENY",
+                "ip_blacklist": ["10.0.0.1"],
+                "rate_limiting": {"enabled": true, "requests_per_minute": 120}
+            },
+            "listener": [
+                {"address": "0.0.0.0", "port": 8080, "default": true},
+                {"port": 8443}
+            ],
+            "vhost": [{
+                "server_name": "api.local",
+                "document_root": "./www",
+                "max_body_size": "2MB",
+                "error_pages": {"404": "./www/error_pages/404.html"},
+                "cors": {"enabled": true, "allowed_origins": ["https://app.local"], "max_age": 600},
+                "cache": {"enabled": false, "etag": false},
+                "route": [
+                    {"path": "/", "methods": ["GET", "HEAD"], "type": "static"},
+                    {"path": "/cgi-bin/*", "type": "cgi", "timeout": 10}
+                ],
+                "redirect": [
+                    {"from": "/old", "to": "/new", "type": "permanent_preserve"}
+                ]
+            }]
+        }"#;
+        
+        let parser = ConfigParser::default();
+        let from_toml = parser.parse_content(toml_content, ConfigFormat::Toml).unwrap();
+        let from_json = parser.parse_content(json_content, ConfigFormat::Json).unwrap();
+        
+        assert_eq!(from_toml, from_json);
+        assert_eq!(from_json.listeners.len(), 2);
+        assert_eq!(from_json.virtual_hosts[0].redirects[0].status, 308);
+        assert_eq!(from_json.virtual_hosts[0].cors.allowed_origins, vec!["https://app.local"]);
+        assert!(from_json.global.security.rate_limiting.enabled);
+        
+        // Auto-detection reads a leading `{` as JSON and anything else as TOML
+        assert_eq!(parser.parse_content(json_content, ConfigFormat::Auto).unwrap(), from_json);
+        assert_eq!(parser.parse_content(toml_content, ConfigFormat::Auto).unwrap(), from_toml);
+        let err = parser.parse_content("[server]\nname = \"edge\n", ConfigFormat::Auto).unwrap_err();
+        assert!(err.to_string().contains("TOML parse error at line 2"), "{}", err);
+        let err = parser.parse_content("\n  {\"server\": 1,}", ConfigFormat::Auto).unwrap_err();
+        assert!(!err.to_string().contains("TOML"), "{}", err);
+    }
+    
+    #[test]
+    fn test_server_toml_round_trips_through_json() {
+        let content = include_str!("../../server.toml");
+        let document = toml::parse(content).unwrap();
+        let json_content = to_json(&document);
+        
+        assert_eq!(json::parse(&json_content).unwrap(), document);
+        
+        let parser = ConfigParser::default();
+        assert_eq!(
+            parser.parse_content(content, ConfigFormat::Toml).unwrap(),
+            parser.parse_content(&json_content, ConfigFormat::Json).unwrap(),
+        );
+    }
+    
+    #[test]
+    fn test_parse_json_type_errors() {
+        let parser = ConfigParser::default();
+        
+        let err = parser.parse_content("[1, 2]", ConfigFormat::Json).unwrap_err();
+        assert!(err.to_string().contains("root must be a table"), "{}", err);
+        
+        let err = parser.parse_content("{\"listener\": {\"port\": 80}}", ConfigFormat::Json).unwrap_err();
+        assert!(err.to_string().contains("listener: expected array of tables, found table"), "{}", err);
+        
+        let err = parser.parse_content("{\"server\": {\n\"name\": }}", ConfigFormat::Json).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
     }
 }
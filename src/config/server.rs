@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 /// Main server configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ServerConfig {
     /// Server listeners (IP:port combinations)
     pub listeners: Vec<ListenerConfig>,
@@ -16,7 +16,7 @@ pub struct ServerConfig {
 }
 
 /// Listener configuration (IP:port binding)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ListenerConfig {
     /// IP address to bind to
     pub address: String,
@@ -24,10 +24,13 @@ pub struct ListenerConfig {
     pub port: u16,
     /// Whether this is the default listener
     pub default: bool,
+    /// Name of a socket passed in by systemd (`FileDescriptorName=`) to
+    /// serve on instead of binding the address
+    pub name: Option<String>,
 }
 
 /// Virtual host configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct VirtualHostConfig {
     /// Server name (domain)
     pub server_name: String,
@@ -39,14 +42,65 @@ pub struct VirtualHostConfig {
     pub error_pages: HashMap<u16, PathBuf>,
     /// Maximum request body size
     pub max_body_size: usize,
+    /// Redirect rules, checked before routes
+    pub redirects: Vec<RedirectConfig>,
+    /// Cross-origin resource sharing settings
+    pub cors: CorsConfig,
+    /// Response caching settings
+    pub cache: CacheConfig,
     /// Access log file
     pub access_log: Option<PathBuf>,
     /// Error log file
     pub error_log: Option<PathBuf>,
 }
 
+/// Redirect rule (`[[vhost.redirect]]`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedirectConfig {
+    /// Path pattern to redirect (e.g., "/old-page", "/legacy/*")
+    pub from: String,
+    /// Target URL or path
+    pub to: String,
+    /// HTTP status code (301, 302, 303, 307 or 308)
+    pub status: u16,
+}
+
+/// CORS configuration (`[vhost.cors]`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsConfig {
+    /// Whether CORS headers are sent
+    pub enabled: bool,
+    /// Allowed origins ("*" for any)
+    pub allowed_origins: Vec<String>,
+    /// Allowed request methods
+    pub allowed_methods: Vec<String>,
+    /// Allowed request headers
+    pub allowed_headers: Vec<String>,
+    /// Response headers exposed to scripts
+    pub exposed_headers: Vec<String>,
+    /// Whether credentials are allowed
+    pub allow_credentials: bool,
+    /// Preflight cache lifetime in seconds
+    pub max_age: Option<u32>,
+}
+
+/// Cache configuration (`[vhost.cache]`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    /// Whether caching headers are sent
+    pub enabled: bool,
+    /// max-age for static files in seconds
+    pub static_max_age: u32,
+    /// max-age for dynamic content in seconds
+    pub dynamic_max_age: u32,
+    /// Send ETag headers
+    pub etag: bool,
+    /// Send Last-Modified headers
+    pub last_modified: bool,
+}
+
 /// Route configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RouteConfig {
     /// Path pattern (e.g., "/api/*", "/static/")
     pub path: String,
@@ -59,7 +113,7 @@ pub struct RouteConfig {
 }
 
 /// Route type enumeration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum RouteType {
     /// Static file serving
     Static {
@@ -86,7 +140,9 @@ pub enum RouteType {
         /// HTTP status code (301, 302, etc.)
         status: u16,
     },
-    /// Reverse proxy (future feature)
+    /// Session endpoints (create, info, destroy, set/<key>, get/<key>, stats)
+    Session,
+    /// Reverse proxy (future feature, rejected by the validator)
     Proxy {
         /// Backend server URL
         backend: String,
@@ -96,7 +152,7 @@ pub enum RouteType {
 }
 
 /// Route-specific settings
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RouteSettings {
     /// Maximum request body size for this route
     pub max_body_size: Option<usize>,
@@ -108,14 +164,16 @@ pub struct RouteSettings {
     pub rate_limit: Option<u32>,
     /// Custom headers to add
     pub custom_headers: HashMap<String, String>,
+    /// Directory PUT writes into (defaults to the global upload directory)
+    pub upload_dir: Option<PathBuf>,
 }
 
 /// Global server configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GlobalConfig {
     /// Server identification string
     pub server_name: String,
-    /// Worker process/thread count
+    /// Number of worker threads, each running its own event loop
     pub workers: usize,
     /// Connection timeouts
     pub timeouts: TimeoutConfig,
@@ -132,7 +190,7 @@ pub struct GlobalConfig {
 }
 
 /// Timeout configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TimeoutConfig {
     /// Read header timeout
     pub read_header: Duration,
@@ -144,10 +202,12 @@ pub struct TimeoutConfig {
     pub keep_alive: Duration,
     /// Overall request timeout
     pub request: Duration,
+    /// How long in-flight requests may take to finish on shutdown
+    pub shutdown: Duration,
 }
 
 /// Upload configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UploadConfig {
     /// Upload directory
     pub directory: PathBuf,
@@ -162,7 +222,7 @@ pub struct UploadConfig {
 }
 
 /// Session configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SessionConfig {
     /// Session cookie name
     pub cookie_name: String,
@@ -181,7 +241,7 @@ pub struct SessionConfig {
 }
 
 /// CGI configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CgiConfig {
     /// Whether CGI is enabled
     pub enabled: bool,
@@ -196,7 +256,7 @@ pub struct CgiConfig {
 }
 
 /// Logging configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LoggingConfig {
     /// Log level (error, warn, info, debug)
     pub level: String,
@@ -209,7 +269,7 @@ pub struct LoggingConfig {
 }
 
 /// Log rotation configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LogRotationConfig {
     /// Maximum log file size
     pub max_size: usize,
@@ -220,14 +280,26 @@ pub struct LogRotationConfig {
 }
 
 /// Security configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SecurityConfig {
     /// Hide server version in headers
     pub hide_version: bool,
-    /// Maximum request header size
+    /// Maximum size of a single request header line
     pub max_header_size: usize,
+    /// Maximum size of the whole request header section
+    pub max_header_section_size: usize,
     /// Maximum number of headers
     pub max_headers: usize,
+    /// Maximum request line length
+    pub max_request_line: usize,
+    /// Maximum request target (URI) length
+    pub max_uri_length: usize,
+    /// Accept `%2F` in request paths instead of answering 400
+    pub allow_encoded_slashes: bool,
+    /// Reject requests RFC 9112 allows a server to reject instead of repairing
+    pub strict_parsing: bool,
+    /// Extra headers added to every response (X-Frame-Options, ...)
+    pub headers: HashMap<String, String>,
     /// Rate limiting settings
     pub rate_limiting: RateLimitConfig,
     /// IP blacklist
@@ -237,7 +309,7 @@ pub struct SecurityConfig {
 }
 
 /// Rate limiting configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RateLimitConfig {
     /// Whether rate limiting is enabled
     pub enabled: bool,
@@ -256,6 +328,7 @@ impl Default for ServerConfig {
                 address: "127.0.0.1".to_string(),
                 port: 8080,
                 default: true,
+                name: None,
             }],
             virtual_hosts: vec![VirtualHostConfig::default()],
             default_host: Some("localhost".to_string()),
@@ -272,12 +345,41 @@ impl Default for VirtualHostConfig {
             routes: vec![RouteConfig::default()],
             error_pages: HashMap::new(),
             max_body_size: 10 * 1024 * 1024, // 10MB
+            redirects: Vec::new(),
+            cors: CorsConfig::default(),
+            cache: CacheConfig::default(),
             access_log: None,
             error_log: None,
         }
     }
 }
 
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            enabled: false,
+            allowed_origins: vec!["*".to_string()],
+            allowed_methods: vec!["GET".to_string(), "POST".to_string(), "HEAD".to_string()],
+            allowed_headers: vec!["Content-Type".to_string()],
+            exposed_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: Some(86400), // 24 hours
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            static_max_age: 3600, // 1 hour
+            dynamic_max_age: 0,
+            etag: true,
+            last_modified: true,
+        }
+    }
+}
+
 impl Default for RouteConfig {
     fn default() -> Self {
         RouteConfig {
@@ -301,6 +403,7 @@ impl Default for RouteSettings {
             auth_required: false,
             rate_limit: None,
             custom_headers: HashMap::new(),
+            upload_dir: None,
         }
     }
 }
@@ -309,7 +412,7 @@ impl Default for GlobalConfig {
     fn default() -> Self {
         GlobalConfig {
             server_name: "localhost/1.0".to_string(),
-            workers: 1, // Single-threaded as per spec
+            workers: 1,
             timeouts: TimeoutConfig::default(),
             uploads: UploadConfig::default(),
             sessions: SessionConfig::default(),
@@ -328,6 +431,7 @@ impl Default for TimeoutConfig {
             write: Duration::from_secs(5),
             keep_alive: Duration::from_secs(10),
             request: Duration::from_secs(30),
+            shutdown: Duration::from_secs(30),
         }
     }
 }
@@ -401,7 +505,13 @@ impl Default for SecurityConfig {
         SecurityConfig {
             hide_version: false,
             max_header_size: 8192, // 8KB
+            max_header_section_size: 64 * 1024, // 64KB
             max_headers: 100,
+            max_request_line: 8192,
+            max_uri_length: 8192,
+            allow_encoded_slashes: false,
+            strict_parsing: true,
+            headers: HashMap::new(),
             rate_limiting: RateLimitConfig::default(),
             ip_blacklist: Vec::new(),
             ip_whitelist: None,
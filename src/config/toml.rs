@@ -0,0 +1,786 @@
+use crate::config::value::ConfigValue;
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// Parse a TOML document into a table value.
+///
+/// Supports the TOML 1.0 syntax used by server configuration files: bare,
+/// quoted and dotted keys, `[tables]`, `[[arrays.of.tables]]`, basic and
+/// literal strings (including multi-line forms), integers, floats, booleans,
+/// arrays spanning multiple lines and inline tables. Date/time values are
+/// rejected. Errors carry the line and column where parsing failed.
+pub fn parse(input: &str) -> io::Result<ConfigValue> {
+    TomlReader::new(input).parse_document()
+}
+
+type Table = HashMap<String, ConfigValue>;
+
+struct TomlReader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl TomlReader {
+    fn new(input: &str) -> Self {
+        TomlReader {
+            chars: input.chars().collect(),
+            pos: 0,
+        }
+    }
+    
+    fn parse_document(&mut self) -> io::Result<ConfigValue> {
+        let mut root = Table::new();
+        let mut current_path: Vec<String> = Vec::new();
+        let mut defined_tables = HashSet::new();
+        // Tables created implicitly by dotted keys, and inline tables and
+        // arrays, which are complete as written
+        let mut dotted_tables = HashSet::new();
+        let mut closed_values = HashSet::new();
+        
+        loop {
+            self.skip_whitespace_and_comments();
+            let Some(c) = self.peek() else { break };
+            
+            if c == '[' {
+                let start = self.pos;
+                let is_array = self.peek_at(1) == Some('[');
+                self.pos += if is_array { 2 } else { 1 };
+                
+                self.skip_inline_whitespace();
+                let keys = self.parse_key()?;
+                self.skip_inline_whitespace();
+                
+                if is_array {
+                    self.expect_str("]]")?;
+                    push_array_table(&mut root, &keys, &closed_values)
+                        .map_err(|msg| self.error_at(start, &msg))?;
+                } else {
+                    self.expect_str("]")?;
+                    let canonical = ensure_table(&mut root, &keys, &closed_values)
+                        .map_err(|msg| self.error_at(start, &msg))?;
+                    if dotted_tables.contains(&canonical) {
+                        return Err(self.error_at(start, &format!("table [{}] is already defined by dotted keys", keys.join("."))));
+                    }
+                    if !defined_tables.insert(canonical) {
+                        return Err(self.error_at(start, &format!("table [{}] is defined more than once", keys.join("."))));
+                    }
+                }
+                
+                current_path = keys;
+            } else {
+                let start = self.pos;
+                let (keys, value) = self.parse_key_value()?;
+                let closes = matches!(value, ConfigValue::Table(_) | ConfigValue::Array(_));
+                let mut canonical = String::new();
+                let table = navigate(&mut root, &current_path, &mut canonical, &closed_values)
+                    .map_err(|msg| self.error_at(start, &msg))?;
+                let implicit = insert_dotted(table, &keys, value, &mut canonical, &closed_values)
+                    .map_err(|msg| self.error_at(start, &msg))?;
+                
+                // Dotted keys may not reach into a table a header defined
+                for name in implicit {
+                    if defined_tables.contains(&name) {
+                        return Err(self.error_at(start, &format!("table [{}] is already defined by a header", &name[1..])));
+                    }
+                    dotted_tables.insert(name);
+                }
+                if closes {
+                    closed_values.insert(canonical);
+                }
+            }
+            
+            self.expect_line_end()?;
+        }
+        
+        Ok(ConfigValue::Table(root))
+    }
+    
+    /// Parse `key = value`
+    fn parse_key_value(&mut self) -> io::Result<(Vec<String>, ConfigValue)> {
+        let keys = self.parse_key()?;
+        self.skip_inline_whitespace();
+        self.expect_str("=")?;
+        self.skip_inline_whitespace();
+        let value = self.parse_value()?;
+        Ok((keys, value))
+    }
+    
+    /// Parse a possibly dotted key made of bare and quoted parts
+    fn parse_key(&mut self) -> io::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        
+        loop {
+            self.skip_inline_whitespace();
+            let key = match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    self.parse_basic_string()?
+                }
+                Some('\'') => {
+                    self.pos += 1;
+                    self.parse_literal_string()?
+                }
+                _ => {
+                    let start = self.pos;
+                    while let Some(c) = self.peek() {
+                        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    if start == self.pos {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            keys.push(key);
+            
+            self.skip_inline_whitespace();
+            if self.peek() == Some('.') {
+                self.pos += 1;
+            } else {
+                return Ok(keys);
+            }
+        }
+    }
+    
+    fn parse_value(&mut self) -> io::Result<ConfigValue> {
+        match self.peek() {
+            Some('"') => {
+                if self.starts_with("\"\"\"") {
+                    self.pos += 3;
+                    self.parse_multiline_basic_string().map(ConfigValue::String)
+                } else {
+                    self.pos += 1;
+                    self.parse_basic_string().map(ConfigValue::String)
+                }
+            }
+            Some('\'') => {
+                if self.starts_with("'''") {
+                    self.pos += 3;
+                    self.parse_multiline_literal_string().map(ConfigValue::String)
+                } else {
+                    self.pos += 1;
+                    self.parse_literal_string().map(ConfigValue::String)
+                }
+            }
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_inline_table(),
+            Some(_) => self.parse_scalar(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+    
+    fn parse_array(&mut self) -> io::Result<ConfigValue> {
+        self.pos += 1; // '['
+        let mut items = Vec::new();
+        
+        loop {
+            self.skip_whitespace_and_comments();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                break;
+            }
+            
+            items.push(self.parse_value()?);
+            
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(_) => return Err(self.error("expected ',' or ']' in array")),
+                None => return Err(self.error("unterminated array")),
+            }
+        }
+        
+        Ok(ConfigValue::Array(items))
+    }
+    
+    fn parse_inline_table(&mut self) -> io::Result<ConfigValue> {
+        self.pos += 1; // '{'
+        let mut table = Table::new();
+        
+        self.skip_inline_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(ConfigValue::Table(table));
+        }
+        
+        loop {
+            let start = self.pos;
+            let (keys, value) = self.parse_key_value()?;
+            insert_dotted(&mut table, &keys, value, &mut String::new(), &HashSet::new())
+                .map_err(|msg| self.error_at(start, &msg))?;
+            
+            self.skip_inline_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.pos += 1;
+                    self.skip_inline_whitespace();
+                }
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(ConfigValue::Table(table));
+                }
+                Some('\n') | Some('\r') => return Err(self.error("newlines are not allowed in inline tables")),
+                Some(_) => return Err(self.error("expected ',' or '}' in inline table")),
+                None => return Err(self.error("unterminated inline table")),
+            }
+        }
+    }
+    
+    /// Parse booleans, integers and floats
+    fn parse_scalar(&mut self) -> io::Result<ConfigValue> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-' | '.' | ':') {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let token: String = self.chars[start..self.pos].iter().collect();
+        
+        match token.as_str() {
+            "" => return Err(self.error("expected a value")),
+            "true" => return Ok(ConfigValue::Boolean(true)),
+            "false" => return Ok(ConfigValue::Boolean(false)),
+            "inf" | "+inf" => return Ok(ConfigValue::Float(f64::INFINITY)),
+            "-inf" => return Ok(ConfigValue::Float(f64::NEG_INFINITY)),
+            "nan" | "+nan" | "-nan" => return Ok(ConfigValue::Float(f64::NAN)),
+            _ => {}
+        }
+        
+        if token.contains(':') || (token.len() >= 10 && token.as_bytes()[4] == b'-') {
+            return Err(self.error_at(start, "date and time values are not supported"));
+        }
+        
+        parse_number(&token).ok_or_else(|| self.error_at(start, &format!("invalid value `{}`", token)))
+    }
+    
+    /// Parse the rest of a basic string after the opening quote
+    fn parse_basic_string(&mut self) -> io::Result<String> {
+        let mut result = String::new();
+        
+        loop {
+            match self.next() {
+                Some('"') => return Ok(result),
+                Some('\\') => result.push(self.parse_escape()?),
+                Some('\n') | None => return Err(self.error_at(self.pos - 1, "unterminated string")),
+                Some(c) if is_disallowed_control(c) => return Err(self.error("control characters must be escaped")),
+                Some(c) => result.push(c),
+            }
+        }
+    }
+    
+    /// Parse the rest of a multi-line basic string after the opening `"""`
+    fn parse_multiline_basic_string(&mut self) -> io::Result<String> {
+        self.skip_newline();
+        let mut result = String::new();
+        
+        loop {
+            if self.starts_with("\"\"\"") {
+                // Up to two quotes may sit directly before the closing delimiter
+                let mut quotes = 0;
+                while self.peek() == Some('"') {
+                    quotes += 1;
+                    self.pos += 1;
+                }
+                if quotes > 5 {
+                    return Err(self.error("too many quotes at end of string"));
+                }
+                for _ in 0..quotes - 3 {
+                    result.push('"');
+                }
+                return Ok(result);
+            }
+            
+            match self.next() {
+                Some('\\') => {
+                    // A backslash at the end of a line trims the following whitespace
+                    let save = self.pos;
+                    self.skip_inline_whitespace();
+                    if matches!(self.peek(), Some('\n') | Some('\r')) {
+                        while matches!(self.peek(), Some(' ') | Some('\t') | Some('\n') | Some('\r')) {
+                            self.pos += 1;
+                        }
+                    } else {
+                        self.pos = save;
+                        result.push(self.parse_escape()?);
+                    }
+                }
+                Some(c) if c != '\n' && c != '\r' && is_disallowed_control(c) => {
+                    return Err(self.error("control characters must be escaped"));
+                }
+                Some(c) => result.push(c),
+                None => return Err(self.error("unterminated multi-line string")),
+            }
+        }
+    }
+    
+    /// Parse the rest of a literal string after the opening quote
+    fn parse_literal_string(&mut self) -> io::Result<String> {
+        let mut result = String::new();
+        
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(result),
+                Some('\n') | None => return Err(self.error_at(self.pos - 1, "unterminated string")),
+                Some(c) => result.push(c),
+            }
+        }
+    }
+    
+    /// Parse the rest of a multi-line literal string after the opening `'''`
+    fn parse_multiline_literal_string(&mut self) -> io::Result<String> {
+        self.skip_newline();
+        let mut result = String::new();
+        
+        loop {
+            if self.starts_with("'''") {
+                let mut quotes = 0;
+                while self.peek() == Some('\'') {
+                    quotes += 1;
+                    self.pos += 1;
+                }
+                if quotes > 5 {
+                    return Err(self.error("too many quotes at end of string"));
+                }
+                for _ in 0..quotes - 3 {
+                    result.push('\'');
+                }
+                return Ok(result);
+            }
+            
+            match self.next() {
+                Some(c) => result.push(c),
+                None => return Err(self.error("unterminated multi-line string")),
+            }
+        }
+    }
+    
+    /// Parse an escape sequence after the backslash
+    fn parse_escape(&mut self) -> io::Result<char> {
+        let start = self.pos - 1;
+        match self.next() {
+            Some('b') => Ok('\u{0008}'),
+            Some('t') => Ok('\t'),
+            Some('n') => Ok('\n'),
+            Some('f') => Ok('\u{000C}'),
+            Some('r') => Ok('\r'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('u') => self.parse_unicode_escape(4, start),
+            Some('U') => self.parse_unicode_escape(8, start),
+            _ => Err(self.error_at(start, "invalid escape sequence")),
+        }
+    }
+    
+    fn parse_unicode_escape(&mut self, digits: usize, start: usize) -> io::Result<char> {
+        if self.pos + digits > self.chars.len() {
+            return Err(self.error_at(start, "invalid unicode escape"));
+        }
+        let hex: String = self.chars[self.pos..self.pos + digits].iter().collect();
+        self.pos += digits;
+        
+        // from_str_radix would also take a leading sign
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error_at(start, "invalid unicode escape"));
+        }
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error_at(start, "invalid unicode escape"))
+    }
+    
+    /// After a key/value pair or table header only a comment may follow on the line
+    fn expect_line_end(&mut self) -> io::Result<()> {
+        self.skip_inline_whitespace();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.peek_at(1) == Some('\n') => Ok(()),
+            Some(_) => Err(self.error("expected a newline")),
+        }
+    }
+    
+    fn expect_str(&mut self, expected: &str) -> io::Result<()> {
+        if self.starts_with(expected) {
+            self.pos += expected.chars().count();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", expected)))
+        }
+    }
+    
+    fn skip_inline_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
+        }
+    }
+    
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+    
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') | Some('\n') | Some('\r') => self.pos += 1,
+                Some('#') => self.skip_comment(),
+                _ => break,
+            }
+        }
+    }
+    
+    /// Skip a newline directly after an opening multi-line delimiter
+    fn skip_newline(&mut self) {
+        if self.peek() == Some('\n') {
+            self.pos += 1;
+        } else if self.peek() == Some('\r') && self.peek_at(1) == Some('\n') {
+            self.pos += 2;
+        }
+    }
+    
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+    
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+    
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+    
+    fn error(&self, message: &str) -> io::Error {
+        self.error_at(self.pos, message)
+    }
+    
+    /// Build an error pointing at a character offset, reported as 1-based line/column
+    fn error_at(&self, pos: usize, message: &str) -> io::Error {
+        let pos = pos.min(self.chars.len());
+        let line = self.chars[..pos].iter().filter(|&&c| c == '\n').count() + 1;
+        let line_start = self.chars[..pos].iter().rposition(|&c| c == '\n').map(|i| i + 1).unwrap_or(0);
+        let column = pos - line_start + 1;
+        
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("TOML parse error at line {}, column {}: {}", line, column, message),
+        )
+    }
+}
+
+fn is_disallowed_control(c: char) -> bool {
+    (c < ' ' && c != '\t') || c == '\u{7f}'
+}
+
+/// Parse a TOML integer or float token
+fn parse_number(token: &str) -> Option<ConfigValue> {
+    // Underscores are only allowed between digits
+    let bytes = token.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'_' {
+            let before = i.checked_sub(1).map(|j| bytes[j].is_ascii_alphanumeric()).unwrap_or(false);
+            let after = bytes.get(i + 1).map(|c| c.is_ascii_alphanumeric()).unwrap_or(false);
+            if !before || !after {
+                return None;
+            }
+        }
+    }
+    let cleaned = token.replace('_', "");
+    
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(digits) = cleaned.strip_prefix(prefix) {
+            // from_str_radix would also take a sign after the prefix
+            if digits.starts_with(['+', '-']) {
+                return None;
+            }
+            return i64::from_str_radix(digits, radix).ok().map(ConfigValue::Integer);
+        }
+    }
+    
+    let unsigned = cleaned.trim_start_matches(['+', '-']);
+    if unsigned.is_empty() || cleaned.len() - unsigned.len() > 1 {
+        return None;
+    }
+    
+    if unsigned.contains(['.', 'e', 'E']) {
+        // A decimal point needs digits on both sides
+        if let Some(dot) = unsigned.find('.') {
+            let digit_before = unsigned[..dot].chars().last().map(|c| c.is_ascii_digit()).unwrap_or(false);
+            let digit_after = unsigned[dot + 1..].chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false);
+            if !digit_before || !digit_after {
+                return None;
+            }
+        }
+        if !unsigned.chars().next()?.is_ascii_digit() {
+            return None;
+        }
+        return cleaned.parse::<f64>().ok().map(ConfigValue::Float);
+    }
+    
+    // Decimal integers may not have leading zeros
+    if unsigned.len() > 1 && unsigned.starts_with('0') {
+        return None;
+    }
+    if !unsigned.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    cleaned.parse::<i64>().ok().map(ConfigValue::Integer)
+}
+
+/// Walk (and create) tables along `keys`. Arrays of tables descend into
+/// their last element. `canonical` receives a unique name for the table;
+/// inline tables and arrays named in `closed` cannot be entered.
+fn navigate<'a>(mut table: &'a mut Table, keys: &[String], canonical: &mut String, closed: &HashSet<String>) -> Result<&'a mut Table, String> {
+    for key in keys {
+        canonical.push('.');
+        canonical.push_str(key);
+        check_open(canonical, key, closed)?;
+        
+        let entry = table.entry(key.clone()).or_insert_with(ConfigValue::table);
+        table = match entry {
+            ConfigValue::Table(inner) => inner,
+            ConfigValue::Array(items) => {
+                let index = items.len().saturating_sub(1);
+                match items.last_mut() {
+                    Some(ConfigValue::Table(inner)) => {
+                        canonical.push_str(&format!("[{}]", index));
+                        inner
+                    }
+                    _ => return Err(format!("key `{}` is not a table", key)),
+                }
+            }
+            other => return Err(format!("key `{}` is already defined as {}", key, other.type_name())),
+        };
+    }
+    Ok(table)
+}
+
+/// Inline tables and arrays are complete as written and cannot be extended
+fn check_open(canonical: &str, key: &str, closed: &HashSet<String>) -> Result<(), String> {
+    if closed.contains(canonical) {
+        return Err(format!("key `{}` is an inline value and cannot be extended", key));
+    }
+    Ok(())
+}
+
+/// Make sure the table named by a `[header]` exists, returning its canonical name
+fn ensure_table(root: &mut Table, keys: &[String], closed: &HashSet<String>) -> Result<String, String> {
+    let (last, parents) = keys.split_last().ok_or("empty table name")?;
+    let mut canonical = String::new();
+    let parent = navigate(root, parents, &mut canonical, closed)?;
+    canonical.push('.');
+    canonical.push_str(last);
+    check_open(&canonical, last, closed)?;
+    
+    match parent.entry(last.clone()).or_insert_with(ConfigValue::table) {
+        ConfigValue::Table(_) => Ok(canonical),
+        ConfigValue::Array(_) => Err(format!("key `{}` is an array of tables", last)),
+        other => Err(format!("key `{}` is already defined as {}", last, other.type_name())),
+    }
+}
+
+/// Append a new table for a `[[header]]`
+fn push_array_table(root: &mut Table, keys: &[String], closed: &HashSet<String>) -> Result<(), String> {
+    let (last, parents) = keys.split_last().ok_or("empty table name")?;
+    let mut canonical = String::new();
+    let parent = navigate(root, parents, &mut canonical, closed)?;
+    canonical.push('.');
+    canonical.push_str(last);
+    check_open(&canonical, last, closed)?;
+    
+    match parent.entry(last.clone()).or_insert_with(|| ConfigValue::Array(Vec::new())) {
+        ConfigValue::Array(items) if items.iter().all(|item| matches!(item, ConfigValue::Table(_))) => {
+            items.push(ConfigValue::table());
+            Ok(())
+        }
+        other => Err(format!("key `{}` is already defined as {}", last, other.type_name())),
+    }
+}
+
+/// Insert a value under a possibly dotted key. `canonical` starts as the
+/// name of `table` and ends as the name of the value; the names of the
+/// tables the dotted parts walked through are returned.
+fn insert_dotted(table: &mut Table, keys: &[String], value: ConfigValue, canonical: &mut String, closed: &HashSet<String>) -> Result<Vec<String>, String> {
+    let (last, parents) = keys.split_last().ok_or("empty key")?;
+    let mut implicit = Vec::new();
+    let mut parent = table;
+    for key in parents {
+        parent = navigate(parent, std::slice::from_ref(key), canonical, closed)?;
+        implicit.push(canonical.clone());
+    }
+    
+    if parent.contains_key(last) {
+        return Err(format!("duplicate key `{}`", keys.join(".")));
+    }
+    parent.insert(last.clone(), value);
+    canonical.push('.');
+    canonical.push_str(last);
+    Ok(implicit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn get<'a>(value: &'a ConfigValue, path: &str) -> &'a ConfigValue {
+        path.split('.').fold(value, |v, key| v.get(key).unwrap_or_else(|| panic!("missing {}", path)))
+    }
+    
+    #[test]
+    fn test_scalars() {
+        let doc = parse("a = \"text\"\nb = 42\nc = -1_000\nd = 3.5\ne = true\nf = 0x1F\ng = 'C:\\path'\nh = 1e3\n").unwrap();
+        
+        assert_eq!(get(&doc, "a"), &ConfigValue::String("text".to_string()));
+        assert_eq!(get(&doc, "b"), &ConfigValue::Integer(42));
+        assert_eq!(get(&doc, "c"), &ConfigValue::Integer(-1000));
+        assert_eq!(get(&doc, "d"), &ConfigValue::Float(3.5));
+        assert_eq!(get(&doc, "e"), &ConfigValue::Boolean(true));
+        assert_eq!(get(&doc, "f"), &ConfigValue::Integer(31));
+        assert_eq!(get(&doc, "g"), &ConfigValue::String("C:\\path".to_string()));
+        assert_eq!(get(&doc, "h"), &ConfigValue::Float(1000.0));
+    }
+    
+    #[test]
+    fn test_strings() {
+        let doc = parse(concat!(
+            "escaped = \"tab\\there \\\"q\\\" \\u00e9\"\n",
+            "multi = \"\"\"\nline one\nline two\"\"\"\n",
+            "folded = \"\"\"one \\\n    two\"\"\"\n",
+            "literal = '''\nraw \\n text'''\n",
+        )).unwrap();
+        
+        assert_eq!(get(&doc, "escaped").as_str(), Some("tab\there \"q\" é"));
+        assert_eq!(get(&doc, "multi").as_str(), Some("line one\nline two"));
+        assert_eq!(get(&doc, "folded").as_str(), Some("one two"));
+        assert_eq!(get(&doc, "literal").as_str(), Some("raw \\n text"));
+    }
+    
+    #[test]
+    fn test_arrays_and_inline_tables() {
+        let doc = parse(concat!(
+            "methods = [\n  \"GET\", # comment\n  \"POST\",\n]\n",
+            "nested = [[1, 2], [\"a\"]]\n",
+            "point = { x = 1, y.z = 2 }\n",
+        )).unwrap();
+        
+        let methods = get(&doc, "methods").as_array().unwrap();
+        assert_eq!(methods.len(), 2);
+        assert_eq!(methods[1].as_str(), Some("POST"));
+        assert_eq!(get(&doc, "nested").as_array().unwrap().len(), 2);
+        assert_eq!(get(&doc, "point.x").as_integer(), Some(1));
+        assert_eq!(get(&doc, "point.y.z").as_integer(), Some(2));
+    }
+    
+    #[test]
+    fn test_tables_and_arrays_of_tables() {
+        let doc = parse(concat!(
+            "[server]\nname = \"x\"\n",
+            "[cgi.interpreters]\npy = \"python3\"\n",
+            "[[vhost]]\nserver_name = \"a\"\n",
+            "[vhost.error_pages]\n404 = \"404.html\"\n",
+            "[[vhost.route]]\npath = \"/\"\n",
+            "[[vhost.route]]\npath = \"/cgi\"\n",
+            "[[vhost]]\nserver_name = \"b\"\n",
+            "\"quoted key\".dotted = 1\n",
+        )).unwrap();
+        
+        assert_eq!(get(&doc, "cgi.interpreters.py").as_str(), Some("python3"));
+        
+        let vhosts = get(&doc, "vhost").as_array().unwrap();
+        assert_eq!(vhosts.len(), 2);
+        assert_eq!(get(&vhosts[0], "error_pages.404").as_str(), Some("404.html"));
+        assert_eq!(get(&vhosts[0], "route").as_array().unwrap().len(), 2);
+        assert!(vhosts[1].get("route").is_none());
+        assert_eq!(get(&vhosts[1], "quoted key.dotted").as_integer(), Some(1));
+    }
+    
+    #[test]
+    fn test_errors_have_line_and_column() {
+        let err = parse("a = 1\nb = \"unterminated\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        
+        let err = parse("a = 1\na = 2\n").unwrap_err();
+        assert!(err.to_string().contains("line 2, column 1"), "{}", err);
+        assert!(err.to_string().contains("duplicate key"));
+        
+        let err = parse("[t]\n[t]\n").unwrap_err();
+        assert!(err.to_string().contains("defined more than once"), "{}", err);
+        
+        let err = parse("x = 1 2\n").unwrap_err();
+        assert!(err.to_string().contains("line 1, column 7"), "{}", err);
+    }
+    
+    #[test]
+    fn test_invalid_values() {
+        assert!(parse("a = 01\n").is_err());
+        assert!(parse("a = 1__0\n").is_err());
+        assert!(parse("a = .5\n").is_err());
+        assert!(parse("a = 1979-05-27\n").is_err());
+        assert!(parse("a = bare\n").is_err());
+        assert!(parse("a = { b = 1,\n c = 2 }\n").is_err());
+        assert!(parse("a = 1\n[a]\n").is_err());
+        assert!(parse("a = 0x-1\n").is_err());
+        assert!(parse("a = 0o+7\n").is_err());
+        assert!(parse("a = 0b-1\n").is_err());
+        assert!(parse("a = \"\\u+123\"\n").is_err());
+        assert!(parse("a = \"\\U+0001F60\"\n").is_err());
+    }
+    
+    #[test]
+    fn test_inline_values_cannot_be_extended() {
+        let err = parse("a = { b = 1 }\n[a]\nc = 2\n").unwrap_err();
+        assert!(err.to_string().contains("line 2, column 1"), "{}", err);
+        assert!(err.to_string().contains("cannot be extended"), "{}", err);
+        
+        let err = parse("a = { b = 1 }\na.c = 2\n").unwrap_err();
+        assert!(err.to_string().contains("line 2, column 1"), "{}", err);
+        
+        let err = parse("[t]\na = { b = 1 }\n\n[t.a.c]\n").unwrap_err();
+        assert!(err.to_string().contains("line 4, column 1"), "{}", err);
+        
+        let err = parse("a = [{ b = 1 }]\n[[a]]\n").unwrap_err();
+        assert!(err.to_string().contains("line 2, column 1"), "{}", err);
+        
+        // Dotted keys inside the inline table itself are fine
+        assert!(parse("a = { b.c = 1, b.d = 2 }\n").is_ok());
+    }
+    
+    #[test]
+    fn test_dotted_key_tables_cannot_be_redefined() {
+        let err = parse("a.b = 1\n[a]\n").unwrap_err();
+        assert!(err.to_string().contains("line 2, column 1"), "{}", err);
+        assert!(err.to_string().contains("defined by dotted keys"), "{}", err);
+        
+        let err = parse("[x]\ny.z = 1\n[x.y]\n").unwrap_err();
+        assert!(err.to_string().contains("line 3, column 1"), "{}", err);
+        
+        let err = parse("[a.b]\nc = 1\n[a]\nb.d = 2\n").unwrap_err();
+        assert!(err.to_string().contains("line 4, column 1"), "{}", err);
+        assert!(err.to_string().contains("defined by a header"), "{}", err);
+        
+        // Sub-tables of a dotted-key table may still get headers
+        let doc = parse("[fruit]\napple.color = \"red\"\n[fruit.apple.texture]\nsmooth = true\n").unwrap();
+        assert_eq!(get(&doc, "fruit.apple.texture.smooth"), &ConfigValue::Boolean(true));
+    }
+}
@@ -88,6 +88,7 @@ impl ConfigValidator {
         }
         
         let mut addresses = HashSet::new();
+        let mut names = HashSet::new();
         let mut default_count = 0;
         
         for (i, listener) in listeners.iter().enumerate() {
@@ -112,6 +113,13 @@ impl ConfigValidator {
             }
             addresses.insert(addr_port);
             
+            // Inherited sockets are matched to listeners by name
+            if let Some(name) = &listener.name {
+                if !names.insert(name) {
+                    self.add_error(&format!("{}.name", field), "Duplicate listener name", ValidationErrorType::Conflict);
+                }
+            }
+            
             // Count default listeners
             if listener.default {
                 default_count += 1;
@@ -247,14 +255,11 @@ impl ConfigValidator {
                     self.add_error(field, &format!("Invalid redirect status code: {}", status), ValidationErrorType::InvalidFormat);
                 }
             }
-            RouteType::Proxy { backend, timeout } => {
-                if backend.is_empty() {
-                    self.add_error(field, "Proxy backend cannot be empty", ValidationErrorType::Required);
-                }
-                
-                if timeout.as_secs() == 0 {
-                    self.add_error(field, "Proxy timeout cannot be 0", ValidationErrorType::OutOfRange);
-                }
+            RouteType::Session => {}
+            RouteType::Proxy { .. } => {
+                // Nothing forwards requests yet, and serving the route any
+                // other way would expose whatever its path maps to
+                self.add_error(field, "Proxy routes are not supported", ValidationErrorType::InvalidFormat);
             }
         }
     }
@@ -281,9 +286,11 @@ impl ConfigValidator {
             self.add_error("global.server_name", "Server name cannot be empty", ValidationErrorType::Required);
         }
         
-        // Validate workers (should always be 1 for this server)
-        if global.workers != 1 {
-            self.add_warning("global.workers", "This server only supports single-threaded operation", ValidationErrorType::InvalidFormat);
+        // Validate workers: each one is a thread running its own event loop
+        if global.workers == 0 {
+            self.add_error("global.workers", "At least one worker is required", ValidationErrorType::OutOfRange);
+        } else if global.workers > 256 {
+            self.add_warning("global.workers", "Very high worker count", ValidationErrorType::OutOfRange);
         }
         
         // Validate timeouts
@@ -439,10 +446,22 @@ impl ConfigValidator {
             self.add_error(&format!("{}.max_header_size", field), "Max header size cannot be 0", ValidationErrorType::OutOfRange);
         }
         
+        if security.max_header_section_size < security.max_header_size {
+            self.add_error(&format!("{}.max_header_section_size", field), "Max header section size cannot be less than max header size", ValidationErrorType::OutOfRange);
+        }
+        
         if security.max_headers == 0 {
             self.add_error(&format!("{}.max_headers", field), "Max headers cannot be 0", ValidationErrorType::OutOfRange);
         }
         
+        if security.max_request_line == 0 {
+            self.add_error(&format!("{}.max_request_line", field), "Max request line cannot be 0", ValidationErrorType::OutOfRange);
+        }
+        
+        if security.max_uri_length == 0 {
+            self.add_error(&format!("{}.max_uri_length", field), "Max URI length cannot be 0", ValidationErrorType::OutOfRange);
+        }
+        
         // Validate rate limiting
         if security.rate_limiting.enabled {
             if security.rate_limiting.requests_per_minute == 0 {
@@ -529,7 +548,6 @@ impl Default for ConfigValidator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
     
     #[test]
     fn test_validator_creation() {
@@ -568,8 +586,8 @@ mod tests {
         let mut validator = ConfigValidator::new();
         let config = ServerConfig {
             listeners: vec![
-                ListenerConfig { address: "127.0.0.1".to_string(), port: 8080, default: true },
-                ListenerConfig { address: "127.0.0.1".to_string(), port: 8080, default: false },
+                ListenerConfig { address: "127.0.0.1".to_string(), port: 8080, default: true, name: None },
+                ListenerConfig { address: "127.0.0.1".to_string(), port: 8080, default: false, name: None },
             ],
             ..Default::default()
         };
@@ -579,6 +597,49 @@ mod tests {
         assert!(validator.errors.iter().any(|e| e.message.contains("Duplicate")));
     }
     
+    #[test]
+    fn test_validate_duplicate_listener_names() {
+        let mut validator = ConfigValidator::new();
+        let named = |port, name: &str| ListenerConfig {
+            address: "127.0.0.1".to_string(),
+            port,
+            default: port == 8080,
+            name: Some(name.to_string()),
+        };
+        let config = ServerConfig {
+            listeners: vec![named(8080, "web"), named(8081, "admin"), named(8082, "web")],
+            ..Default::default()
+        };
+        
+        assert!(validator.validate(&config).is_err());
+        assert!(validator.errors.iter().any(|e| e.field == "listeners[2].name"));
+        assert!(!validator.errors.iter().any(|e| e.field == "listeners[1].name"));
+    }
+    
+    #[test]
+    fn test_validate_rejects_proxy_routes() {
+        let mut validator = ConfigValidator::new();
+        let proxy = RouteConfig {
+            path: "/api/*".to_string(),
+            route_type: RouteType::Proxy {
+                backend: "http://127.0.0.1:9000".to_string(),
+                timeout: std::time::Duration::from_secs(30),
+            },
+            ..Default::default()
+        };
+        let config = ServerConfig {
+            virtual_hosts: vec![VirtualHostConfig {
+                routes: vec![RouteConfig::default(), proxy],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        
+        assert!(validator.validate(&config).is_err());
+        let error = validator.errors.iter().find(|e| e.field.ends_with("routes[1].route_type")).unwrap();
+        assert!(error.message.contains("not supported"));
+    }
+    
     #[test]
     fn test_validate_http_methods() {
         let validator = ConfigValidator::new();
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+/// Format-independent configuration value tree.
+/// Both the TOML and JSON readers produce this before it is mapped onto `ServerConfig`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<ConfigValue>),
+    Table(HashMap<String, ConfigValue>),
+}
+
+impl ConfigValue {
+    /// Create an empty table
+    pub fn table() -> Self {
+        ConfigValue::Table(HashMap::new())
+    }
+    
+    /// Human-readable type name for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ConfigValue::String(_) => "string",
+            ConfigValue::Integer(_) => "integer",
+            ConfigValue::Float(_) => "float",
+            ConfigValue::Boolean(_) => "boolean",
+            ConfigValue::Array(_) => "array",
+            ConfigValue::Table(_) => "table",
+        }
+    }
+    
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConfigValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            ConfigValue::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+    
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ConfigValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+    
+    pub fn as_table(&self) -> Option<&HashMap<String, ConfigValue>> {
+        match self {
+            ConfigValue::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl ConfigValue {
+    pub fn as_array(&self) -> Option<&Vec<ConfigValue>> {
+        match self {
+            ConfigValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+    
+    /// Look up a key if this value is a table
+    pub fn get(&self, key: &str) -> Option<&ConfigValue> {
+        self.as_table().and_then(|table| table.get(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_accessors() {
+        let mut table = HashMap::new();
+        table.insert("port".to_string(), ConfigValue::Integer(8080));
+        let value = ConfigValue::Table(table);
+        
+        assert_eq!(value.get("port").and_then(|v| v.as_integer()), Some(8080));
+        assert_eq!(value.get("missing"), None);
+        assert_eq!(value.type_name(), "table");
+        assert_eq!(ConfigValue::String("x".to_string()).as_str(), Some("x"));
+        assert_eq!(ConfigValue::Boolean(true).as_integer(), None);
+    }
+}
@@ -1,5 +1,6 @@
 //! Error handling and HTTP error responses
 
+use crate::http::date;
 use crate::http::response::HttpResponse;
 use std::collections::HashMap;
 use std::fs;
@@ -118,7 +119,7 @@ impl ErrorPageGenerator {
             .replace("{{MESSAGE}}", message)
             .replace("{{DETAILS}}", details)
             .replace("{{SERVER_NAME}}", &self.config.server_name)
-            .replace("{{TIMESTAMP}}", &std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs().to_string())
+            .replace("{{TIMESTAMP}}", &date::cached_date())
     }
     
     /// Generate default error page HTML
@@ -236,7 +237,7 @@ impl ErrorPageGenerator {
 </body>
 </html>"#,
             self.config.server_name,
-            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+            date::cached_date()
         ));
         
         html
@@ -251,9 +252,15 @@ impl ErrorPageGenerator {
             404 => "Not Found".to_string(),
             405 => "Method Not Allowed".to_string(),
             408 => "Request Timeout".to_string(),
+            409 => "Conflict".to_string(),
+            412 => "Precondition Failed".to_string(),
             413 => "Payload Too Large".to_string(),
             414 => "URI Too Long".to_string(),
+            415 => "Unsupported Media Type".to_string(),
+            416 => "Range Not Satisfiable".to_string(),
+            417 => "Expectation Failed".to_string(),
             429 => "Too Many Requests".to_string(),
+            431 => "Request Header Fields Too Large".to_string(),
             500 => "Internal Server Error".to_string(),
             501 => "Not Implemented".to_string(),
             502 => "Bad Gateway".to_string(),
@@ -272,9 +279,15 @@ impl ErrorPageGenerator {
             404 => "The requested resource could not be found on this server.",
             405 => "The request method is not allowed for this resource.",
             408 => "The server timed out waiting for the request.",
+            409 => "The request conflicts with the current state of the resource.",
+            412 => "A precondition given in the request headers was not met.",
             413 => "The request payload is too large for the server to process.",
             414 => "The request URI is too long for the server to process.",
+            415 => "The type of the uploaded content is not accepted here.",
+            416 => "The requested range cannot be served from this resource.",
+            417 => "The expectation given in the Expect header cannot be met.",
             429 => "Too many requests have been sent in a given amount of time.",
+            431 => "The request headers are too large for the server to process.",
             500 => "The server encountered an unexpected condition.",
             501 => "The server does not support the functionality required.",
             502 => "The server received an invalid response from an upstream server.",
@@ -630,7 +643,7 @@ impl DirectoryListing {
         
         if self.show_times {
             let time_str = if let Some(modified) = file.modified {
-                format_time(modified)
+                date::format_system_time(modified)
             } else {
                 "-".to_string()
             };
@@ -705,15 +718,3 @@ fn format_file_size(size: u64) -> String {
         format!("{:.1} {}", size, UNITS[unit_index])
     }
 }
-
-/// Format system time for display
-fn format_time(time: std::time::SystemTime) -> String {
-    match time.duration_since(std::time::UNIX_EPOCH) {
-        Ok(duration) => {
-            let timestamp = duration.as_secs();
-            // Simple timestamp formatting without external dependencies
-            format!("{}", timestamp)
-        }
-        Err(_) => "-".to_string(),
-    }
-}
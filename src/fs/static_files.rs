@@ -1,15 +1,130 @@
 use std::fs::{File, Metadata};
-use std::io::{self, Read};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 use crate::fs::path_utils::{safe_path_join, should_serve_index, get_index_path};
 use crate::mime::MimeTypes;
-use crate::http::response::HttpResponse;
+use crate::http::compress::{self, Encoding};
+use crate::http::conditional::{Precondition, Validators};
+use crate::http::date::{format_http_date, parse_http_date};
+use crate::http::request::{HttpRequest, Method};
+use crate::http::response::{BodyChunk, FileBody, HttpResponse};
+use crate::routing::redirections::CacheSettings;
+use crate::errors::DirectoryListing;
+
+/// Most ranges honored in one Range header; longer lists are ignored and
+/// the full file is sent, so a client cannot make us emit thousands of parts
+const MAX_RANGES: usize = 16;
+
+/// Inclusive byte range of a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Outcome of evaluating a Range header against a file
+#[derive(Debug, PartialEq)]
+pub enum RangeSpec {
+    /// Unknown unit or invalid syntax: serve the whole file
+    Ignore,
+    /// Sorted, non-overlapping ranges to send
+    Satisfiable(Vec<ByteRange>),
+    /// No requested range overlaps the file
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header value (RFC 9110 section 14.2) for a file of `size` bytes.
+/// Overlapping and adjacent ranges are merged.
+pub fn parse_range(value: &str, size: u64) -> RangeSpec {
+    let (unit, set) = match value.split_once('=') {
+        Some(parts) => parts,
+        None => return RangeSpec::Ignore,
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return RangeSpec::Ignore;
+    }
+    
+    let specs: Vec<&str> = set.split(',').map(|spec| spec.trim()).filter(|spec| !spec.is_empty()).collect();
+    if specs.is_empty() || specs.len() > MAX_RANGES {
+        return RangeSpec::Ignore;
+    }
+    
+    let mut ranges = Vec::new();
+    for spec in specs {
+        let (first, last) = match spec.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => return RangeSpec::Ignore,
+        };
+        
+        if first.is_empty() {
+            // Suffix range: the final N bytes
+            let suffix = match parse_position(last) {
+                Some(suffix) => suffix,
+                None => return RangeSpec::Ignore,
+            };
+            if suffix > 0 && size > 0 {
+                ranges.push(ByteRange { start: size.saturating_sub(suffix), end: size - 1 });
+            }
+            continue;
+        }
+        
+        let start = match parse_position(first) {
+            Some(start) => start,
+            None => return RangeSpec::Ignore,
+        };
+        let end = if last.is_empty() {
+            u64::MAX
+        } else {
+            match parse_position(last) {
+                Some(end) if end >= start => end,
+                _ => return RangeSpec::Ignore,
+            }
+        };
+        
+        if start < size {
+            ranges.push(ByteRange { start, end: end.min(size - 1) });
+        }
+    }
+    
+    if ranges.is_empty() {
+        return RangeSpec::Unsatisfiable;
+    }
+    
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(previous) if range.start <= previous.end.saturating_add(1) => {
+                previous.end = previous.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    RangeSpec::Satisfiable(merged)
+}
+
+fn parse_position(digits: &str) -> Option<u64> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // Positions beyond u64 are clamped; they lie past any file anyway
+    Some(digits.parse().unwrap_or(u64::MAX))
+}
 
 pub struct StaticFileServer {
     document_root: PathBuf,
     mime_types: MimeTypes,
     index_file: String,
+    /// Which validators (ETag, Last-Modified) file responses carry
+    cache_settings: CacheSettings,
 }
 
 impl StaticFileServer {
@@ -35,38 +150,131 @@ impl StaticFileServer {
             document_root: root,
             mime_types: MimeTypes::new(),
             index_file: index_file.unwrap_or_else(|| "index.html".to_string()),
+            cache_settings: CacheSettings::default(),
         })
     }
     
+    /// Set the cache settings used for file responses
+    pub fn set_cache_settings(&mut self, cache_settings: CacheSettings) {
+        self.cache_settings = cache_settings;
+    }
+    
     /// Serve a static file for the given request path
     pub fn serve_file(&self, request_path: &str) -> io::Result<HttpResponse> {
+        self.serve_route(request_path, &[], false)
+    }
+    
+    /// Serve a GET or HEAD request, evaluating conditional headers and
+    /// honoring `Range` and `If-Range` for files
+    pub fn serve_request(
+        &self,
+        request: &HttpRequest,
+        index_files: &[String],
+        directory_listing: bool,
+    ) -> io::Result<HttpResponse> {
+        let accept_encoding = request.headers.get_combined("accept-encoding");
+        let response = self.serve_path(request.path(), index_files, directory_listing, accept_encoding.as_deref())?;
+        
+        if response.status_code != 200 || !matches!(request.method, Method::GET | Method::HEAD) {
+            return Ok(response);
+        }
+        
+        if response.single_file_body().is_some() {
+            match Self::response_validators(&response).evaluate(request) {
+                Precondition::Proceed => {}
+                Precondition::NotModified => return Ok(Self::not_modified(&response)),
+                Precondition::Failed => {
+                    let mut failed = HttpResponse::new(412);
+                    failed.set_header("Content-Type", "text/plain");
+                    failed.set_body_string("412 Precondition Failed");
+                    return Ok(failed);
+                }
+            }
+        }
+        
+        match request.get_header("range") {
+            Some(range) if self.if_range_matches(request, &response) => Ok(self.apply_range(response, range)),
+            _ => Ok(response),
+        }
+    }
+    
+    /// Serve a static file using a route's index files and directory listing
+    /// settings. A directory without an index is listed when enabled.
+    pub fn serve_route(
+        &self,
+        request_path: &str,
+        index_files: &[String],
+        directory_listing: bool,
+    ) -> io::Result<HttpResponse> {
+        self.serve_path(request_path, index_files, directory_listing, None)
+    }
+    
+    /// Serve a path, preferring a precompressed `.gz` sibling of the file
+    /// when `accept_encoding` allows gzip and the sibling is not older
+    /// than the file
+    fn serve_path(
+        &self,
+        request_path: &str,
+        index_files: &[String],
+        directory_listing: bool,
+        accept_encoding: Option<&str>,
+    ) -> io::Result<HttpResponse> {
         // Resolve the safe file path
         let file_path = safe_path_join(&self.document_root, request_path)?;
         
         // Check if we should serve an index file for a directory
         let final_path = if should_serve_index(&file_path) {
-            get_index_path(&file_path, &self.index_file)
+            // The first index file that exists wins
+            let index_files = if index_files.is_empty() { std::slice::from_ref(&self.index_file) } else { index_files };
+            let index_paths: Vec<PathBuf> = index_files.iter().map(|index_file| get_index_path(&file_path, index_file)).collect();
+            match index_paths.iter().find(|path| path.is_file()) {
+                Some(index_path) => index_path.clone(),
+                None if directory_listing => return self.serve_directory_listing(&file_path, request_path),
+                None => index_paths[0].clone(),
+            }
         } else {
             file_path
         };
         
-        // Try to open and read the file
-        match self.read_file(&final_path) {
-            Ok((content, metadata)) => {
+        // Open the file; its contents are streamed by the connection
+        match self.open_file(&final_path) {
+            Ok(opened) => {
                 let mut response = HttpResponse::ok();
                 
                 // Set content type based on file extension
                 let mime_type = self.mime_types.get_mime_type(&final_path);
                 response.set_header("Content-Type", mime_type);
                 
+                let precompressed = accept_encoding
+                    .filter(|accept| compress::negotiate(accept, &[Encoding::Gzip]).is_some())
+                    .and_then(|_| self.open_file(&Self::gzip_sibling(&final_path)).ok())
+                    .filter(|(_, sibling)| match (sibling.modified(), opened.1.modified()) {
+                        // A sibling older than the file was made from a previous version
+                        (Ok(sibling_modified), Ok(modified)) => sibling_modified >= modified,
+                        _ => false,
+                    });
+                if precompressed.is_some() {
+                    response.set_header("Content-Encoding", "gzip");
+                }
+                if precompressed.is_some() || self.mime_types.is_text_type(mime_type) {
+                    response.add_vary("Accept-Encoding");
+                }
+                let (file, metadata) = precompressed.unwrap_or(opened);
+                
                 // Set content length
-                response.set_body(&content);
+                response.set_file_body(Arc::new(file), 0, metadata.len());
+                response.set_header("Accept-Ranges", "bytes");
                 
-                // Set Last-Modified header
-                if let Ok(modified) = metadata.modified() {
-                    if let Ok(duration) = modified.duration_since(SystemTime::UNIX_EPOCH) {
-                        let timestamp = duration.as_secs();
-                        response.set_header("Last-Modified", &format!("timestamp-{}", timestamp));
+                // Set validators for conditional requests
+                let validators = Validators::for_file(&metadata);
+                if self.cache_settings.etag_enabled {
+                    if let Some(etag) = &validators.etag {
+                        response.set_header("ETag", etag);
+                    }
+                }
+                if self.cache_settings.last_modified_enabled {
+                    if let Some(modified) = validators.last_modified {
+                        response.set_header("Last-Modified", &format_http_date(modified));
                     }
                 }
                 
@@ -97,23 +305,137 @@ impl StaticFileServer {
         }
     }
     
-    fn read_file(&self, path: &Path) -> io::Result<(Vec<u8>, Metadata)> {
-        let mut file = File::open(path)?;
-        let metadata = file.metadata()?;
+    /// Validators as advertised in a response's ETag and Last-Modified headers
+    fn response_validators(response: &HttpResponse) -> Validators {
+        Validators {
+            etag: response.headers.get("ETag").map(str::to_string),
+            last_modified: response.headers.get("Last-Modified").and_then(parse_http_date),
+        }
+    }
+    
+    /// A 304 carrying the validators and caching headers of the full response
+    fn not_modified(response: &HttpResponse) -> HttpResponse {
+        let mut not_modified = HttpResponse::new(304);
+        for name in ["ETag", "Last-Modified", "Cache-Control", "Expires", "Vary"] {
+            if let Some(value) = response.headers.get(name) {
+                not_modified.set_header(name, value);
+            }
+        }
+        not_modified
+    }
+    
+    /// An `If-Range` validator must match exactly, otherwise the full file is sent
+    fn if_range_matches(&self, request: &HttpRequest, response: &HttpResponse) -> bool {
+        let validator = match request.get_header("if-range") {
+            Some(validator) => validator.trim(),
+            None => return true,
+        };
         
-        // Check file size to prevent reading extremely large files
-        const MAX_FILE_SIZE: u64 = 100 * 1024 * 1024; // 100MB
-        if metadata.len() > MAX_FILE_SIZE {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "File too large to serve"
-            ));
+        if validator.starts_with("W/") {
+            // Weak entity tags never match for ranges
+            false
+        } else if validator.starts_with('"') {
+            response.headers.get("ETag").is_some_and(|etag| etag == validator)
+        } else {
+            response.headers.get("Last-Modified").is_some_and(|modified| modified == validator)
+        }
+    }
+    
+    /// Turn a full 200 file response into a 206 or 416 according to `range`
+    fn apply_range(&self, response: HttpResponse, range: &str) -> HttpResponse {
+        let file_body = match response.single_file_body() {
+            Some(file_body) => file_body.clone(),
+            None => return response,
+        };
+        let size = file_body.length;
+        
+        let ranges = match parse_range(range, size) {
+            RangeSpec::Ignore => return response,
+            RangeSpec::Unsatisfiable => {
+                let mut unsatisfiable = HttpResponse::new(416);
+                unsatisfiable.set_header("Content-Type", "text/plain");
+                unsatisfiable.set_header("Content-Range", &format!("bytes */{}", size));
+                unsatisfiable.set_body_string("416 Range Not Satisfiable");
+                return unsatisfiable;
+            }
+            RangeSpec::Satisfiable(ranges) => ranges,
+        };
+        
+        let mut partial = HttpResponse::new(206);
+        for (name, value) in &response.headers {
+            partial.set_header(name, value);
+        }
+        
+        let part = |range: &ByteRange| BodyChunk::File(FileBody {
+            file: file_body.file.clone(),
+            offset: file_body.offset + range.start,
+            length: range.length(),
+        });
+        
+        if let [range] = ranges.as_slice() {
+            partial.set_header("Content-Range", &format!("bytes {}-{}/{}", range.start, range.end, size));
+            partial.set_body_stream(vec![part(range)]);
+            return partial;
+        }
+        
+        // Several ranges are sent as multipart/byteranges, each part with its own headers
+        let content_type = response.headers.get("Content-Type").map(str::to_string)
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        let boundary = Self::multipart_boundary();
+        let mut chunks = Vec::with_capacity(ranges.len() * 2 + 1);
+        
+        for range in &ranges {
+            let part_head = format!(
+                "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                boundary, content_type, range.start, range.end, size
+            );
+            chunks.push(BodyChunk::Bytes(part_head.into_bytes()));
+            chunks.push(part(range));
         }
+        chunks.push(BodyChunk::Bytes(format!("\r\n--{}--\r\n", boundary).into_bytes()));
         
-        let mut content = Vec::with_capacity(metadata.len() as usize);
-        file.read_to_end(&mut content)?;
+        partial.set_header("Content-Type", &format!("multipart/byteranges; boundary={}", boundary));
+        partial.set_body_stream(chunks);
+        partial
+    }
+    
+    fn multipart_boundary() -> String {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        format!("localhost-byteranges-{:x}", nanos)
+    }
+    
+    fn serve_directory_listing(&self, dir_path: &Path, request_path: &str) -> io::Result<HttpResponse> {
+        let html = DirectoryListing::default().generate_listing(dir_path, request_path)?;
         
-        Ok((content, metadata))
+        let mut response = HttpResponse::ok();
+        response.set_header("Content-Type", "text/html; charset=utf-8");
+        response.set_body(html.as_bytes());
+        Ok(response)
+    }
+    
+    /// `style.css` -> `style.css.gz`
+    fn gzip_sibling(path: &Path) -> PathBuf {
+        let mut sibling = path.as_os_str().to_owned();
+        sibling.push(".gz");
+        PathBuf::from(sibling)
+    }
+    
+    pub fn mime_types(&self) -> &MimeTypes {
+        &self.mime_types
+    }
+    
+    fn open_file(&self, path: &Path) -> io::Result<(File, Metadata)> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        
+        if !metadata.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Not a regular file"));
+        }
+        
+        Ok((file, metadata))
     }
     
     fn is_cacheable_asset(&self, path: &Path) -> bool {
@@ -142,6 +464,7 @@ impl StaticFileServer {
 mod tests {
     use super::*;
     use std::fs;
+    use std::time::Duration;
     use tempfile::TempDir;
     
     #[test]
@@ -176,8 +499,8 @@ mod tests {
         let response = server.serve_file("/test.txt").unwrap();
         
         assert_eq!(response.status_code, 200);
-        assert_eq!(response.body, test_content.as_bytes());
-        assert_eq!(response.headers.get("Content-Type"), Some(&"text/plain".to_string()));
+        assert_eq!(response.body_bytes().unwrap(), test_content.as_bytes());
+        assert_eq!(response.headers.get("Content-Type"), Some("text/plain"));
     }
     
     #[test]
@@ -204,8 +527,236 @@ mod tests {
         let response = server.serve_file("/").unwrap();
         
         assert_eq!(response.status_code, 200);
-        assert_eq!(response.body, index_content.as_bytes());
-        assert_eq!(response.headers.get("Content-Type"), Some(&"text/html".to_string()));
+        assert_eq!(response.body_bytes().unwrap(), index_content.as_bytes());
+        assert_eq!(response.headers.get("Content-Type"), Some("text/html"));
+    }
+    
+    #[test]
+    fn test_serve_route_directory_listing() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/readme.txt"), "hi").unwrap();
+        fs::write(root.join("docs/home.html"), "home").unwrap();
+        
+        let server = StaticFileServer::new(root, None).unwrap();
+        
+        // Listing disabled: missing index is a 404
+        let response = server.serve_route("/docs/", &[], false).unwrap();
+        assert_eq!(response.status_code, 404);
+        
+        // Listing enabled: directory contents are shown
+        let response = server.serve_route("/docs/", &[], true).unwrap();
+        assert_eq!(response.status_code, 200);
+        assert!(String::from_utf8_lossy(&response.body).contains("readme.txt"));
+        
+        // Route index files take precedence over the listing, in order
+        let index_files = ["index.htm".to_string(), "home.html".to_string(), "readme.txt".to_string()];
+        let response = server.serve_route("/docs/", &index_files, true).unwrap();
+        assert_eq!(response.body_bytes().unwrap(), b"home");
+    }
+    
+    #[test]
+    fn test_parse_range() {
+        let range = |start, end| ByteRange { start, end };
+        
+        assert_eq!(parse_range("bytes=0-4", 10), RangeSpec::Satisfiable(vec![range(0, 4)]));
+        assert_eq!(parse_range("bytes=5-", 10), RangeSpec::Satisfiable(vec![range(5, 9)]));
+        assert_eq!(parse_range("bytes=-3", 10), RangeSpec::Satisfiable(vec![range(7, 9)]));
+        assert_eq!(parse_range("bytes=-30", 10), RangeSpec::Satisfiable(vec![range(0, 9)]));
+        assert_eq!(parse_range("bytes=8-100", 10), RangeSpec::Satisfiable(vec![range(8, 9)]));
+        
+        // Overlapping and adjacent ranges are merged and sorted
+        assert_eq!(
+            parse_range("bytes=6-7, 0-2,3-4, 1-1", 10),
+            RangeSpec::Satisfiable(vec![range(0, 4), range(6, 7)])
+        );
+        
+        // Ranges past the end are dropped; nothing left is unsatisfiable
+        assert_eq!(parse_range("bytes=20-30, 2-3", 10), RangeSpec::Satisfiable(vec![range(2, 3)]));
+        assert_eq!(parse_range("bytes=10-", 10), RangeSpec::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 10), RangeSpec::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), RangeSpec::Unsatisfiable);
+        
+        // Invalid headers are ignored
+        assert_eq!(parse_range("items=0-4", 10), RangeSpec::Ignore);
+        assert_eq!(parse_range("bytes=4-2", 10), RangeSpec::Ignore);
+        assert_eq!(parse_range("bytes=a-b", 10), RangeSpec::Ignore);
+        assert_eq!(parse_range("bytes=", 10), RangeSpec::Ignore);
+        assert_eq!(parse_range("bytes=+1-2", 10), RangeSpec::Ignore);
+        assert_eq!(parse_range(&format!("bytes={}", vec!["0-0"; 17].join(",")), 10), RangeSpec::Ignore);
+    }
+    
+    #[test]
+    fn test_serve_single_range() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("data.txt"), "0123456789").unwrap();
+        let server = StaticFileServer::new(temp_dir.path(), None).unwrap();
+        
+        let response = server.serve_request(&HttpRequest::with_headers(Method::GET, "/data.txt", &[]), &[], false).unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.headers.get("Accept-Ranges"), Some("bytes"));
+        
+        let request = HttpRequest::with_headers(Method::GET, "/data.txt", &[("Range", "bytes=2-5")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert_eq!(response.status_code, 206);
+        assert_eq!(response.headers.get("Content-Range"), Some("bytes 2-5/10"));
+        assert_eq!(response.headers.get("Content-Length"), Some("4"));
+        assert_eq!(response.headers.get("Content-Type"), Some("text/plain"));
+        assert_eq!(response.body_bytes().unwrap(), b"2345");
+        
+        // HEAD carries the same headers
+        let request = HttpRequest::with_headers(Method::HEAD, "/data.txt", &[("Range", "bytes=-3")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert_eq!(response.status_code, 206);
+        assert_eq!(response.headers.get("Content-Range"), Some("bytes 7-9/10"));
+        assert_eq!(response.headers.get("Content-Length"), Some("3"));
+    }
+    
+    #[test]
+    fn test_serve_multiple_ranges() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("data.txt"), "0123456789").unwrap();
+        let server = StaticFileServer::new(temp_dir.path(), None).unwrap();
+        
+        let request = HttpRequest::with_headers(Method::GET, "/data.txt", &[("Range", "bytes=0-1,-2")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert_eq!(response.status_code, 206);
+        
+        let content_type = response.headers.get("Content-Type").unwrap();
+        let boundary = content_type.strip_prefix("multipart/byteranges; boundary=").unwrap();
+        let expected = format!(
+            "\r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\
+             \r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 8-9/10\r\n\r\n89\
+             \r\n--{b}--\r\n",
+            b = boundary
+        );
+        
+        let body = response.body_bytes().unwrap();
+        assert_eq!(String::from_utf8(body).unwrap(), expected);
+        assert_eq!(response.headers.get("Content-Length"), Some(expected.len().to_string().as_str()));
+        assert!(!response.headers.contains_key("Content-Range"));
+    }
+    
+    #[test]
+    fn test_unsatisfiable_range_and_if_range() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("data.txt"), "0123456789").unwrap();
+        let mut server = StaticFileServer::new(temp_dir.path(), None).unwrap();
+        server.set_cache_settings(CacheSettings { etag_enabled: true, ..CacheSettings::default() });
+        
+        let request = HttpRequest::with_headers(Method::GET, "/data.txt", &[("Range", "bytes=50-60")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert_eq!(response.status_code, 416);
+        assert_eq!(response.headers.get("Content-Range"), Some("bytes */10"));
+        
+        // A matching If-Range validator allows the partial response
+        let full = server.serve_file("/data.txt").unwrap();
+        let modified = full.headers.get("Last-Modified").unwrap().to_string();
+        let request = HttpRequest::with_headers(Method::GET, "/data.txt", &[("Range", "bytes=0-0"), ("If-Range", &modified)]);
+        assert_eq!(server.serve_request(&request, &[], false).unwrap().status_code, 206);
+        
+        // A stale or weak validator means the whole file is sent
+        for validator in ["Thu, 01 Jan 1970 00:00:00 GMT", "\"stale\"", "W/\"weak\""] {
+            let request = HttpRequest::with_headers(Method::GET, "/data.txt", &[("Range", "bytes=0-0"), ("If-Range", validator)]);
+            let response = server.serve_request(&request, &[], false).unwrap();
+            assert_eq!(response.status_code, 200);
+            assert_eq!(response.body_bytes().unwrap(), b"0123456789");
+        }
+        
+        // Entity tags compare exactly: the tag of a compressed variant is
+        // not the tag of the file
+        let old = SystemTime::now() - Duration::from_secs(60);
+        File::options().write(true).open(temp_dir.path().join("data.txt")).unwrap().set_modified(old).unwrap();
+        let etag = server.serve_file("/data.txt").unwrap().headers.get("ETag").unwrap().to_string();
+        assert!(!etag.starts_with("W/"));
+        let variant = compress::variant_etag(&etag, Encoding::Gzip);
+        for (validator, status) in [(etag.as_str(), 206), (variant.as_str(), 200)] {
+            let request = HttpRequest::with_headers(Method::GET, "/data.txt", &[("Range", "bytes=0-0"), ("If-Range", validator)]);
+            assert_eq!(server.serve_request(&request, &[], false).unwrap().status_code, status, "{}", validator);
+        }
+    }
+    
+    #[test]
+    fn test_conditional_requests() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("data.txt"), "0123456789").unwrap();
+        let mut server = StaticFileServer::new(temp_dir.path(), None).unwrap();
+        server.set_cache_settings(CacheSettings { etag_enabled: true, ..CacheSettings::default() });
+        
+        let full = server.serve_file("/data.txt").unwrap();
+        let etag = full.headers.get("ETag").unwrap().to_string();
+        let modified = full.headers.get("Last-Modified").unwrap().to_string();
+        assert!(modified.ends_with(" GMT"), "{}", modified);
+        assert_eq!(parse_http_date(&modified).map(format_http_date), Some(modified.clone()));
+        
+        let serve = |headers: &[(&str, &str)]| {
+            server.serve_request(&HttpRequest::with_headers(Method::GET, "/data.txt", headers), &[], false).unwrap()
+        };
+        
+        let response = serve(&[("If-None-Match", &etag)]);
+        assert_eq!(response.status_code, 304);
+        assert_eq!(response.headers.get("ETag"), Some(etag.as_str()));
+        assert_eq!(response.body_len(), 0);
+        assert!(!response.headers.contains_key("Content-Length"));
+        
+        assert_eq!(serve(&[("If-None-Match", "\"other\"")]).status_code, 200);
+        assert_eq!(serve(&[("If-Modified-Since", &modified)]).status_code, 304);
+        assert_eq!(serve(&[("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")]).status_code, 200);
+        assert_eq!(serve(&[("If-Match", "\"other\"")]).status_code, 412);
+        assert_eq!(serve(&[("If-Unmodified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")]).status_code, 412);
+        
+        // Preconditions are checked before the range is applied
+        assert_eq!(serve(&[("If-Match", "*"), ("Range", "bytes=0-1")]).status_code, 206);
+        assert_eq!(serve(&[("If-None-Match", "*"), ("Range", "bytes=0-1")]).status_code, 304);
+        
+        // Without ETags only the modification date is available
+        server.set_cache_settings(CacheSettings::default());
+        assert!(!server.serve_file("/data.txt").unwrap().headers.contains_key("ETag"));
+        let request = HttpRequest::with_headers(Method::GET, "/data.txt", &[("If-None-Match", &etag)]);
+        assert_eq!(server.serve_request(&request, &[], false).unwrap().status_code, 200);
+    }
+    
+    #[test]
+    fn test_precompressed_sibling() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("app.js"), "console.log(1);").unwrap();
+        fs::write(temp_dir.path().join("app.js.gz"), compress::gzip(b"console.log(1);")).unwrap();
+        fs::write(temp_dir.path().join("logo.png"), "png").unwrap();
+        let server = StaticFileServer::new(temp_dir.path(), None).unwrap();
+        
+        let request = HttpRequest::with_headers(Method::GET, "/app.js", &[("Accept-Encoding", "gzip, deflate")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert_eq!(response.headers.get("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.headers.get("Content-Type"), Some("application/javascript"));
+        assert_eq!(response.headers.get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.body_bytes().unwrap(), compress::gzip(b"console.log(1);"));
+        
+        // Clients that do not accept gzip get the original file
+        let request = HttpRequest::with_headers(Method::GET, "/app.js", &[("Accept-Encoding", "gzip;q=0")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert!(!response.headers.contains_key("Content-Encoding"));
+        assert_eq!(response.headers.get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.body_bytes().unwrap(), b"console.log(1);");
+        
+        // Binary files without a sibling do not vary
+        let request = HttpRequest::with_headers(Method::GET, "/logo.png", &[("Accept-Encoding", "gzip")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert!(!response.headers.contains_key("Vary"));
+        
+        // A sibling older than the file is stale and ignored
+        let stale = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options().write(true).open(temp_dir.path().join("app.js.gz")).unwrap().set_modified(stale).unwrap();
+        let request = HttpRequest::with_headers(Method::GET, "/app.js", &[("Accept-Encoding", "gzip")]);
+        let response = server.serve_request(&request, &[], false).unwrap();
+        assert!(!response.headers.contains_key("Content-Encoding"));
+        assert_eq!(response.body_bytes().unwrap(), b"console.log(1);");
+        
+        // A sibling alone is not served for a missing file
+        fs::remove_file(temp_dir.path().join("app.js")).unwrap();
+        let request = HttpRequest::with_headers(Method::GET, "/app.js", &[("Accept-Encoding", "gzip")]);
+        assert_eq!(server.serve_request(&request, &[], false).unwrap().status_code, 404);
     }
     
     #[test]
@@ -18,6 +18,12 @@ pub enum ChunkedState {
     Error(String),
 }
 
+/// Longest chunk-size or trailer line accepted
+const MAX_LINE_LENGTH: usize = 8192;
+
+/// Maximum number of trailer headers
+const MAX_TRAILERS: usize = 64;
+
 /// Chunked transfer decoder for incoming requests
 #[derive(Debug)]
 pub struct ChunkedDecoder {
@@ -48,161 +54,156 @@ impl ChunkedDecoder {
         }
     }
     
-    /// Process incoming data and return number of bytes consumed
+    /// Process incoming data and return number of bytes consumed.
+    /// Decoding stops at the end of the chunked body, so any bytes after it
+    /// (the next pipelined request) are left unconsumed.
     pub fn process(&mut self, data: &[u8]) -> io::Result<usize> {
-        let mut consumed = 0;
+        let result = self.process_data(data);
+        if let Err(e) = &result {
+            self.state = ChunkedState::Error(e.to_string());
+        }
+        result
+    }
+    
+    fn process_data(&mut self, data: &[u8]) -> io::Result<usize> {
         let mut pos = 0;
         
         while pos < data.len() {
-            match &self.state {
+            match self.state.clone() {
                 ChunkedState::ChunkSize => {
-                    let (bytes_consumed, new_state) = self.parse_chunk_size(&data[pos..])?;
+                    let (bytes_consumed, line) = self.read_line(&data[pos..])?;
                     pos += bytes_consumed;
-                    consumed += bytes_consumed;
-                    self.state = new_state;
+                    if let Some(line) = line {
+                        self.state = self.parse_chunk_size(&line)?;
+                    }
                 }
                 ChunkedState::ChunkData { size, read } => {
                     let remaining = size - read;
                     let available = data.len() - pos;
                     let to_read = remaining.min(available);
                     
-                    // Check body size limit
-                    if self.body.len() + to_read > self.max_body_size {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "Chunked body exceeds maximum size"
-                        ));
-                    }
-                    
                     self.body.extend_from_slice(&data[pos..pos + to_read]);
                     pos += to_read;
-                    consumed += to_read;
                     
                     let new_read = read + to_read;
-                    if new_read == *size {
+                    if new_read == size {
                         self.state = ChunkedState::ChunkTrailer;
                     } else {
-                        self.state = ChunkedState::ChunkData { size: *size, read: new_read };
+                        self.state = ChunkedState::ChunkData { size, read: new_read };
                     }
                 }
                 ChunkedState::ChunkTrailer => {
-                    let (bytes_consumed, new_state) = self.parse_chunk_trailer(&data[pos..])?;
+                    let (bytes_consumed, line) = self.read_line(&data[pos..])?;
                     pos += bytes_consumed;
-                    consumed += bytes_consumed;
-                    self.state = new_state;
+                    if let Some(line) = line {
+                        if !line.is_empty() {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected CRLF after chunk data"));
+                        }
+                        self.state = ChunkedState::ChunkSize;
+                    }
                 }
                 ChunkedState::Trailer => {
-                    let (bytes_consumed, new_state) = self.parse_trailer(&data[pos..])?;
+                    let (bytes_consumed, line) = self.read_line(&data[pos..])?;
                     pos += bytes_consumed;
-                    consumed += bytes_consumed;
-                    self.state = new_state;
+                    if let Some(line) = line {
+                        if line.is_empty() {
+                            self.state = ChunkedState::Complete;
+                        } else {
+                            self.parse_trailer(&line)?;
+                        }
+                    }
                 }
                 ChunkedState::Complete => {
                     break;
                 }
-                ChunkedState::Error(ref msg) => {
-                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg.clone()));
+                ChunkedState::Error(msg) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
                 }
             }
         }
         
-        Ok(consumed)
+        Ok(pos)
     }
     
-    /// Parse chunk size line
-    fn parse_chunk_size(&mut self, data: &[u8]) -> io::Result<(usize, ChunkedState)> {
-        self.buffer.extend_from_slice(data);
-        
-        // Look for CRLF
-        if let Some(crlf_pos) = find_crlf(&self.buffer) {
-            let line = &self.buffer[..crlf_pos];
-            let line_str = std::str::from_utf8(line)
-                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8 in chunk size"))?;
-            
-            // Parse hex chunk size (ignore extensions after semicolon)
-            let size_str = line_str.split(';').next().unwrap_or(line_str).trim();
-            let chunk_size = usize::from_str_radix(size_str, 16)
-                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid chunk size"))?;
-            
-            // Check chunk size limit
-            if chunk_size > self.max_chunk_size {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Chunk size exceeds maximum allowed"
-                ));
+    /// Buffer bytes up to and including the next LF. Returns the bytes consumed
+    /// and, once the line is complete, its content without the CRLF.
+    fn read_line(&mut self, data: &[u8]) -> io::Result<(usize, Option<Vec<u8>>)> {
+        match data.iter().position(|&b| b == b'\n') {
+            Some(lf_pos) => {
+                self.buffer.extend_from_slice(&data[..=lf_pos]);
+                let mut line = std::mem::take(&mut self.buffer);
+                
+                if line.len() < 2 || line[line.len() - 2] != b'\r' {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Chunked line not terminated by CRLF"));
+                }
+                line.truncate(line.len() - 2);
+                Ok((lf_pos + 1, Some(line)))
             }
-            
-            let consumed = crlf_pos + 2;
-            self.buffer.drain(..consumed);
-            
-            if chunk_size == 0 {
-                // Final chunk, move to trailer parsing
-                Ok((consumed, ChunkedState::Trailer))
-            } else {
-                Ok((consumed, ChunkedState::ChunkData { size: chunk_size, read: 0 }))
+            None => {
+                self.buffer.extend_from_slice(data);
+                if self.buffer.len() > MAX_LINE_LENGTH {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Chunked line too long"));
+                }
+                Ok((data.len(), None))
             }
-        } else {
-            // Need more data
-            Ok((data.len(), ChunkedState::ChunkSize))
         }
     }
     
-    /// Parse chunk trailer (CRLF after chunk data)
-    fn parse_chunk_trailer(&mut self, data: &[u8]) -> io::Result<(usize, ChunkedState)> {
-        self.buffer.extend_from_slice(data);
+    /// Parse chunk size line
+    fn parse_chunk_size(&mut self, line: &[u8]) -> io::Result<ChunkedState> {
+        let line_str = std::str::from_utf8(line)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8 in chunk size"))?;
         
-        if self.buffer.len() >= 2 && &self.buffer[..2] == b"\r\n" {
-            self.buffer.drain(..2);
-            Ok((2, ChunkedState::ChunkSize))
-        } else if self.buffer.len() >= 1 {
-            if self.buffer[0] == b'\r' {
-                // Need more data for LF
-                Ok((data.len(), ChunkedState::ChunkTrailer))
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "Expected CRLF after chunk data"))
-            }
+        // Parse hex chunk size (ignore extensions after semicolon)
+        let size_str = line_str.split(';').next().unwrap_or(line_str).trim_end_matches([' ', '\t']);
+        if size_str.is_empty() || !size_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid chunk size"));
+        }
+        let chunk_size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid chunk size"))?;
+        
+        // Check chunk size limit
+        if chunk_size > self.max_chunk_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                ChunkedError::ChunkTooLarge(chunk_size, self.max_chunk_size),
+            ));
+        }
+        
+        // Reject as soon as the declared size would overflow the body limit
+        let total = self.body.len().saturating_add(chunk_size);
+        if total > self.max_body_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                ChunkedError::BodyTooLarge(total, self.max_body_size),
+            ));
+        }
+        
+        if chunk_size == 0 {
+            // Final chunk, move to trailer parsing
+            Ok(ChunkedState::Trailer)
         } else {
-            // Need more data
-            Ok((data.len(), ChunkedState::ChunkTrailer))
+            Ok(ChunkedState::ChunkData { size: chunk_size, read: 0 })
         }
     }
     
-    /// Parse trailer headers
-    fn parse_trailer(&mut self, data: &[u8]) -> io::Result<(usize, ChunkedState)> {
-        self.buffer.extend_from_slice(data);
+    /// Parse one trailer header line
+    fn parse_trailer(&mut self, line: &[u8]) -> io::Result<()> {
+        let line = std::str::from_utf8(line)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8 in trailer headers"))?;
         
-        // Look for end of headers (empty line)
-        if let Some(empty_line_pos) = find_empty_line(&self.buffer) {
-            let headers_data = &self.buffer[..empty_line_pos];
-            
-            // Parse trailer headers
-            let headers_str = std::str::from_utf8(headers_data)
-                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8 in trailer headers"))?;
-            
-            for line in headers_str.lines() {
-                if line.trim().is_empty() {
-                    continue;
-                }
-                
-                if let Some(colon_pos) = line.find(':') {
-                    let name = line[..colon_pos].trim().to_string();
-                    let value = line[colon_pos + 1..].trim().to_string();
-                    self.trailer_headers.push((name, value));
-                }
-            }
-            
-            let consumed = empty_line_pos + 2;
-            self.buffer.drain(..consumed);
-            Ok((consumed, ChunkedState::Complete))
-        } else {
-            // Check for immediate end (just CRLF)
-            if self.buffer.len() >= 2 && &self.buffer[..2] == b"\r\n" {
-                self.buffer.drain(..2);
-                Ok((2, ChunkedState::Complete))
-            } else {
-                // Need more data
-                Ok((data.len(), ChunkedState::Trailer))
+        if self.trailer_headers.len() >= MAX_TRAILERS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Too many trailer headers"));
+        }
+        
+        match line.find(':') {
+            Some(colon_pos) if colon_pos > 0 => {
+                let name = line[..colon_pos].trim().to_string();
+                let value = line[colon_pos + 1..].trim().to_string();
+                self.trailer_headers.push((name, value));
+                Ok(())
             }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid trailer header")),
         }
     }
     
@@ -488,7 +489,7 @@ mod tests {
         
         // Process data incrementally
         let consumed1 = decoder.process(b"5\r\n").unwrap();
-        assert_eq!(consumed1, 4);
+        assert_eq!(consumed1, 3);
         assert!(!decoder.is_complete());
         
         let consumed2 = decoder.process(b"Hello\r\n").unwrap();
@@ -0,0 +1,562 @@
+/// Response bodies smaller than this are sent uncompressed; the gzip
+/// framing alone would eat most of the savings
+pub const MIN_COMPRESS_SIZE: u64 = 1024;
+
+/// Larger bodies are sent uncompressed rather than buffered in memory
+pub const MAX_COMPRESS_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Content codings this server can produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+    
+    /// Encode `data` with this content coding
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Gzip => gzip(data),
+            Encoding::Deflate => zlib(data),
+        }
+    }
+}
+
+/// Entity tag for a compressed variant of a representation, so caches
+/// never confuse it with the unencoded one: `"abc"` becomes `"abc-gzip"`
+pub fn variant_etag(etag: &str, encoding: Encoding) -> String {
+    match etag.strip_suffix('"') {
+        Some(opaque) => format!("{}-{}\"", opaque, encoding.as_str()),
+        None => etag.to_string(),
+    }
+}
+
+/// Reverse of `variant_etag`; tags of unencoded representations are returned as is
+pub fn base_etag(etag: &str) -> String {
+    for encoding in [Encoding::Gzip, Encoding::Deflate] {
+        if let Some(opaque) = etag.strip_suffix(&format!("-{}\"", encoding.as_str())) {
+            return format!("{}\"", opaque);
+        }
+    }
+    etag.to_string()
+}
+
+/// Pick a content coding from an `Accept-Encoding` header value
+/// (RFC 9110 section 12.5.3) among `available`, in order of preference.
+/// Returns `None` when the response should be sent as is.
+pub fn negotiate(accept_encoding: &str, available: &[Encoding]) -> Option<Encoding> {
+    let mut wildcard = None;
+    let mut identity = None;
+    let mut listed: Vec<(&str, f32)> = Vec::new();
+    
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let coding = params.next().unwrap_or("").trim();
+        if coding.is_empty() {
+            continue;
+        }
+        
+        let mut quality = Some(1.0);
+        for param in params {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    quality = parse_quality(value.trim());
+                }
+            }
+        }
+        let quality = match quality {
+            Some(quality) => quality,
+            None => continue,
+        };
+        
+        if coding == "*" {
+            wildcard = Some(quality);
+        } else if coding.eq_ignore_ascii_case("identity") {
+            identity = Some(quality);
+        } else {
+            listed.push((coding, quality));
+        }
+    }
+    
+    let quality_of = |encoding: &Encoding| -> f32 {
+        listed.iter()
+            .find(|(coding, _)| {
+                coding.eq_ignore_ascii_case(encoding.as_str())
+                    || (*encoding == Encoding::Gzip && coding.eq_ignore_ascii_case("x-gzip"))
+            })
+            .map(|(_, quality)| *quality)
+            .or(wildcard)
+            .unwrap_or(0.0)
+    };
+    
+    // Earlier entries in `available` win ties
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in available {
+        let quality = quality_of(encoding);
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((*encoding, quality));
+        }
+    }
+    
+    match (best, identity) {
+        // The client explicitly prefers the unencoded form
+        (Some((_, quality)), Some(identity)) if identity > quality => None,
+        (best, _) => best.map(|(encoding, _)| encoding),
+    }
+}
+
+/// Parse a qvalue: `0`, `1` or a fraction with at most three decimals
+fn parse_quality(value: &str) -> Option<f32> {
+    let valid = match value.split_once('.') {
+        Some((whole, fraction)) => {
+            (whole == "0" || whole == "1")
+                && fraction.len() <= 3
+                && fraction.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => value == "0" || value == "1",
+    };
+    if !valid {
+        return None;
+    }
+    value.parse::<f32>().ok().filter(|q| *q <= 1.0)
+}
+
+/// gzip file format (RFC 1952) around a DEFLATE stream
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // Magic, CM=deflate, no flags, no mtime, no extra flags, OS=Unix
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// zlib format (RFC 1950), which is what the `deflate` content coding means
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    // CM=8 with a 32K window; FCHECK makes the header a multiple of 31
+    let mut out = vec![0x78, 0x9c];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// CRC-32 as used by gzip
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 { 0xedb8_8320 ^ (value >> 1) } else { value >> 1 };
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+};
+
+/// Adler-32 as used by zlib
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest run that cannot overflow before the modulo
+    for block in data.chunks(5552) {
+        for &byte in block {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+/// How many earlier positions with the same hash are tried per match
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// Raw DEFLATE stream (RFC 1951): LZ77 matching with hash chains, emitted
+/// as a single block using the fixed Huffman codes
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    
+    // BFINAL=1, BTYPE=01 (fixed Huffman codes)
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+    
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+    let mut pos = 0;
+    
+    while pos < data.len() {
+        let (length, distance) = longest_match(data, pos, &head, &prev);
+        
+        if length >= MIN_MATCH {
+            write_length(&mut writer, length);
+            write_distance(&mut writer, distance);
+            for i in pos..pos + length {
+                insert_hash(data, i, &mut head, &mut prev);
+            }
+            pos += length;
+        } else {
+            write_literal(&mut writer, data[pos] as u16);
+            insert_hash(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+    
+    // End of block
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+fn hash_at(data: &[u8], pos: usize) -> Option<usize> {
+    if pos + MIN_MATCH > data.len() {
+        return None;
+    }
+    let value = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+    Some((value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize)
+}
+
+fn insert_hash(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if let Some(hash) = hash_at(data, pos) {
+        prev[pos % WINDOW_SIZE] = head[hash];
+        head[hash] = pos;
+    }
+}
+
+/// Longest earlier match for the bytes at `pos`, as (length, distance)
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    let hash = match hash_at(data, pos) {
+        Some(hash) => hash,
+        None => return (0, 0),
+    };
+    
+    let max_length = MAX_MATCH.min(data.len() - pos);
+    let mut best = (0, 0);
+    let mut candidate = head[hash];
+    let mut chain = 0;
+    
+    while candidate != usize::MAX && pos - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+        let length = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_length])
+            .take_while(|(a, b)| a == b)
+            .count();
+        
+        if length > best.0 {
+            best = (length, pos - candidate);
+            if length == max_length {
+                break;
+            }
+        }
+        
+        let next = prev[candidate % WINDOW_SIZE];
+        // Slots are reused once the window wraps; stop at a stale link
+        if next == usize::MAX || next >= candidate {
+            break;
+        }
+        candidate = next;
+        chain += 1;
+    }
+    
+    best
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u16) {
+    let (code, bits) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    writer.write_code(code as u32, bits);
+}
+
+fn write_length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap_or(0);
+    write_literal(writer, 257 + index as u16);
+    writer.write_bits((length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+}
+
+fn write_distance(writer: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap_or(0);
+    writer.write_code(index as u32, 5);
+    writer.write_bits((distance - DISTANCE_BASE[index] as usize) as u32, DISTANCE_EXTRA[index] as u32);
+}
+
+/// Packs bits least-significant first, as DEFLATE requires
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter { out: Vec::new(), buffer: 0, count: 0 }
+    }
+    
+    fn write_bits(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+    
+    /// Huffman codes are defined most-significant bit first
+    fn write_code(&mut self, code: u32, bits: u32) {
+        let reversed = code.reverse_bits() >> (32 - bits);
+        self.write_bits(reversed, bits);
+    }
+    
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::response::HttpResponse;
+    use crate::mime::MimeTypes;
+    
+    /// Minimal inflater for the fixed-Huffman blocks `deflate` produces
+    fn inflate_fixed(data: &[u8]) -> Vec<u8> {
+        let mut bit_pos = 0;
+        let mut read_bits = |bits: u32| -> u32 {
+            let mut value = 0;
+            for i in 0..bits {
+                let bit = (data[bit_pos / 8] >> (bit_pos % 8)) & 1;
+                value |= (bit as u32) << i;
+                bit_pos += 1;
+            }
+            value
+        };
+        
+        assert_eq!(read_bits(1), 1, "single final block");
+        assert_eq!(read_bits(2), 1, "fixed Huffman block");
+        
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            // Read a fixed literal/length code most-significant bit first
+            let mut code = 0;
+            let mut bits = 0;
+            let symbol = loop {
+                code = (code << 1) | read_bits(1);
+                bits += 1;
+                match (bits, code) {
+                    (7, 0..=0x17) => break code + 256,
+                    (8, 0x30..=0xbf) => break code - 0x30,
+                    (8, 0xc0..=0xc7) => break code - 0xc0 + 280,
+                    (9, 0x190..=0x1ff) => break code - 0x190 + 144,
+                    _ => assert!(bits < 9, "invalid code"),
+                }
+            };
+            
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => return out,
+                _ => {
+                    let index = (symbol - 257) as usize;
+                    let length = LENGTH_BASE[index] as usize + read_bits(LENGTH_EXTRA[index] as u32) as usize;
+                    let mut dist_code = 0;
+                    for _ in 0..5 {
+                        dist_code = (dist_code << 1) | read_bits(1);
+                    }
+                    let index = dist_code as usize;
+                    let distance = DISTANCE_BASE[index] as usize + read_bits(DISTANCE_EXTRA[index] as u32) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b""), 1);
+    }
+    
+    #[test]
+    fn test_deflate_round_trip() {
+        let text = "<p>Hello, compressed world!</p>\n".repeat(200);
+        let mut binary: Vec<u8> = (0..70_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        binary.extend(std::iter::repeat_n(b'a', 1000));
+        
+        for input in [b"".to_vec(), b"a".to_vec(), b"abcabcabcabc".to_vec(), text.into_bytes(), binary] {
+            let compressed = deflate(&input);
+            assert_eq!(inflate_fixed(&compressed), input);
+        }
+        
+        let repetitive = vec![b'x'; 100_000];
+        assert!(deflate(&repetitive).len() < 1000);
+    }
+    
+    #[test]
+    fn test_gzip_and_zlib_framing() {
+        let data = b"hello hello hello hello";
+        
+        let gz = gzip(data);
+        assert_eq!(&gz[..4], &[0x1f, 0x8b, 8, 0]);
+        assert_eq!(inflate_fixed(&gz[10..gz.len() - 8]), data);
+        assert_eq!(&gz[gz.len() - 8..gz.len() - 4], &crc32(data).to_le_bytes());
+        assert_eq!(&gz[gz.len() - 4..], &(data.len() as u32).to_le_bytes());
+        
+        let z = zlib(data);
+        assert_eq!(((z[0] as u16) << 8 | z[1] as u16) % 31, 0);
+        assert_eq!(inflate_fixed(&z[2..z.len() - 4]), data);
+        assert_eq!(&z[z.len() - 4..], &adler32(data).to_be_bytes());
+    }
+    
+    #[test]
+    fn test_output_matches_zlib() {
+        // zlib 1.2.13 with Z_FIXED at level 6; its headers only differ in
+        // the compression level hint (FLEVEL, XFL), which decoders ignore
+        let vectors: [(&[u8], &[u8], &[u8]); 5] = [
+            (&b""[..], &[0x78, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01], &[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+            (&b"a"[..], &[0x78, 0x01, 0x4b, 0x04, 0x00, 0x00, 0x62, 0x00, 0x62], &[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x4b, 0x04, 0x00, 0x43, 0xbe, 0xb7, 0xe8, 0x01, 0x00, 0x00, 0x00]),
+            (&b"Wikipedia"[..], &[0x78, 0x01, 0x0b, 0xcf, 0xcc, 0xce, 0x2c, 0x48, 0x4d, 0xc9, 0x4c, 0x04, 0x00, 0x11, 0xe6, 0x03, 0x98], &[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x0b, 0xcf, 0xcc, 0xce, 0x2c, 0x48, 0x4d, 0xc9, 0x4c, 0x04, 0x00, 0x2e, 0xc0, 0xaa, 0xad, 0x09, 0x00, 0x00, 0x00]),
+            (&b"Hello, World!"[..], &[0x78, 0x01, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0x08, 0xcf, 0x2f, 0xca, 0x49, 0x51, 0x04, 0x00, 0x1f, 0x9e, 0x04, 0x6a], &[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0x08, 0xcf, 0x2f, 0xca, 0x49, 0x51, 0x04, 0x00, 0xd0, 0xc3, 0x4a, 0xec, 0x0d, 0x00, 0x00, 0x00]),
+            (&b"GET /index.html HTTP/1.1\r\nHost: localhost\r\n\r\n"[..], &[0x78, 0x01, 0x73, 0x77, 0x0d, 0x51, 0xd0, 0xcf, 0xcc, 0x4b, 0x49, 0xad, 0xd0, 0xcb, 0x28, 0xc9, 0xcd, 0x51, 0xf0, 0x08, 0x09, 0x09, 0xd0, 0x37, 0xd4, 0x33, 0xe4, 0xe5, 0xf2, 0xc8, 0x2f, 0x2e, 0xb1, 0x52, 0xc8, 0xc9, 0x4f, 0x4e, 0xcc, 0xc9, 0x00, 0x32, 0x79, 0xb9, 0x78, 0xb9, 0x00, 0x3d, 0x92, 0x0d, 0x50], &[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x73, 0x77, 0x0d, 0x51, 0xd0, 0xcf, 0xcc, 0x4b, 0x49, 0xad, 0xd0, 0xcb, 0x28, 0xc9, 0xcd, 0x51, 0xf0, 0x08, 0x09, 0x09, 0xd0, 0x37, 0xd4, 0x33, 0xe4, 0xe5, 0xf2, 0xc8, 0x2f, 0x2e, 0xb1, 0x52, 0xc8, 0xc9, 0x4f, 0x4e, 0xcc, 0xc9, 0x00, 0x32, 0x79, 0xb9, 0x78, 0xb9, 0x00, 0x1a, 0x32, 0xab, 0xb5, 0x2d, 0x00, 0x00, 0x00]),
+        ];
+        for (input, zlib_output, gzip_output) in vectors {
+            assert_eq!(zlib(input)[2..], zlib_output[2..], "zlib {:?}", input);
+            assert_eq!(gzip(input)[10..], gzip_output[10..], "gzip {:?}", input);
+        }
+        
+        // zlib starts repeats one literal later, so its raw streams differ;
+        // they still decode with the inflater the round trips rely on
+        let deferred: [(&[u8], &[u8]); 3] = [
+            (&b"aaaaaaaaaa"[..], &[0x4b, 0x4c, 0x84, 0x01, 0x00]),
+            (&b"abcabcabcabc"[..], &[0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00]),
+            (&b"hello hello hello hello"[..], &[0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01]),
+        ];
+        for (input, stream) in deferred {
+            assert_eq!(inflate_fixed(stream), input);
+        }
+    }
+    
+    #[test]
+    fn test_compress_response() {
+        let mime_types = MimeTypes::new();
+        let html = "<li>item</li>\n".repeat(200);
+        let page = |body: &str| {
+            let mut response = HttpResponse::ok();
+            response.set_header("Content-Type", "text/html; charset=utf-8");
+            response.set_header("ETag", "\"v1\"");
+            response.set_body_string(body);
+            response
+        };
+        
+        let mut response = page(&html);
+        response.compress(Some("deflate;q=0.5, gzip"), &mime_types).unwrap();
+        assert_eq!(response.headers.get("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.headers.get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers.get("ETag"), Some("\"v1-gzip\""));
+        assert_eq!(response.headers.get("Content-Length"), Some(response.body.len().to_string().as_str()));
+        assert_eq!(inflate_fixed(&response.body[10..response.body.len() - 8]), html.as_bytes());
+        
+        // Without an acceptable coding the body is unchanged, but still varies
+        let mut response = page(&html);
+        response.compress(Some("br"), &mime_types).unwrap();
+        assert!(!response.headers.contains_key("Content-Encoding"));
+        assert_eq!(response.headers.get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.body, html.as_bytes());
+        
+        // Bodies below the threshold are not worth compressing
+        let mut response = page("<p>short</p>");
+        response.compress(Some("gzip"), &mime_types).unwrap();
+        assert!(!response.headers.contains_key("Content-Encoding"));
+        
+        // Neither are binary types or non-200 responses
+        let mut response = page(&html);
+        response.set_header("Content-Type", "image/png");
+        response.compress(Some("gzip"), &mime_types).unwrap();
+        assert!(!response.headers.contains_key("Content-Encoding"));
+        assert!(!response.headers.contains_key("Vary"));
+        
+        let mut response = page(&html);
+        response.status_code = 404;
+        response.compress(Some("gzip"), &mime_types).unwrap();
+        assert!(!response.headers.contains_key("Content-Encoding"));
+    }
+    
+    #[test]
+    fn test_variant_etag() {
+        assert_eq!(variant_etag("\"abc\"", Encoding::Gzip), "\"abc-gzip\"");
+        assert_eq!(variant_etag("W/\"abc\"", Encoding::Deflate), "W/\"abc-deflate\"");
+        assert_eq!(base_etag("\"abc-gzip\""), "\"abc\"");
+        assert_eq!(base_etag("W/\"abc-deflate\""), "W/\"abc\"");
+        assert_eq!(base_etag("\"abc\""), "\"abc\"");
+    }
+    
+    #[test]
+    fn test_negotiate() {
+        let both = [Encoding::Gzip, Encoding::Deflate];
+        
+        assert_eq!(negotiate("gzip, deflate, br", &both), Some(Encoding::Gzip));
+        assert_eq!(negotiate("deflate", &both), Some(Encoding::Deflate));
+        assert_eq!(negotiate("gzip;q=0.5, deflate;q=0.8", &both), Some(Encoding::Deflate));
+        assert_eq!(negotiate("x-gzip", &both), Some(Encoding::Gzip));
+        assert_eq!(negotiate("*", &both), Some(Encoding::Gzip));
+        assert_eq!(negotiate("*;q=0.1, gzip;q=0", &both), Some(Encoding::Deflate));
+        assert_eq!(negotiate("br", &both), None);
+        assert_eq!(negotiate("", &both), None);
+        assert_eq!(negotiate("gzip;q=0", &both), None);
+        assert_eq!(negotiate("gzip;q=0.5, identity", &both), None);
+        assert_eq!(negotiate("gzip, identity;q=0.5", &both), Some(Encoding::Gzip));
+        assert_eq!(negotiate("deflate", &[Encoding::Gzip]), None);
+        
+        // Malformed qvalues make the entry unusable
+        assert_eq!(negotiate("gzip;q=2", &both), None);
+        assert_eq!(negotiate("gzip;q=0.1234", &both), None);
+        assert_eq!(negotiate("gzip;q=abc, deflate", &both), Some(Encoding::Deflate));
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::http::compress::base_etag;
+use crate::http::date::parse_http_date;
+use crate::http::request::{HttpRequest, Method};
+
+/// Validators of the selected representation, compared against the
+/// request's precondition headers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    /// Entity tag including quotes, with a `W/` prefix when weak
+    pub etag: Option<String>,
+    /// Modification time in seconds since the Unix epoch
+    pub last_modified: Option<u64>,
+}
+
+/// Outcome of evaluating the precondition headers (RFC 9110 section 13.2.2)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Precondition {
+    /// Serve the request normally
+    Proceed,
+    /// Answer 304 Not Modified
+    NotModified,
+    /// Answer 412 Precondition Failed
+    Failed,
+}
+
+impl Precondition {
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            Precondition::Proceed => None,
+            Precondition::NotModified => Some(304),
+            Precondition::Failed => Some(412),
+        }
+    }
+}
+
+impl Validators {
+    /// Validators for a file: an ETag built from inode, size and modification
+    /// time, and the modification time itself. The ETag is weak when the file
+    /// changed within the last second, since a second write in that same
+    /// second would not alter the modification time we can observe.
+    pub fn for_file(metadata: &Metadata) -> Self {
+        let modified = metadata.modified().ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+        
+        let etag = modified.map(|mtime| {
+            let tag = format!(
+                "\"{:x}-{:x}-{:x}.{:x}\"",
+                metadata.ino(), metadata.len(), mtime.as_secs(), mtime.subsec_nanos()
+            );
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            if now.as_secs() <= mtime.as_secs() {
+                format!("W/{}", tag)
+            } else {
+                tag
+            }
+        });
+        
+        Validators {
+            etag,
+            last_modified: modified.map(|mtime| mtime.as_secs()),
+        }
+    }
+    
+    /// Evaluate If-Match, If-Unmodified-Since, If-None-Match and
+    /// If-Modified-Since in the order RFC 9110 prescribes
+    pub fn evaluate(&self, request: &HttpRequest) -> Precondition {
+        let is_get_or_head = matches!(request.method, Method::GET | Method::HEAD);
+        
+        // Step 1 and 2: the representation must still be the one the client expects
+        if let Some(if_match) = request.headers.get_combined("if-match") {
+            if !self.matches_any(&if_match, true) {
+                return Precondition::Failed;
+            }
+        } else if let Some(since) = request.get_header("if-unmodified-since").and_then(parse_http_date) {
+            if self.last_modified.is_some_and(|modified| modified > since) {
+                return Precondition::Failed;
+            }
+        }
+        
+        // Step 3 and 4: the client already holds the current representation
+        if let Some(if_none_match) = request.headers.get_combined("if-none-match") {
+            if self.matches_any(&if_none_match, false) {
+                return if is_get_or_head { Precondition::NotModified } else { Precondition::Failed };
+            }
+        } else if is_get_or_head {
+            if let Some(since) = request.get_header("if-modified-since").and_then(parse_http_date) {
+                if self.last_modified.is_some_and(|modified| modified <= since) {
+                    return Precondition::NotModified;
+                }
+            }
+        }
+        
+        Precondition::Proceed
+    }
+    
+    /// Whether an entity tag list matches the current ETag. `*` matches any
+    /// existing representation; strong comparison ignores weak tags and
+    /// needs the exact tag (RFC 9110 section 8.8.3.2).
+    fn matches_any(&self, list: &str, strong: bool) -> bool {
+        if list.trim() == "*" {
+            return true;
+        }
+        
+        let current = match &self.etag {
+            Some(etag) => etag,
+            None => return false,
+        };
+        if strong && current.starts_with("W/") {
+            return false;
+        }
+        
+        list.split(',')
+            .map(|tag| tag.trim())
+            .filter(|tag| !(strong && tag.starts_with("W/")))
+            .any(|tag| if strong { tag == current } else { opaque_tag(tag) == opaque_tag(current) })
+    }
+}
+
+/// The quoted part of an entity tag, without any weakness prefix, for weak
+/// comparison. Tags of compressed variants match the representation they
+/// were derived from.
+fn opaque_tag(tag: &str) -> String {
+    base_etag(tag.strip_prefix("W/").unwrap_or(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_if_none_match_and_if_modified_since() {
+        let validators = Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some(784111777),
+        };
+        let evaluate = |method, headers: &[(&str, &str)]| validators.evaluate(&HttpRequest::with_headers(method, "/", headers));
+        
+        assert_eq!(evaluate(Method::GET, &[]), Precondition::Proceed);
+        assert_eq!(evaluate(Method::GET, &[("If-None-Match", "\"xyz\", W/\"abc\"")]), Precondition::NotModified);
+        assert_eq!(evaluate(Method::HEAD, &[("If-None-Match", "*")]), Precondition::NotModified);
+        assert_eq!(evaluate(Method::GET, &[("If-None-Match", "\"xyz\"")]), Precondition::Proceed);
+        assert_eq!(evaluate(Method::GET, &[("If-None-Match", "\"abc-gzip\"")]), Precondition::NotModified);
+        assert_eq!(evaluate(Method::DELETE, &[("If-None-Match", "\"abc\"")]), Precondition::Failed);
+        
+        assert_eq!(evaluate(Method::GET, &[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]), Precondition::NotModified);
+        assert_eq!(evaluate(Method::GET, &[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:36 GMT")]), Precondition::Proceed);
+        assert_eq!(evaluate(Method::GET, &[("If-Modified-Since", "Sunday, 06-Nov-94 08:49:37 GMT")]), Precondition::NotModified);
+        assert_eq!(evaluate(Method::GET, &[("If-Modified-Since", "yesterday")]), Precondition::Proceed);
+        
+        // If-None-Match takes precedence over If-Modified-Since
+        let headers = [("If-None-Match", "\"xyz\""), ("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")];
+        assert_eq!(evaluate(Method::GET, &headers), Precondition::Proceed);
+    }
+    
+    #[test]
+    fn test_if_match_and_if_unmodified_since() {
+        let validators = Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some(784111777),
+        };
+        let evaluate = |headers: &[(&str, &str)]| validators.evaluate(&HttpRequest::with_headers(Method::GET, "/", headers));
+        
+        assert_eq!(evaluate(&[("If-Match", "\"abc\"")]), Precondition::Proceed);
+        assert_eq!(evaluate(&[("If-Match", "*")]), Precondition::Proceed);
+        assert_eq!(evaluate(&[("If-Match", "\"xyz\"")]), Precondition::Failed);
+        assert_eq!(evaluate(&[("If-Match", "W/\"abc\"")]), Precondition::Failed);
+        
+        assert_eq!(evaluate(&[("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]), Precondition::Proceed);
+        assert_eq!(evaluate(&[("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:36 GMT")]), Precondition::Failed);
+        
+        // A weak current ETag never satisfies If-Match
+        let weak = Validators { etag: Some("W/\"abc\"".to_string()), last_modified: None };
+        assert_eq!(weak.evaluate(&HttpRequest::with_headers(Method::GET, "/", &[("If-Match", "\"abc\"")])), Precondition::Failed);
+        assert_eq!(weak.evaluate(&HttpRequest::with_headers(Method::GET, "/", &[("If-None-Match", "\"abc\"")])), Precondition::NotModified);
+    }
+    
+    #[test]
+    fn test_compressed_variant_tags_match_only_weakly() {
+        let evaluate = |current: &str, headers: &[(&str, &str)]| {
+            let validators = Validators { etag: Some(current.to_string()), last_modified: None };
+            validators.evaluate(&HttpRequest::with_headers(Method::GET, "/", headers))
+        };
+        
+        // If-None-Match uses weak comparison, so variants fold onto their base
+        assert_eq!(evaluate("\"abc\"", &[("If-None-Match", "\"abc-gzip\"")]), Precondition::NotModified);
+        assert_eq!(evaluate("\"abc-gzip\"", &[("If-None-Match", "\"abc\"")]), Precondition::NotModified);
+        
+        // If-Match uses strong comparison, which needs the exact tag
+        assert_eq!(evaluate("\"abc\"", &[("If-Match", "\"abc-gzip\"")]), Precondition::Failed);
+        assert_eq!(evaluate("\"abc-gzip\"", &[("If-Match", "\"abc\"")]), Precondition::Failed);
+        assert_eq!(evaluate("\"abc-gzip\"", &[("If-Match", "\"abc-gzip\"")]), Precondition::Proceed);
+    }
+}
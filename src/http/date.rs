@@ -0,0 +1,239 @@
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const FULL_DAY_NAMES: [&str; 7] = ["Thursday", "Friday", "Saturday", "Sunday", "Monday", "Tuesday", "Wednesday"];
+const MONTH_NAMES: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+thread_local! {
+    /// Formatted `Date` header and the second it was formatted for
+    static CACHED_DATE: RefCell<Option<(u64, String)>> = const { RefCell::new(None) };
+}
+
+/// Seconds since the Unix epoch, clamping times before it to zero
+pub fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Format seconds since the Unix epoch as an IMF-fixdate,
+/// e.g. `Sun, 06 Nov 1994 08:49:37 GMT` (RFC 9110 section 5.6.7)
+pub fn format_http_date(timestamp: u64) -> String {
+    let days = timestamp / 86400;
+    let seconds = timestamp % 86400;
+    let (year, month, day) = civil_from_days(days as i64);
+    
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAY_NAMES[(days % 7) as usize],
+        day,
+        MONTH_NAMES[month as usize - 1],
+        year,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Format a `SystemTime` as an IMF-fixdate
+pub fn format_system_time(time: SystemTime) -> String {
+    format_http_date(unix_seconds(time))
+}
+
+/// Refresh the cached `Date` header value if the second has changed.
+/// The event loop calls this once per wakeup, so formatting happens at
+/// most once per second however many responses are sent.
+pub fn update_cached_date(now: SystemTime) {
+    let seconds = unix_seconds(now);
+    CACHED_DATE.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        if cached.as_ref().map(|(second, _)| *second) != Some(seconds) {
+            *cached = Some((seconds, format_http_date(seconds)));
+        }
+    });
+}
+
+/// The current `Date` header value. Uses the event loop's cached value,
+/// formatting the current time when no loop is running on this thread.
+pub fn cached_date() -> String {
+    CACHED_DATE.with(|cached| match cached.borrow().as_ref() {
+        Some((_, formatted)) => formatted.clone(),
+        None => format_system_time(SystemTime::now()),
+    })
+}
+
+/// Parse an HTTP date in any of the three formats recipients must accept:
+/// IMF-fixdate, the obsolete RFC 850 format and ANSI C's asctime() format.
+/// Returns seconds since the Unix epoch, or `None` for an invalid date.
+pub fn parse_http_date(value: &str) -> Option<u64> {
+    let value = value.trim();
+    parse_imf_fixdate(value)
+        .or_else(|| parse_rfc850_date(value))
+        .or_else(|| parse_asctime_date(value))
+}
+
+/// `Sun, 06 Nov 1994 08:49:37 GMT`
+fn parse_imf_fixdate(value: &str) -> Option<u64> {
+    let parts: Vec<&str> = value.split(' ').collect();
+    if parts.len() != 6 || parts[5] != "GMT" || parts[1].len() != 2 || parts[3].len() != 4 {
+        return None;
+    }
+    
+    let weekday = parts[0].strip_suffix(',')?;
+    if !DAY_NAMES.contains(&weekday) {
+        return None;
+    }
+    
+    timestamp(parts[3].parse().ok()?, month_number(parts[2])?, parts[1].parse().ok()?, parts[4])
+}
+
+/// `Sunday, 06-Nov-94 08:49:37 GMT`
+fn parse_rfc850_date(value: &str) -> Option<u64> {
+    let parts: Vec<&str> = value.split(' ').collect();
+    if parts.len() != 4 || parts[3] != "GMT" {
+        return None;
+    }
+    
+    let weekday = parts[0].strip_suffix(',')?;
+    if !FULL_DAY_NAMES.contains(&weekday) {
+        return None;
+    }
+    
+    let date: Vec<&str> = parts[1].split('-').collect();
+    if date.len() != 3 || date[0].len() != 2 || date[2].len() != 2 {
+        return None;
+    }
+    
+    let year = expand_two_digit_year(date[2].parse().ok()?);
+    timestamp(year, month_number(date[1])?, date[0].parse().ok()?, parts[2])
+}
+
+/// `Sun Nov  6 08:49:37 1994`
+fn parse_asctime_date(value: &str) -> Option<u64> {
+    // The day of month is padded with a space, so split on runs of spaces
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != 5 || !DAY_NAMES.contains(&parts[0]) || parts[2].len() > 2 || parts[4].len() != 4 {
+        return None;
+    }
+    
+    timestamp(parts[4].parse().ok()?, month_number(parts[1])?, parts[2].parse().ok()?, parts[3])
+}
+
+/// RFC 850 years have two digits; one that appears to be more than 50 years
+/// in the future is taken as the most recent past year with those digits
+/// (RFC 9110 section 5.6.7)
+fn expand_two_digit_year(two_digits: i64) -> i64 {
+    let (current_year, _, _) = civil_from_days((unix_seconds(SystemTime::now()) / 86400) as i64);
+    let century = current_year - current_year % 100;
+    let year = century + two_digits;
+    if year > current_year + 50 {
+        year - 100
+    } else {
+        year
+    }
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    MONTH_NAMES.iter().position(|&month| month == name).map(|i| i as u32 + 1)
+}
+
+/// Combine a validated date and an `HH:MM:SS` time into seconds since the epoch
+fn timestamp(year: i64, month: u32, day: u32, time: &str) -> Option<u64> {
+    let time: Vec<u64> = time.split(':')
+        .map(|part| if part.len() == 2 { part.parse().ok() } else { None })
+        .collect::<Option<Vec<u64>>>()?;
+    if time.len() != 3 || time[0] > 23 || time[1] > 59 || time[2] > 60 {
+        return None;
+    }
+    
+    if year < 1970 || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    
+    let days = days_from_civil(year, month, day) as u64;
+    Some(days * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = (if month_index < 10 { month_index + 3 } else { month_index - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    
+    #[test]
+    fn test_format_http_date() {
+        assert_eq!(format_http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(format_http_date(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format_http_date(951782400), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(format_http_date(4133980799), "Fri, 31 Dec 2100 23:59:59 GMT");
+        
+        for timestamp in [1, 86399, 1700000000, 4102444800] {
+            assert_eq!(parse_http_date(&format_http_date(timestamp)), Some(timestamp));
+        }
+    }
+    
+    #[test]
+    fn test_parse_all_formats() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784111777));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(784111777));
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(784111777));
+        assert_eq!(parse_http_date("Tue Feb 29 00:00:00 2000"), Some(951782400));
+    }
+    
+    #[test]
+    fn test_parse_invalid_dates() {
+        assert_eq!(parse_http_date("Mon, 29 Feb 2001 00:00:00 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 6 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sonntag, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun Nov 06 08:49 1994"), None);
+        assert_eq!(parse_http_date("784111777"), None);
+        assert_eq!(parse_http_date(""), None);
+    }
+    
+    #[test]
+    fn test_cached_date_updates_once_per_second() {
+        let start = UNIX_EPOCH + Duration::from_secs(784111777);
+        update_cached_date(start);
+        assert_eq!(cached_date(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        
+        update_cached_date(start + Duration::from_millis(999));
+        assert_eq!(cached_date(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        
+        update_cached_date(start + Duration::from_secs(1));
+        assert_eq!(cached_date(), "Sun, 06 Nov 1994 08:49:38 GMT");
+    }
+}
@@ -1 +1,170 @@
-// Placeholder for HTTP headers handling - will be implemented in next iteration
+/// Header fields of a request or response.
+///
+/// Names are matched case-insensitively but kept as given, and fields keep
+/// the order they were added in, so responses serialize predictably and a
+/// field sent several times (`Set-Cookie`, `Cookie`, `Via`) keeps every value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        HeaderMap { entries: Vec::new() }
+    }
+    
+    /// First value of a field
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+    
+    /// Every value of a field, in the order they were added
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries.iter()
+            .filter(move |(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+    
+    /// All values of a field joined with commas, which is equivalent to the
+    /// separate lines for list-based fields (RFC 9110 section 5.3)
+    pub fn get_combined(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self.get_all(name).collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join(", "))
+        }
+    }
+    
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+    
+    /// Set a field to a single value. An existing field keeps its position
+    /// and loses any other values; a new one is added at the end.
+    /// Returns the previous first value.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        let name = name.into();
+        let value = value.into();
+        
+        match self.entries.iter().position(|(field, _)| field.eq_ignore_ascii_case(&name)) {
+            Some(index) => {
+                let previous = std::mem::replace(&mut self.entries[index].1, value);
+                
+                // Any other lines of the field come after the first one
+                let rest = self.entries.split_off(index + 1);
+                self.entries.extend(rest.into_iter().filter(|(field, _)| !field.eq_ignore_ascii_case(&name)));
+                Some(previous)
+            }
+            None => {
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+    
+    /// Add a value to a field, keeping the values it already has
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+    
+    /// Continue the value of the most recently added field with `more`,
+    /// as obsolete line folding does. Returns false when there is none.
+    pub fn fold_last(&mut self, more: &str) -> bool {
+        match self.entries.last_mut() {
+            Some((_, value)) => {
+                if !more.is_empty() {
+                    value.push(' ');
+                    value.push_str(more);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Remove every value of a field, returning the first
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let previous = self.get(name).map(str::to_string);
+        self.entries.retain(|(field, _)| !field.eq_ignore_ascii_case(name));
+        previous
+    }
+    
+    /// Name and value of every field line, in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+    
+    /// Number of field lines
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Box<dyn Iterator<Item = (&'a str, &'a str)> + 'a>;
+    
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_case_insensitive_lookup() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", "text/plain");
+        
+        assert_eq!(headers.get("content-type"), Some("text/plain"));
+        assert_eq!(headers.get("CONTENT-TYPE"), Some("text/plain"));
+        assert!(headers.contains_key("Content-type"));
+        assert_eq!(headers.get("Content-Length"), None);
+        
+        // The name is kept as it was given
+        assert_eq!(headers.iter().next(), Some(("Content-Type", "text/plain")));
+    }
+    
+    #[test]
+    fn test_insert_and_append() {
+        let mut headers = HeaderMap::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.insert("Server", "Localhost");
+        headers.append("set-cookie", "b=2");
+        
+        assert_eq!(headers.get("Set-Cookie"), Some("a=1"));
+        assert_eq!(headers.get_all("Set-Cookie").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+        assert_eq!(headers.len(), 3);
+        
+        // Insert replaces every value in place of the first
+        assert_eq!(headers.insert("SET-COOKIE", "c=3"), Some("a=1".to_string()));
+        let fields: Vec<(&str, &str)> = headers.iter().collect();
+        assert_eq!(fields, vec![("Set-Cookie", "c=3"), ("Server", "Localhost")]);
+        
+        assert_eq!(headers.remove("set-cookie"), Some("c=3".to_string()));
+        assert_eq!(headers.remove("set-cookie"), None);
+        assert_eq!(headers.len(), 1);
+    }
+    
+    #[test]
+    fn test_order_and_combined_values() {
+        let mut headers = HeaderMap::new();
+        headers.append("Via", "1.1 first");
+        headers.append("Accept", "text/html");
+        headers.append("Via", "1.1 second");
+        
+        let names: Vec<&str> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["Via", "Accept", "Via"]);
+        assert_eq!(headers.get_combined("via"), Some("1.1 first, 1.1 second".to_string()));
+        assert_eq!(headers.get_combined("Cookie"), None);
+    }
+}
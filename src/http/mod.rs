@@ -2,4 +2,8 @@ pub mod request;
 pub mod response;
 pub mod parse;
 pub mod headers;
+pub mod uri;
 pub mod chunked;
+pub mod compress;
+pub mod conditional;
+pub mod date;
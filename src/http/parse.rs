@@ -1,7 +1,83 @@
-use crate::http::request::{HttpRequest, Method};
+use crate::http::chunked::{ChunkedDecoder, ChunkedError, ChunkedUtils};
+use crate::http::request::{is_token, HttpRequest, Method};
+use crate::http::uri::{normalize_path, RequestTarget};
+use std::fmt;
 use std::io::{self, ErrorKind};
 use std::str;
 
+/// Body limit used when no route or virtual host limit applies
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// Resolves the body size limit for a request once its headers are parsed
+pub type BodyLimitFn = Box<dyn Fn(&HttpRequest) -> Option<usize>>;
+
+/// Size and count limits applied to the request line and header section
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseLimits {
+    /// Longest request line, in bytes (414 beyond it)
+    pub max_request_line: usize,
+    /// Longest request target, in bytes (414 beyond it)
+    pub max_uri_length: usize,
+    /// Longest single header field line, in bytes (431 beyond it)
+    pub max_header_size: usize,
+    /// Largest header section, all field lines together (431 beyond it)
+    pub max_header_section: usize,
+    /// Most header field lines (431 beyond it)
+    pub max_headers: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_request_line: 8192,
+            max_uri_length: 8192,
+            max_header_size: 8192,
+            max_header_section: 64 * 1024,
+            max_headers: 100,
+        }
+    }
+}
+
+/// Request rejected by the parser, with the status code to answer it with
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub status: u16,
+    pub reason: String,
+}
+
+impl ParseError {
+    /// Build an `io::Error` carrying a `ParseError`, the form parser
+    /// functions return it in
+    pub fn into_io(status: u16, reason: &str) -> io::Error {
+        io::Error::new(ErrorKind::InvalidData, ParseError { status, reason: reason.to_string() })
+    }
+    
+    /// Status code for a parser error: 413 for oversized bodies, the carried
+    /// status for a `ParseError`, and 400 for any other malformed input
+    pub fn status_of(err: &io::Error) -> u16 {
+        let inner = match err.get_ref() {
+            Some(inner) => inner,
+            None => return 400,
+        };
+        
+        if let Some(parse_error) = inner.downcast_ref::<ParseError>() {
+            return parse_error.status;
+        }
+        match inner.downcast_ref::<ChunkedError>() {
+            Some(ChunkedError::BodyTooLarge(..)) | Some(ChunkedError::ChunkTooLarge(..)) => 413,
+            _ => 400,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.reason, self.status)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 #[derive(Debug, Clone)]
 pub enum ParseState {
     RequestLine,
@@ -10,13 +86,45 @@ pub enum ParseState {
     Complete,
 }
 
-#[derive(Debug)]
 pub struct HttpParser {
     state: ParseState,
     buffer: Vec<u8>,
     request: HttpRequest,
     body_bytes_read: usize,
     expected_body_length: Option<usize>,
+    /// Decoder for a `Transfer-Encoding: chunked` body
+    chunked_decoder: Option<ChunkedDecoder>,
+    /// Route/virtual host body limit lookup
+    body_limit: Option<BodyLimitFn>,
+    /// Reject everything RFC 9112 lets a server reject instead of repairing it
+    strict: bool,
+    limits: ParseLimits,
+    /// Keep `%2F` in paths (still encoded) instead of rejecting the request
+    allow_encoded_slash: bool,
+    /// Authority of an absolute-form request target, which replaces Host
+    target_authority: Option<String>,
+    /// Bytes of the header section received so far
+    header_bytes: usize,
+    /// The body is announced with `Expect` and the connection has not
+    /// answered the expectation yet
+    expect_continue: bool,
+}
+
+impl fmt::Debug for HttpParser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpParser")
+            .field("state", &self.state)
+            .field("buffered", &self.buffer.len())
+            .field("request", &self.request)
+            .field("body_bytes_read", &self.body_bytes_read)
+            .field("expected_body_length", &self.expected_body_length)
+            .field("chunked_decoder", &self.chunked_decoder)
+            .field("strict", &self.strict)
+            .field("limits", &self.limits)
+            .field("allow_encoded_slash", &self.allow_encoded_slash)
+            .field("expect_continue", &self.expect_continue)
+            .finish()
+    }
 }
 
 impl HttpParser {
@@ -27,9 +135,51 @@ impl HttpParser {
             request: HttpRequest::new(),
             body_bytes_read: 0,
             expected_body_length: None,
+            chunked_decoder: None,
+            body_limit: None,
+            strict: true,
+            limits: ParseLimits::default(),
+            allow_encoded_slash: false,
+            target_authority: None,
+            header_bytes: 0,
+            expect_continue: false,
         }
     }
     
+    /// Set the request line and header section limits
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.limits = limits;
+    }
+    
+    /// Accept encoded slashes in request paths, keeping them encoded so
+    /// they never separate path segments. Rejected with 400 by default.
+    pub fn set_allow_encoded_slash(&mut self, allow: bool) {
+        self.allow_encoded_slash = allow;
+    }
+    
+    /// Choose between strict RFC 9112 parsing (the default) and the lenient
+    /// mode, which accepts bare LF line endings, whitespace before the colon,
+    /// obsolete line folding, control characters in field values and
+    /// HTTP/1.1 requests without `Host`. Message framing is checked either way.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+    
+    /// Set the lookup used to find the body size limit for a request.
+    /// Returning `None` falls back to `DEFAULT_MAX_BODY_SIZE`.
+    pub fn set_body_limit<F>(&mut self, body_limit: F)
+    where
+        F: Fn(&HttpRequest) -> Option<usize> + 'static,
+    {
+        self.body_limit = Some(Box::new(body_limit));
+    }
+    
+    /// The request being parsed; only the request line and headers are
+    /// meaningful before parsing completes
+    pub fn request(&self) -> &HttpRequest {
+        &self.request
+    }
+    
     /// Parse incoming data incrementally. Returns Ok(Some(request)) when complete,
     /// Ok(None) when more data is needed, or Err for parse errors.
     pub fn parse(&mut self, data: &[u8]) -> io::Result<Option<HttpRequest>> {
@@ -47,10 +197,18 @@ impl HttpParser {
                 }
                 ParseState::Headers => {
                     if self.parse_headers()? {
-                        // Headers complete, determine if we need to read body
-                        self.expected_body_length = self.request.content_length();
+                        self.check_host()?;
+                        
+                        // The authority of an absolute-form target overrides Host
+                        if let Some(authority) = self.target_authority.take() {
+                            self.request.headers.insert("Host", authority);
+                        }
                         
-                        if self.expected_body_length.is_some() || self.request.is_chunked() {
+                        // Headers complete, determine if we need to read body
+                        if self.start_body()? {
+                            // HTTP/1.0 clients cannot expect anything (RFC 9110 section 10.1.1)
+                            self.expect_continue = self.request.version != "HTTP/1.0"
+                                && self.request.get_header("expect").is_some();
                             self.state = ParseState::Body;
                         } else {
                             self.state = ParseState::Complete;
@@ -74,90 +232,259 @@ impl HttpParser {
     }
     
     fn parse_request_line(&mut self) -> io::Result<Option<HttpRequest>> {
-        // Look for CRLF to end the request line
-        if let Some(pos) = self.find_crlf() {
-            let line_bytes = self.buffer.drain(..pos + 2).collect::<Vec<u8>>();
-            let line = str::from_utf8(&line_bytes[..pos])
-                .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8 in request line"))?;
+        // Look for the line ending of the request line; empty lines
+        // before it are ignored (RFC 9112 section 2.2)
+        let line_bytes = loop {
+            self.skip_empty_lines();
+            match self.take_line("request line")? {
+                Some(line_bytes) if line_bytes.is_empty() => continue,
+                Some(line_bytes) => break line_bytes,
+                None => {
+                    // Check for oversized request line
+                    if self.buffer.len() > self.limits.max_request_line {
+                        return Err(ParseError::into_io(414, "Request line too long"));
+                    }
+                    return Ok(None);
+                }
+            }
+        };
+        
+        if line_bytes.len() > self.limits.max_request_line {
+            return Err(ParseError::into_io(414, "Request line too long"));
+        }
+        
+        let line = str::from_utf8(&line_bytes)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8 in request line"))?;
+        
+        let parts: Vec<&str> = if self.strict {
+            // method SP request-target SP HTTP-version, with single spaces
+            line.split(' ').collect()
+        } else {
+            line.split_whitespace().collect()
+        };
+        if parts.len() != 3 || parts.iter().any(|part| part.is_empty()) {
+            return Err(ParseError::into_io(400, "Invalid request line format"));
+        }
+        
+        let method = Method::from_str(parts[0])
+            .ok_or_else(|| ParseError::into_io(400, "Invalid HTTP method"))?;
+        
+        if parts[1].len() > self.limits.max_uri_length {
+            return Err(ParseError::into_io(414, "Request target too long"));
+        }
+        
+        if self.strict && parts[1].bytes().any(|b| b <= b' ' || b == 0x7f) {
+            return Err(ParseError::into_io(400, "Invalid character in request target"));
+        }
+        
+        let target = RequestTarget::parse(parts[1], &method)
+            .map_err(|e| ParseError::into_io(400, &e.to_string()))?;
+        
+        // Routing and file lookup use the decoded, normalized path
+        let raw_path = target.raw_path().to_string();
+        let path = match target {
+            RequestTarget::Origin { .. } | RequestTarget::Absolute { .. } => {
+                normalize_path(&raw_path, self.allow_encoded_slash)
+                    .map_err(|e| ParseError::into_io(400, &e.to_string()))?
+            }
+            RequestTarget::Authority(_) | RequestTarget::Asterisk => raw_path.clone(),
+        };
+        let query_string = target.query().map(str::to_string);
+        self.target_authority = target.authority().map(str::to_string);
+        
+        let version = parts[2].to_string();
+        
+        // Validate HTTP version: "HTTP/" DIGIT "." DIGIT
+        let valid_version = if self.strict {
+            let digits = version.as_bytes();
+            digits.len() == 8 && version.starts_with("HTTP/")
+                && digits[5].is_ascii_digit() && digits[6] == b'.' && digits[7].is_ascii_digit()
+        } else {
+            version.starts_with("HTTP/")
+        };
+        if !valid_version {
+            return Err(ParseError::into_io(400, "Invalid HTTP version"));
+        }
+        
+        let mut request = HttpRequest::new();
+        request.method = method;
+        request.path = path;
+        request.raw_path = raw_path;
+        request.version = version;
+        request.query_string = query_string;
+        
+        Ok(Some(request))
+    }
+    
+    fn parse_headers(&mut self) -> io::Result<bool> {
+        loop {
+            let line_bytes = match self.take_line("header section")? {
+                Some(line_bytes) => line_bytes,
+                None => {
+                    // A field line still being received counts towards both limits
+                    if self.buffer.len() > self.limits.max_header_size {
+                        return Err(ParseError::into_io(431, "Header field too large"));
+                    }
+                    if self.header_bytes + self.buffer.len() > self.limits.max_header_section {
+                        return Err(ParseError::into_io(431, "Header section too large"));
+                    }
+                    return Ok(false); // Need more data
+                }
+            };
             
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() != 3 {
-                return Err(io::Error::new(ErrorKind::InvalidData, "Invalid request line format"));
+            if line_bytes.is_empty() {
+                // Empty line indicates end of headers
+                return Ok(true);
+            }
+            
+            self.header_bytes += line_bytes.len() + 2;
+            if line_bytes.len() > self.limits.max_header_size {
+                return Err(ParseError::into_io(431, "Header field too large"));
+            }
+            if self.header_bytes > self.limits.max_header_section {
+                return Err(ParseError::into_io(431, "Header section too large"));
             }
             
-            let method = Method::from_str(parts[0])
-                .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Invalid HTTP method"))?;
+            let line = str::from_utf8(&line_bytes)
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8 in header"))?;
             
-            let (path, query_string) = if let Some(query_pos) = parts[1].find('?') {
-                let path = parts[1][..query_pos].to_string();
-                let query = Some(parts[1][query_pos + 1..].to_string());
-                (path, query)
+            // A line starting with whitespace continues the previous field (obs-fold)
+            if line.starts_with([' ', '\t']) {
+                if self.strict {
+                    return Err(ParseError::into_io(400, "Obsolete line folding in header section"));
+                }
+                if !self.request.headers.fold_last(line.trim()) {
+                    return Err(ParseError::into_io(400, "Invalid header format"));
+                }
+                continue;
+            }
+            
+            let colon_pos = line.find(':')
+                .ok_or_else(|| ParseError::into_io(400, "Invalid header format"))?;
+            let raw_name = &line[..colon_pos];
+            let value = line[colon_pos + 1..].trim_matches([' ', '\t']);
+            
+            let name = if self.strict {
+                if raw_name.ends_with([' ', '\t']) {
+                    return Err(ParseError::into_io(400, "Whitespace between header field name and colon"));
+                }
+                if !is_token(raw_name) {
+                    return Err(ParseError::into_io(400, "Invalid character in header field name"));
+                }
+                if value.bytes().any(|b| (b < b' ' && b != b'\t') || b == 0x7f) {
+                    return Err(ParseError::into_io(400, "Control character in header field value"));
+                }
+                raw_name
             } else {
-                (parts[1].to_string(), None)
+                raw_name.trim()
             };
             
-            let version = parts[2].to_string();
+            if name.is_empty() {
+                return Err(ParseError::into_io(400, "Empty header name"));
+            }
+            if self.request.headers.len() >= self.limits.max_headers {
+                return Err(ParseError::into_io(431, "Too many header fields"));
+            }
             
-            // Validate HTTP version
-            if !version.starts_with("HTTP/") {
-                return Err(io::Error::new(ErrorKind::InvalidData, "Invalid HTTP version"));
+            // Repeated fields keep every line, in order
+            self.request.headers.append(name, value);
+        }
+    }
+    
+    /// HTTP/1.1 requests must carry exactly one Host field (RFC 9112 section 3.2)
+    fn check_host(&self) -> io::Result<()> {
+        if !self.strict || self.request.version != "HTTP/1.1" {
+            return Ok(());
+        }
+        
+        match self.request.headers.get_all("host").count() {
+            0 => Err(ParseError::into_io(400, "Missing Host header")),
+            1 => Ok(()),
+            _ => Err(ParseError::into_io(400, "Multiple Host headers")),
+        }
+    }
+    
+    /// Work out how the body is framed (RFC 9112 section 6.3).
+    /// Returns whether there is a body to read.
+    fn start_body(&mut self) -> io::Result<bool> {
+        if let Some(transfer_encoding) = self.request.headers.get_combined("transfer-encoding") {
+            // Both headers present is a request smuggling vector
+            if self.request.get_header("content-length").is_some() {
+                return Err(ParseError::into_io(400, "Both Content-Length and Transfer-Encoding present"));
             }
             
-            let mut request = HttpRequest::new();
-            request.method = method;
-            request.path = path;
-            request.version = version;
-            request.query_string = query_string;
+            // HTTP/1.0 has no transfer codings, so the framing cannot be trusted
+            if self.request.version == "HTTP/1.0" {
+                return Err(ParseError::into_io(400, "Transfer-Encoding in an HTTP/1.0 request"));
+            }
             
-            Ok(Some(request))
-        } else {
-            // Check for oversized request line
-            if self.buffer.len() > 8192 {
-                return Err(io::Error::new(ErrorKind::InvalidData, "Request line too long"));
+            let codings = ChunkedUtils::parse_transfer_encoding(&transfer_encoding);
+            if codings.last().map(|c| c.as_str()) != Some("chunked") {
+                return Err(ParseError::into_io(400, "chunked must be the final transfer coding"));
+            }
+            if codings.len() > 1 {
+                return Err(ParseError::into_io(501, "Unsupported transfer coding"));
             }
-            Ok(None)
+            
+            let limit = self.max_body_size();
+            self.chunked_decoder = Some(ChunkedDecoder::new(limit, limit));
+            return Ok(true);
         }
-    }
-    
-    fn parse_headers(&mut self) -> io::Result<bool> {
-        loop {
-            if let Some(pos) = self.find_crlf() {
-                if pos == 0 {
-                    // Empty line indicates end of headers
-                    self.buffer.drain(..2); // Remove the CRLF
-                    return Ok(true);
-                }
-                
-                let line_bytes = self.buffer.drain(..pos + 2).collect::<Vec<u8>>();
-                let line = str::from_utf8(&line_bytes[..pos])
-                    .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8 in header"))?;
-                
-                if let Some(colon_pos) = line.find(':') {
-                    let name = line[..colon_pos].trim().to_lowercase();
-                    let value = line[colon_pos + 1..].trim().to_string();
-                    
-                    if name.is_empty() {
-                        return Err(io::Error::new(ErrorKind::InvalidData, "Empty header name"));
-                    }
-                    
-                    self.request.headers.insert(name, value);
-                } else {
-                    return Err(io::Error::new(ErrorKind::InvalidData, "Invalid header format"));
-                }
-            } else {
-                // Check for oversized headers
-                if self.buffer.len() > 65536 {
-                    return Err(io::Error::new(ErrorKind::InvalidData, "Headers too large"));
-                }
-                return Ok(false); // Need more data
+        
+        if let Some(content_length) = self.request.headers.get_combined("content-length") {
+            // Repeated Content-Length values are only acceptable when they all agree
+            let mut values = content_length.split(',').map(|value| value.trim());
+            let first = values.next().unwrap_or("").to_string();
+            if values.any(|value| value != first) {
+                return Err(ParseError::into_io(400, "Conflicting Content-Length values"));
+            }
+            
+            // Only digits: no sign, no spaces, nothing for a parser to skip over
+            if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParseError::into_io(400, "Invalid Content-Length"));
             }
+            let length = first.parse::<usize>()
+                .map_err(|_| ParseError::into_io(400, "Invalid Content-Length"))?;
+            
+            // Refuse an oversized body before any of it is buffered
+            if length > self.max_body_size() {
+                return Err(ParseError::into_io(413, "Content-Length exceeds the body size limit"));
+            }
+            self.request.headers.insert("content-length", first);
+            self.expected_body_length = Some(length);
+            return Ok(true);
         }
+        
+        Ok(false)
+    }
+    
+    /// Body size limit for the current request, from its route or virtual
+    /// host once the headers are parsed. Bodies declared or decoded beyond
+    /// it are rejected with 413 as soon as that is known.
+    pub fn max_body_size(&self) -> usize {
+        self.body_limit.as_ref()
+            .and_then(|body_limit| body_limit(&self.request))
+            .unwrap_or(DEFAULT_MAX_BODY_SIZE)
     }
     
     fn parse_body(&mut self) -> io::Result<bool> {
-        if self.request.is_chunked() {
-            // TODO: Implement chunked transfer encoding in next iteration
-            return Err(io::Error::new(ErrorKind::InvalidData, "Chunked encoding not yet supported"));
+        if let Some(decoder) = self.chunked_decoder.as_mut() {
+            let consumed = decoder.process(&self.buffer)?;
+            self.buffer.drain(..consumed);
+            
+            if !decoder.is_complete() {
+                return Ok(false); // Need more data
+            }
+            
+            // Hand the decoded body on as if it had been sent with Content-Length
+            self.request.body = decoder.body().to_vec();
+            for (name, value) in decoder.trailer_headers() {
+                self.request.trailers.insert(name.to_lowercase(), value.clone());
+            }
+            self.request.headers.remove("transfer-encoding");
+            self.request.headers.insert("content-length", self.request.body.len().to_string());
+            self.body_bytes_read = self.request.body.len();
+            return Ok(true);
         }
         
         if let Some(expected_length) = self.expected_body_length {
@@ -176,11 +503,7 @@ impl HttpParser {
                 self.request.body.extend_from_slice(&body_data);
                 self.body_bytes_read += available;
                 
-                // Check for oversized body
-                if self.body_bytes_read > 10 * 1024 * 1024 { // 10MB limit
-                    return Err(io::Error::new(ErrorKind::InvalidData, "Request body too large"));
-                }
-                
+                // The length was checked against the body size limit in start_body
                 return Ok(false); // Need more data
             }
         }
@@ -189,20 +512,81 @@ impl HttpParser {
         Ok(true)
     }
     
-    fn find_crlf(&self) -> Option<usize> {
-        self.buffer.windows(2).position(|window| window == b"\r\n")
+    /// Drop the empty lines at the start of the buffer all at once, so a
+    /// flood of them does not shift the rest of the buffer once per line
+    fn skip_empty_lines(&mut self) {
+        let mut skipped = 0;
+        loop {
+            match self.buffer[skipped..] {
+                [b'\r', b'\n', ..] => skipped += 2,
+                [b'\n', ..] if !self.strict => skipped += 1,
+                _ => break,
+            }
+        }
+        self.buffer.drain(..skipped);
+    }
+    
+    /// Remove the next line from the buffer, without its line ending.
+    /// A bare LF only ends a line in lenient mode, and a CR is only
+    /// allowed as part of a CRLF in strict mode (RFC 9112 section 2.2).
+    fn take_line(&mut self, section: &str) -> io::Result<Option<Vec<u8>>> {
+        let pos = match self.buffer.iter().position(|&b| b == b'\n') {
+            Some(pos) => pos,
+            None => return Ok(None),
+        };
+        
+        let mut line = self.buffer.drain(..pos + 1).collect::<Vec<u8>>();
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        } else if self.strict {
+            return Err(ParseError::into_io(400, &format!("Bare LF in {}", section)));
+        }
+        
+        if self.strict && line.contains(&b'\r') {
+            return Err(ParseError::into_io(400, &format!("Bare CR in {}", section)));
+        }
+        Ok(Some(line))
     }
     
     pub fn is_complete(&self) -> bool {
         matches!(self.state, ParseState::Complete)
     }
     
+    /// Get ready for the next request on the connection. Bytes received
+    /// after the end of the last request are kept: they are the start of
+    /// the next, pipelined request.
     pub fn reset(&mut self) {
         self.state = ParseState::RequestLine;
-        self.buffer.clear();
         self.request = HttpRequest::new();
         self.body_bytes_read = 0;
         self.expected_body_length = None;
+        self.chunked_decoder = None;
+        self.header_bytes = 0;
+        self.target_authority = None;
+        self.expect_continue = false;
+    }
+    
+    /// Whether the headers of a request with an `Expect` field are complete
+    /// and its body is still to come. The connection either invites the body
+    /// with `100 Continue` or answers with a final status instead.
+    pub fn expects_continue(&self) -> bool {
+        self.expect_continue && self.is_reading_body()
+    }
+    
+    /// Record that the request's expectation has been answered
+    pub fn clear_expectation(&mut self) {
+        self.expect_continue = false;
+    }
+    
+    /// Whether bytes of a further request are waiting to be parsed
+    pub fn has_buffered_data(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+    
+    /// Whether no part of a request has been received yet
+    pub fn is_idle(&self) -> bool {
+        matches!(self.state, ParseState::RequestLine) && self.buffer.is_empty()
     }
     
     /// Check if the parser is currently reading request body
@@ -210,3 +594,310 @@ impl HttpParser {
         matches!(self.state, ParseState::Body)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_methods() {
+        for (line, method) in [
+            ("PATCH", Method::PATCH),
+            ("TRACE", Method::TRACE),
+            ("CONNECT", Method::CONNECT),
+            ("PROPFIND", Method::Extension("PROPFIND".to_string())),
+            ("get", Method::Extension("get".to_string())),
+        ] {
+            let mut parser = HttpParser::new();
+            // CONNECT only takes an authority-form target
+            let target = if method == Method::CONNECT { "a:443" } else { "/x" };
+            let raw = format!("{} {} HTTP/1.1\r\nHost: a\r\n\r\n", line, target);
+            let request = parser.parse(raw.as_bytes()).unwrap().unwrap();
+            assert_eq!(request.method, method);
+            assert_eq!(request.method.as_str(), line);
+        }
+        
+        // Method tokens may not contain separators
+        let err = HttpParser::new().parse(b"GE(T / HTTP/1.1\r\n\r\n").unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 400);
+    }
+    
+    #[test]
+    fn test_repeated_headers_are_kept() {
+        let mut parser = HttpParser::new();
+        let raw = b"GET / HTTP/1.1\r\nHost: a\r\nCookie: a=1\r\nAccept: text/html\r\ncookie: b=2\r\n\r\n";
+        let request = parser.parse(raw).unwrap().unwrap();
+        
+        assert_eq!(request.headers.get_all("Cookie").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+        let names: Vec<&str> = request.headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["Host", "Cookie", "Accept", "cookie"]);
+    }
+    
+    #[test]
+    fn test_repeated_content_length() {
+        let mut parser = HttpParser::new();
+        let request = parser.parse(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 2\r\nContent-Length: 2\r\n\r\nhi").unwrap().unwrap();
+        assert_eq!(request.body, b"hi");
+        assert_eq!(request.headers.get_all("content-length").count(), 1);
+        
+        let mut parser = HttpParser::new();
+        let err = parser.parse(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\nhi!").unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 400);
+    }
+    
+    #[test]
+    fn test_parse_chunked_body_incrementally() {
+        let mut parser = HttpParser::new();
+        let head = b"POST /upload HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHel";
+        
+        assert!(parser.parse(head).unwrap().is_none());
+        assert!(parser.is_reading_body());
+        assert!(parser.parse(b"lo\r\n6\r\n World\r\n0\r\nX-Checksum: abc\r\n").unwrap().is_none());
+        
+        let request = parser.parse(b"\r\n").unwrap().unwrap();
+        assert_eq!(request.body, b"Hello World");
+        assert_eq!(request.get_trailer("x-checksum"), Some("abc"));
+        assert_eq!(request.content_length(), Some(11));
+        assert!(!request.is_chunked());
+    }
+    
+    #[test]
+    fn test_reject_content_length_with_chunked() {
+        let mut parser = HttpParser::new();
+        let data = b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        
+        let err = parser.parse(data).unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 400);
+    }
+    
+    #[test]
+    fn test_reject_unsupported_transfer_coding() {
+        let mut parser = HttpParser::new();
+        let err = parser.parse(b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, gzip\r\n\r\n").unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 400);
+        
+        let mut parser = HttpParser::new();
+        let err = parser.parse(b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip, chunked\r\n\r\n").unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 501);
+    }
+    
+    #[test]
+    fn test_chunked_body_limit() {
+        let mut parser = HttpParser::new();
+        parser.set_body_limit(|request| if request.path() == "/small" { Some(8) } else { None });
+        
+        let err = parser.parse(b"POST /small HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHello\r\n5\r\n").unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 413);
+        
+        let mut parser = HttpParser::new();
+        parser.set_body_limit(|request| if request.path() == "/small" { Some(8) } else { None });
+        let request = parser.parse(b"POST /big HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHello\r\n5\r\nWorld\r\n0\r\n\r\n").unwrap();
+        assert_eq!(request.unwrap().body, b"HelloWorld");
+    }
+    
+    #[test]
+    fn test_content_length_limit_is_checked_before_the_body() {
+        let mut parser = HttpParser::new();
+        parser.set_body_limit(|request| if request.path() == "/small" { Some(8) } else { None });
+        
+        // Rejected from the headers alone, without waiting for the body
+        let err = parser.parse(b"POST /small HTTP/1.1\r\nHost: a\r\nContent-Length: 9\r\n\r\n").unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 413);
+        
+        let mut parser = HttpParser::new();
+        parser.set_body_limit(|request| if request.path() == "/small" { Some(8) } else { None });
+        let request = parser.parse(b"POST /small HTTP/1.1\r\nHost: a\r\nContent-Length: 8\r\n\r\n12345678").unwrap();
+        assert_eq!(request.unwrap().body, b"12345678");
+        
+        // Without a route or virtual host limit the default applies
+        let mut parser = HttpParser::new();
+        let head = format!("POST / HTTP/1.1\r\nHost: a\r\nContent-Length: {}\r\n\r\n", DEFAULT_MAX_BODY_SIZE + 1);
+        let err = parser.parse(head.as_bytes()).unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 413);
+    }
+    
+    #[test]
+    fn test_head_limits() {
+        let limits = ParseLimits {
+            max_request_line: 64,
+            max_uri_length: 32,
+            max_header_size: 32,
+            max_header_section: 80,
+            max_headers: 3,
+        };
+        let status = |raw: &[u8]| {
+            let mut parser = HttpParser::new();
+            parser.set_limits(limits.clone());
+            parser.parse(raw).map(|request| request.is_some()).map_err(|err| ParseError::status_of(&err))
+        };
+        
+        assert_eq!(status(b"GET /ok HTTP/1.1\r\nHost: a\r\n\r\n"), Ok(true));
+        
+        // Request target and request line, complete or still arriving
+        let long_target = format!("GET /{} HTTP/1.1\r\nHost: a\r\n\r\n", "a".repeat(40));
+        assert_eq!(status(long_target.as_bytes()), Err(414));
+        let long_line = format!("GET /{}", "a".repeat(80));
+        assert_eq!(status(long_line.as_bytes()), Err(414));
+        
+        // One field line, the whole section and the number of fields
+        let long_field = format!("GET / HTTP/1.1\r\nHost: a\r\nX: {}\r\n\r\n", "b".repeat(40));
+        assert_eq!(status(long_field.as_bytes()), Err(431));
+        assert_eq!(status(format!("GET / HTTP/1.1\r\nX: {}", "b".repeat(40)).as_bytes()), Err(431));
+        let value = "c".repeat(27);
+        let many_bytes = format!("GET / HTTP/1.1\r\nA: {0}\r\nB: {0}\r\nC: {0}\r\n\r\n", value);
+        assert_eq!(status(many_bytes.as_bytes()), Err(431));
+        assert_eq!(status(b"GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n"), Err(431));
+        assert_eq!(status(b"GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\nB: 2\r\n\r\n"), Ok(true));
+    }
+    
+    #[test]
+    fn test_request_target_is_normalized() {
+        let mut parser = HttpParser::new();
+        let request = parser.parse(b"GET /docs/./a//b/../hello%20world.txt?x=%2F HTTP/1.1\r\nHost: a\r\n\r\n").unwrap().unwrap();
+        assert_eq!(request.path(), "/docs/a/hello world.txt");
+        assert_eq!(request.raw_path(), "/docs/./a//b/../hello%20world.txt");
+        assert_eq!(request.query_string.as_deref(), Some("x=%2F"));
+        
+        // The authority of an absolute-form target replaces Host
+        let mut parser = HttpParser::new();
+        let request = parser.parse(b"GET http://example.com/%7Euser HTTP/1.1\r\nHost: other\r\n\r\n").unwrap().unwrap();
+        assert_eq!(request.path(), "/~user");
+        assert_eq!(request.host(), Some("example.com"));
+        
+        let request = HttpParser::new().parse(b"OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n").unwrap().unwrap();
+        assert_eq!((request.path(), request.raw_path()), ("*", "*"));
+        
+        for raw in [
+            &b"GET /a%2F..%2F..%2Fetc HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+            &b"GET /a%00.txt HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+            &b"GET /a%zz HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+            &b"GET * HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+        ] {
+            let err = HttpParser::new().parse(raw).unwrap_err();
+            assert_eq!(ParseError::status_of(&err), 400);
+        }
+        
+        let mut parser = HttpParser::new();
+        parser.set_allow_encoded_slash(true);
+        let request = parser.parse(b"GET /a%2Fb/../c HTTP/1.1\r\nHost: a\r\n\r\n").unwrap().unwrap();
+        assert_eq!(request.path(), "/c");
+    }
+    
+    #[test]
+    fn test_malformed_chunk_is_bad_request() {
+        let mut parser = HttpParser::new();
+        let err = parser.parse(b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n").unwrap_err();
+        assert_eq!(ParseError::status_of(&err), 400);
+    }
+    
+    #[test]
+    fn test_pipelined_requests_keep_leftover_bytes() {
+        let mut parser = HttpParser::new();
+        let raw = b"POST /a HTTP/1.1\r\nHost: a\r\nContent-Length: 2\r\n\r\nhiGET /b HTTP/1.1\r\nHost: a\r\n\r\nGET /c HT";
+        
+        let first = parser.parse(raw).unwrap().unwrap();
+        assert_eq!((first.path.as_str(), first.body.as_slice()), ("/a", &b"hi"[..]));
+        
+        parser.reset();
+        assert!(parser.has_buffered_data());
+        assert_eq!(parser.parse(&[]).unwrap().unwrap().path, "/b");
+        
+        parser.reset();
+        assert!(parser.parse(&[]).unwrap().is_none());
+        assert_eq!(parser.parse(b"TP/1.1\r\nHost: a\r\n\r\n").unwrap().unwrap().path, "/c");
+        parser.reset();
+        assert!(!parser.has_buffered_data());
+    }
+    
+    /// Parse `raw` in one go and return the rejection status and reason
+    fn rejection(raw: &[u8], strict: bool) -> Option<(u16, String)> {
+        let mut parser = HttpParser::new();
+        parser.set_strict(strict);
+        let err = parser.parse(raw).err()?;
+        let reason = err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<ParseError>())
+            .map(|parse_error| parse_error.reason.clone())
+            .unwrap_or_default();
+        Some((ParseError::status_of(&err), reason))
+    }
+    
+    #[test]
+    fn test_smuggling_vectors_are_rejected() {
+        let corpus: &[(&[u8], &str)] = &[
+            // Ambiguous framing
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n", "Both Content-Length and Transfer-Encoding present"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\n0\r\n\r\n", "Both Content-Length and Transfer-Encoding present"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4\r\nContent-Length: 5\r\n\r\nabcde", "Conflicting Content-Length values"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4, 5\r\n\r\nabcde", "Conflicting Content-Length values"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: +4\r\n\r\nabcd", "Invalid Content-Length"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 0x4\r\n\r\nabcd", "Invalid Content-Length"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4 4\r\n\r\nabcd", "Invalid Content-Length"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length:\r\n\r\n", "Invalid Content-Length"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 99999999999999999999999\r\n\r\n", "Invalid Content-Length"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: xchunked\r\n\r\n0\r\n\r\n", "chunked must be the final transfer coding"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, identity\r\n\r\n0\r\n\r\n", "chunked must be the final transfer coding"),
+            (b"POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n", "Transfer-Encoding in an HTTP/1.0 request"),
+            // Line endings
+            (b"GET / HTTP/1.1\nHost: a\r\n\r\n", "Bare LF in request line"),
+            (b"GET / HTTP/1.1\r\nHost: a\nX: y\r\n\r\n", "Bare LF in header section"),
+            (b"GET / HTTP/1.1\r\nHost: a\r\nX: y\rZ: w\r\n\r\n", "Bare CR in header section"),
+            // Field syntax
+            (b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding : chunked\r\n\r\n0\r\n\r\n", "Whitespace between header field name and colon"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length\t: 4\r\n\r\nabcd", "Whitespace between header field name and colon"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nX: y\r\n Transfer-Encoding: chunked\r\n\r\n", "Obsolete line folding in header section"),
+            (b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding:\r\n\tchunked\r\n\r\n", "Obsolete line folding in header section"),
+            (b"GET / HTTP/1.1\r\nHost: a\r\nX[1]: y\r\n\r\n", "Invalid character in header field name"),
+            (b"GET / HTTP/1.1\r\nHost: a\r\n: y\r\n\r\n", "Invalid character in header field name"),
+            (b"GET / HTTP/1.1\r\nHost: a\r\nX: a\x00b\r\n\r\n", "Control character in header field value"),
+            (b"GET / HTTP/1.1\r\nHost: a\r\nX: a\x7fb\r\n\r\n", "Control character in header field value"),
+            // Request line and Host
+            (b"GET  / HTTP/1.1\r\nHost: a\r\n\r\n", "Invalid request line format"),
+            (b"GET /\tHTTP/1.1\r\nHost: a\r\n\r\n", "Invalid request line format"),
+            (b"GET / HTTP/1.1 \r\nHost: a\r\n\r\n", "Invalid request line format"),
+            (b"GET /a\x01b HTTP/1.1\r\nHost: a\r\n\r\n", "Invalid character in request target"),
+            (b"GET / HTTP/1.10\r\nHost: a\r\n\r\n", "Invalid HTTP version"),
+            (b"GET / http/1.1\r\nHost: a\r\n\r\n", "Invalid HTTP version"),
+            (b"GET / HTTP/1.1\r\n\r\n", "Missing Host header"),
+            (b"GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n", "Multiple Host headers"),
+        ];
+        
+        for (raw, reason) in corpus {
+            let request = String::from_utf8_lossy(raw);
+            assert_eq!(rejection(raw, true), Some((400, reason.to_string())), "{:?}", request);
+        }
+    }
+    
+    #[test]
+    fn test_lenient_mode() {
+        let mut parser = HttpParser::new();
+        parser.set_strict(false);
+        let raw = b"\r\nGET /  HTTP/1.1\nX-Long: a\n\tb\nName : value\n\n";
+        let request = parser.parse(raw).unwrap().unwrap();
+        assert_eq!(request.path, "/");
+        assert_eq!(request.get_header("x-long"), Some("a b"));
+        assert_eq!(request.get_header("name"), Some("value"));
+        assert_eq!(request.host(), None);
+        
+        // Framing is never left ambiguous
+        for raw in [
+            &b"POST / HTTP/1.1\nContent-Length: 1\nContent-Length: 2\n\nab"[..],
+            &b"POST / HTTP/1.1\nContent-Length: +2\n\nab"[..],
+            &b"POST / HTTP/1.1\nContent-Length: 2\nTransfer-Encoding: chunked\n\n0\n\n"[..],
+        ] {
+            assert_eq!(rejection(raw, false).map(|(status, _)| status), Some(400));
+        }
+        
+        // Strict parsing accepts well-formed requests, leading empty lines included
+        let mut parser = HttpParser::new();
+        let request = parser.parse(b"\r\nGET /a?b=c HTTP/1.1\r\nHost: a\r\nX: \tspaced\t \r\n\r\n").unwrap().unwrap();
+        assert_eq!(request.query_string.as_deref(), Some("b=c"));
+        assert_eq!(request.get_header("x"), Some("spaced"));
+        assert!(HttpParser::new().parse(b"GET / HTTP/1.0\r\n\r\n").unwrap().is_some());
+        
+        // A long run of them is dropped in one go
+        let mut parser = HttpParser::new();
+        assert!(parser.parse(&b"\r\n".repeat(1 << 20)).unwrap().is_none());
+        assert!(!parser.has_buffered_data());
+        assert!(parser.parse(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n").unwrap().is_some());
+    }
+}
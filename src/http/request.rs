@@ -1,6 +1,10 @@
 use std::collections::HashMap;
+use crate::http::headers::HeaderMap;
 use std::str;
 
+/// Request method. Variants are spelled as the method tokens are, so the
+/// later additions follow the existing `GET`/`POST`/... style.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Method {
     GET,
@@ -9,9 +13,29 @@ pub enum Method {
     HEAD,
     PUT,
     OPTIONS,
+    PATCH,
+    TRACE,
+    CONNECT,
+    /// Any other syntactically valid method token
+    Extension(String),
 }
 
+/// Order methods are listed in for `Allow` headers
+const ALLOW_ORDER: [Method; 9] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+    Method::OPTIONS,
+    Method::TRACE,
+    Method::CONNECT,
+];
+
 impl Method {
+    /// Parse a method token. Methods are case-sensitive (RFC 9110 section 9.1),
+    /// so `get` is an extension method. Returns `None` for an invalid token.
     pub fn from_str(s: &str) -> Option<Method> {
         match s {
             "GET" => Some(Method::GET),
@@ -20,11 +44,15 @@ impl Method {
             "HEAD" => Some(Method::HEAD),
             "PUT" => Some(Method::PUT),
             "OPTIONS" => Some(Method::OPTIONS),
+            "PATCH" => Some(Method::PATCH),
+            "TRACE" => Some(Method::TRACE),
+            "CONNECT" => Some(Method::CONNECT),
+            _ if is_token(s) => Some(Method::Extension(s.to_string())),
             _ => None,
         }
     }
     
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Method::GET => "GET",
             Method::POST => "POST",
@@ -32,18 +60,60 @@ impl Method {
             Method::HEAD => "HEAD",
             Method::PUT => "PUT",
             Method::OPTIONS => "OPTIONS",
+            Method::PATCH => "PATCH",
+            Method::TRACE => "TRACE",
+            Method::CONNECT => "CONNECT",
+            Method::Extension(name) => name,
         }
     }
+    
+    /// Whether this is a method defined by HTTP rather than an extension
+    pub fn is_standard(&self) -> bool {
+        !matches!(self, Method::Extension(_))
+    }
+    
+    /// `Allow` header value for a set of methods in a stable order.
+    /// HEAD is listed wherever GET is, since it is always served with it.
+    pub fn allow_header<'a>(methods: impl IntoIterator<Item = &'a Method>) -> String {
+        let methods: Vec<&Method> = methods.into_iter().collect();
+        let allows_get = methods.contains(&&Method::GET);
+        
+        let mut names: Vec<&str> = ALLOW_ORDER.iter()
+            .filter(|method| methods.contains(method) || (**method == Method::HEAD && allows_get))
+            .map(|method| method.as_str())
+            .collect();
+        
+        let mut extensions: Vec<&str> = methods.iter()
+            .filter(|method| !method.is_standard())
+            .map(|method| method.as_str())
+            .collect();
+        extensions.sort_unstable();
+        extensions.dedup();
+        names.extend(extensions);
+        
+        names.join(", ")
+    }
+}
+
+/// `token` from RFC 9110 section 5.6.2
+pub fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
 }
 
 #[derive(Debug, Clone)]
 pub struct HttpRequest {
     pub method: Method,
+    /// Percent-decoded path without dot segments or repeated slashes,
+    /// used for routing and file lookup
     pub path: String,
+    /// Path exactly as it appeared in the request target
+    pub raw_path: String,
     pub version: String,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
     pub body: Vec<u8>,
     pub query_string: Option<String>,
+    /// Trailer fields sent after a chunked body, with lowercase names
+    pub trailers: HashMap<String, String>,
 }
 
 impl HttpRequest {
@@ -51,15 +121,17 @@ impl HttpRequest {
         HttpRequest {
             method: Method::GET,
             path: String::from("/"),
+            raw_path: String::from("/"),
             version: String::from("HTTP/1.1"),
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body: Vec::new(),
             query_string: None,
+            trailers: HashMap::new(),
         }
     }
     
     pub fn get_header(&self, name: &str) -> Option<&str> {
-        self.headers.get(&name.to_lowercase()).map(|v| v.as_str())
+        self.headers.get(name)
     }
     
     pub fn content_length(&self) -> Option<usize> {
@@ -69,12 +141,18 @@ impl HttpRequest {
     
     pub fn is_chunked(&self) -> bool {
         self.get_header("transfer-encoding")
-            .map(|v| v.to_lowercase().contains("chunked"))
+            .and_then(|v| v.rsplit(',').next())
+            .map(|v| v.trim().eq_ignore_ascii_case("chunked"))
             .unwrap_or(false)
     }
     
+    /// Get a trailer field sent after a chunked body
+    pub fn get_trailer(&self, name: &str) -> Option<&str> {
+        self.trailers.get(&name.to_lowercase()).map(|v| v.as_str())
+    }
+    
     pub fn connection_keep_alive(&self) -> bool {
-        match self.get_header("connection") {
+        match self.headers.get_combined("connection") {
             Some(conn) => {
                 let conn_lower = conn.to_lowercase();
                 // HTTP/1.1 defaults to keep-alive unless explicitly closed
@@ -106,6 +184,11 @@ impl HttpRequest {
         &self.path
     }
     
+    /// Get the request path as sent, before decoding and normalization
+    pub fn raw_path(&self) -> &str {
+        &self.raw_path
+    }
+    
     /// Get the request body
     pub fn body(&self) -> Option<&[u8]> {
         if self.body.is_empty() {
@@ -115,3 +198,18 @@ impl HttpRequest {
         }
     }
 }
+
+#[cfg(test)]
+impl HttpRequest {
+    /// Request as the parser would produce it: field names keep the case
+    /// they are given in, lookups go through `HeaderMap`'s case folding
+    pub fn with_headers(method: Method, path: &str, headers: &[(&str, &str)]) -> Self {
+        let mut request = HttpRequest::new();
+        request.method = method;
+        request.path = path.to_string();
+        for (name, value) in headers {
+            request.headers.insert(name.to_string(), value.to_string());
+        }
+        request
+    }
+}
@@ -1,12 +1,46 @@
-use std::collections::HashMap;
 use std::fmt;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::sync::Arc;
+use crate::http::compress::{self, Encoding, MAX_COMPRESS_SIZE, MIN_COMPRESS_SIZE};
+use crate::http::date;
+use crate::http::headers::HeaderMap;
+use crate::mime::MimeTypes;
+
+/// A byte range of an open file sent as part of the response body.
+/// The connection streams it after the headers instead of buffering it.
+#[derive(Debug, Clone)]
+pub struct FileBody {
+    pub file: Arc<File>,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// Piece of a streamed response body
+#[derive(Debug, Clone)]
+pub enum BodyChunk {
+    Bytes(Vec<u8>),
+    File(FileBody),
+}
+
+impl BodyChunk {
+    pub fn length(&self) -> u64 {
+        match self {
+            BodyChunk::Bytes(bytes) => bytes.len() as u64,
+            BodyChunk::File(file_body) => file_body.length,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct HttpResponse {
     pub status_code: u16,
     pub status_text: String,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
     pub body: Vec<u8>,
+    /// Streamed body, sent in order after `body`; used for file contents
+    pub body_stream: Vec<BodyChunk>,
     pub version: String,
 }
 
@@ -14,27 +48,39 @@ impl HttpResponse {
     pub fn new(status_code: u16) -> Self {
         let status_text = match status_code {
             200 => "OK",
+            201 => "Created",
             204 => "No Content",
+            206 => "Partial Content",
             301 => "Moved Permanently",
             302 => "Found",
+            304 => "Not Modified",
             400 => "Bad Request",
             403 => "Forbidden",
             404 => "Not Found",
             405 => "Method Not Allowed",
+            409 => "Conflict",
+            412 => "Precondition Failed",
             413 => "Payload Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            431 => "Request Header Fields Too Large",
             500 => "Internal Server Error",
+            501 => "Not Implemented",
             _ => "Unknown",
         }.to_string();
         
-        let mut headers = HashMap::new();
-        headers.insert("Server".to_string(), "Localhost".to_string());
-        headers.insert("Date".to_string(), Self::current_date());
+        let mut headers = HeaderMap::new();
+        headers.insert("Server", "Localhost");
+        headers.insert("Date", Self::current_date());
         
         HttpResponse {
             status_code,
             status_text,
             headers,
             body: Vec::new(),
+            body_stream: Vec::new(),
             version: "HTTP/1.1".to_string(),
         }
     }
@@ -71,15 +117,121 @@ impl HttpResponse {
         response
     }
     
+    /// Set a header, replacing any values it already has
     pub fn set_header(&mut self, name: &str, value: &str) {
-        self.headers.insert(name.to_string(), value.to_string());
+        self.headers.insert(name, value);
+    }
+    
+    /// Add a header value, keeping any already set (e.g. `Set-Cookie`)
+    pub fn append_header(&mut self, name: &str, value: &str) {
+        self.headers.append(name, value);
+    }
+    
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
     }
     
     pub fn set_body(&mut self, body: &[u8]) {
         self.body = body.to_vec();
+        self.body_stream.clear();
         self.set_header("Content-Length", &self.body.len().to_string());
     }
     
+    /// Stream `length` bytes of `file` starting at `offset` as the body
+    pub fn set_file_body(&mut self, file: Arc<File>, offset: u64, length: u64) {
+        self.set_body_stream(vec![BodyChunk::File(FileBody { file, offset, length })]);
+    }
+    
+    /// Replace the body with streamed chunks
+    pub fn set_body_stream(&mut self, chunks: Vec<BodyChunk>) {
+        self.body.clear();
+        self.body_stream = chunks;
+        self.set_header("Content-Length", &self.body_len().to_string());
+    }
+    
+    /// The file range streamed as the whole body, if that is what this response carries
+    pub fn single_file_body(&self) -> Option<&FileBody> {
+        match self.body_stream.as_slice() {
+            [BodyChunk::File(file_body)] if self.body.is_empty() => Some(file_body),
+            _ => None,
+        }
+    }
+    
+    /// The body bytes, reading streamed file ranges from disk
+    pub fn body_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut content = self.body.clone();
+        for chunk in &self.body_stream {
+            match chunk {
+                BodyChunk::Bytes(bytes) => content.extend_from_slice(bytes),
+                BodyChunk::File(file_body) => {
+                    let start = content.len();
+                    content.resize(start + file_body.length as usize, 0);
+                    file_body.file.read_exact_at(&mut content[start..], file_body.offset)?;
+                }
+            }
+        }
+        Ok(content)
+    }
+    
+    /// Length of the body, whether in memory or streamed
+    pub fn body_len(&self) -> u64 {
+        self.body.len() as u64 + self.body_stream.iter().map(|chunk| chunk.length()).sum::<u64>()
+    }
+    
+    /// Add a field name to the Vary header, keeping any already listed
+    pub fn add_vary(&mut self, field: &str) {
+        let vary = match self.headers.get("Vary") {
+            Some(vary) if vary.split(',').any(|name| name.trim().eq_ignore_ascii_case(field)) => return,
+            Some(vary) => format!("{}, {}", vary, field),
+            None => field.to_string(),
+        };
+        self.set_header("Vary", &vary);
+    }
+    
+    /// Compress a textual 200 response with the best coding `accept_encoding`
+    /// allows. Small, huge, partial and already encoded bodies are left alone.
+    pub fn compress(&mut self, accept_encoding: Option<&str>, mime_types: &MimeTypes) -> io::Result<()> {
+        if self.status_code != 200
+            || self.headers.contains_key("Content-Encoding")
+            || self.headers.contains_key("Content-Range")
+        {
+            return Ok(());
+        }
+        
+        let content_type = match self.headers.get("Content-Type") {
+            Some(content_type) => content_type.split(';').next().unwrap_or("").trim().to_lowercase(),
+            None => return Ok(()),
+        };
+        if !mime_types.is_text_type(&content_type) {
+            return Ok(());
+        }
+        self.add_vary("Accept-Encoding");
+        
+        let length = self.body_len();
+        if !(MIN_COMPRESS_SIZE..=MAX_COMPRESS_SIZE).contains(&length) {
+            return Ok(());
+        }
+        
+        let encoding = match accept_encoding.and_then(|accept| compress::negotiate(accept, &[Encoding::Gzip, Encoding::Deflate])) {
+            Some(encoding) => encoding,
+            None => return Ok(()),
+        };
+        
+        let encoded = encoding.encode(&self.body_bytes()?);
+        if encoded.len() as u64 >= length {
+            return Ok(());
+        }
+        
+        self.set_body(&encoded);
+        self.set_header("Content-Encoding", encoding.as_str());
+        // Byte ranges would refer to the unencoded file
+        self.headers.remove("Accept-Ranges");
+        if let Some(etag) = self.headers.get("ETag").map(str::to_string) {
+            self.set_header("ETag", &compress::variant_etag(&etag, encoding));
+        }
+        Ok(())
+    }
+    
     pub fn set_body_string(&mut self, body: &str) {
         self.set_body(body.as_bytes());
     }
@@ -92,6 +244,8 @@ impl HttpResponse {
         }
     }
     
+    /// Serialize the status line, headers and in-memory body.
+    /// The `body_stream` is not included; the connection streams it separately.
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut response = Vec::new();
         
@@ -115,13 +269,7 @@ impl HttpResponse {
     }
     
     fn current_date() -> String {
-        // Simple date format - in production would use proper HTTP date formatting
-        use std::time::{SystemTime, UNIX_EPOCH};
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        format!("timestamp-{}", timestamp)
+        date::cached_date()
     }
 }
 
@@ -0,0 +1,253 @@
+use crate::http::request::Method;
+use std::io::{self, ErrorKind};
+
+/// The request target of a request line (RFC 9112 section 3.2)
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestTarget {
+    /// `/path?query`, the usual form
+    Origin { path: String, query: Option<String> },
+    /// `http://host/path?query`, as sent to proxies
+    Absolute { scheme: String, authority: String, path: String, query: Option<String> },
+    /// `host:port`, only used by CONNECT
+    Authority(String),
+    /// `*`, only used by OPTIONS
+    Asterisk,
+}
+
+impl RequestTarget {
+    /// Parse a request target, choosing the form the method allows
+    pub fn parse(target: &str, method: &Method) -> io::Result<Self> {
+        if *method == Method::CONNECT {
+            if target.is_empty() || target.contains(['/', '?', '#']) {
+                return Err(invalid("Invalid authority-form request target"));
+            }
+            return Ok(RequestTarget::Authority(target.to_string()));
+        }
+        
+        if target == "*" {
+            if *method != Method::OPTIONS {
+                return Err(invalid("Asterisk-form request target is only allowed for OPTIONS"));
+            }
+            return Ok(RequestTarget::Asterisk);
+        }
+        
+        if target.contains('#') {
+            return Err(invalid("Fragment in request target"));
+        }
+        
+        if target.starts_with('/') {
+            let (path, query) = split_query(target);
+            return Ok(RequestTarget::Origin { path, query });
+        }
+        
+        // absolute-URI: scheme "://" authority path-abempty [ "?" query ]
+        let (scheme, rest) = target.split_once("://")
+            .ok_or_else(|| invalid("Invalid request target"))?;
+        let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+        if !valid_scheme {
+            return Err(invalid("Invalid scheme in request target"));
+        }
+        
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let authority = &rest[..authority_end];
+        if authority.is_empty() || authority.contains('@') {
+            return Err(invalid("Invalid authority in request target"));
+        }
+        
+        let (path, query) = split_query(&rest[authority_end..]);
+        let path = if path.is_empty() { "/".to_string() } else { path };
+        Ok(RequestTarget::Absolute {
+            scheme: scheme.to_ascii_lowercase(),
+            authority: authority.to_string(),
+            path,
+            query,
+        })
+    }
+    
+    /// Path as sent, still percent-encoded; `*` and the authority for the
+    /// asterisk and authority forms
+    pub fn raw_path(&self) -> &str {
+        match self {
+            RequestTarget::Origin { path, .. } | RequestTarget::Absolute { path, .. } => path,
+            RequestTarget::Authority(authority) => authority,
+            RequestTarget::Asterisk => "*",
+        }
+    }
+    
+    pub fn query(&self) -> Option<&str> {
+        match self {
+            RequestTarget::Origin { query, .. } | RequestTarget::Absolute { query, .. } => query.as_deref(),
+            _ => None,
+        }
+    }
+    
+    /// Host named by an absolute-form target, which takes precedence over
+    /// the Host header (RFC 9112 section 3.2.2)
+    pub fn authority(&self) -> Option<&str> {
+        match self {
+            RequestTarget::Absolute { authority, .. } => Some(authority),
+            _ => None,
+        }
+    }
+}
+
+/// Split a path from its query at the first `?`
+fn split_query(target: &str) -> (String, Option<String>) {
+    match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, reason)
+}
+
+/// Decode the percent-encoded octets of a path. Encoded NULs are always
+/// rejected; an encoded `/` is rejected unless `allow_encoded_slash` is set,
+/// in which case it stays encoded so it never separates segments.
+pub fn percent_decode_path(path: &str, allow_encoded_slash: bool) -> io::Result<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        
+        let byte = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| invalid("Invalid percent-encoding in request path"))?;
+        
+        match byte {
+            0 => return Err(invalid("Encoded NUL in request path")),
+            b'/' if !allow_encoded_slash => return Err(invalid("Encoded slash in request path")),
+            b'/' => decoded.extend_from_slice(&bytes[i..i + 3]),
+            _ => decoded.push(byte),
+        }
+        i += 3;
+    }
+    
+    String::from_utf8(decoded).map_err(|_| invalid("Request path is not valid UTF-8 once decoded"))
+}
+
+/// Remove `.` and `..` segments (RFC 3986 section 5.2.4). `..` never
+/// climbs above the root.
+pub fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').collect();
+    
+    for (index, segment) in segments.iter().enumerate() {
+        let last = index == segments.len() - 1;
+        match *segment {
+            "." | ".." => {
+                // The empty segment before a leading slash is the root
+                if *segment == ".." && !(output.is_empty() || output == [""]) {
+                    output.pop();
+                }
+                // A trailing dot segment leaves the path ending in a slash
+                if last {
+                    output.push("");
+                }
+            }
+            _ => output.push(segment),
+        }
+    }
+    
+    let joined = output.join("/");
+    if path.starts_with('/') && !joined.starts_with('/') {
+        format!("/{}", joined)
+    } else {
+        joined
+    }
+}
+
+/// Collapse runs of slashes into one
+pub fn collapse_slashes(path: &str) -> String {
+    let mut collapsed = String::with_capacity(path.len());
+    for c in path.chars() {
+        if !(c == '/' && collapsed.ends_with('/')) {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// The path used for routing and file lookup: percent-decoded, without
+/// duplicate slashes or dot segments
+pub fn normalize_path(raw_path: &str, allow_encoded_slash: bool) -> io::Result<String> {
+    let decoded = percent_decode_path(raw_path, allow_encoded_slash)?;
+    Ok(remove_dot_segments(&collapse_slashes(&decoded)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_request_target_forms() {
+        let origin = RequestTarget::parse("/a/b?x=1", &Method::GET).unwrap();
+        assert_eq!(origin, RequestTarget::Origin { path: "/a/b".to_string(), query: Some("x=1".to_string()) });
+        
+        let absolute = RequestTarget::parse("HTTP://example.com:8080?q", &Method::GET).unwrap();
+        assert_eq!(absolute.raw_path(), "/");
+        assert_eq!(absolute.query(), Some("q"));
+        assert_eq!(absolute.authority(), Some("example.com:8080"));
+        
+        assert_eq!(RequestTarget::parse("*", &Method::OPTIONS).unwrap(), RequestTarget::Asterisk);
+        assert_eq!(RequestTarget::parse("example.com:443", &Method::CONNECT).unwrap().raw_path(), "example.com:443");
+        
+        for (target, method) in [
+            ("*", Method::GET),
+            ("a/b", Method::GET),
+            ("/a#frag", Method::GET),
+            ("http://user@host/", Method::GET),
+            ("1http://host/", Method::GET),
+            ("http:///path", Method::GET),
+            ("/path", Method::CONNECT),
+        ] {
+            assert!(RequestTarget::parse(target, &method).is_err(), "{}", target);
+        }
+    }
+    
+    #[test]
+    fn test_percent_decoding() {
+        assert_eq!(percent_decode_path("/hello%20world.txt", false).unwrap(), "/hello world.txt");
+        assert_eq!(percent_decode_path("/caf%C3%A9", false).unwrap(), "/café");
+        assert_eq!(percent_decode_path("/a%2fb", true).unwrap(), "/a%2fb");
+        
+        for path in ["/a%2Fb", "/a%00", "/a%2", "/a%zz", "/%C3"] {
+            assert!(percent_decode_path(path, false).is_err(), "{}", path);
+        }
+        assert!(percent_decode_path("/a%00", true).is_err());
+    }
+    
+    #[test]
+    fn test_remove_dot_segments() {
+        // Examples from RFC 3986 section 5.2.4
+        assert_eq!(remove_dot_segments("/a/b/c/./../../g"), "/a/g");
+        assert_eq!(remove_dot_segments("mid/content=5/../6"), "mid/6");
+        assert_eq!(remove_dot_segments("a/../b"), "b");
+        
+        assert_eq!(remove_dot_segments("/../../etc/passwd"), "/etc/passwd");
+        assert_eq!(remove_dot_segments("/a/b/.."), "/a/");
+        assert_eq!(remove_dot_segments("/a/."), "/a/");
+        assert_eq!(remove_dot_segments("/.."), "/");
+        assert_eq!(remove_dot_segments("/"), "/");
+        assert_eq!(remove_dot_segments("/a/b/"), "/a/b/");
+    }
+    
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("//a///b/../c", false).unwrap(), "/a/c");
+        assert_eq!(normalize_path("/%2e%2e/%2E%2E/etc/passwd", false).unwrap(), "/etc/passwd");
+        assert_eq!(normalize_path("/docs/my%20file.txt", false).unwrap(), "/docs/my file.txt");
+        assert!(normalize_path("/a/..%2f..%2fetc", false).is_err());
+    }
+}
@@ -11,10 +11,16 @@ mod session;
 mod cgi;
 
 use std::process;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::env;
-use net::event_loop::EventLoop;
-use config::server::ServerConfig;
+use std::net::TcpListener;
+use std::sync::Arc;
+use net::reload::SharedConfig;
+use net::notify::Notifier;
+use net::signals::SignalSet;
+use net::upgrade::InheritedListeners;
+use net::worker::WorkerPool;
+use config::server::{RouteType, ServerConfig};
 use config::parser::{ConfigParser, ConfigFormat};
 use config::validation::ConfigValidator;
 use session::{SessionStore, SessionConfig};
@@ -22,8 +28,22 @@ use session::{SessionStore, SessionConfig};
 fn main() {
     println!("🚀 Starting Localhost HTTP Server");
     
+    // Sockets handed over by a running server this process replaces, or by systemd
+    let mut inherited = match InheritedListeners::from_env() {
+        Ok(inherited) => inherited,
+        Err(e) => {
+            eprintln!("❌ Failed to adopt inherited listeners: {}", e);
+            process::exit(1);
+        }
+    };
+    if inherited.is_upgrade() {
+        println!("♻️  Taking over listening sockets from a running server");
+    } else if !inherited.is_empty() {
+        println!("♻️  Using listening sockets passed by systemd");
+    }
+    
     // Load configuration from file or use defaults
-    let config = load_config();
+    let (config, config_path) = load_config();
     
     // Validate configuration
     let mut validator = ConfigValidator::new();
@@ -50,13 +70,25 @@ fn main() {
             listener.port,
             if listener.default { "default" } else { "secondary" }
         );
-        println!("      Max body size: 10485760 bytes");
-        println!("      Routes: 5");
-        println!("         / -> www [GET, HEAD]");
-        println!("         /uploads/* -> uploads [GET, DELETE]");
-        println!("         /upload -> uploads [GET, POST]");
-        println!("         /session/* -> N/A [GET, POST, DELETE]");
-        println!("         /cgi-bin/* -> cgi-bin [GET, POST]");
+    }
+    
+    for vhost in &config.virtual_hosts {
+        println!("   Virtual host: {} -> {}", vhost.server_name, vhost.document_root.display());
+        println!("      Max body size: {} bytes", vhost.max_body_size);
+        println!("      Routes: {}", vhost.routes.len());
+        for route in &vhost.routes {
+            let kind = match &route.route_type {
+                RouteType::Static { .. } => "static",
+                RouteType::Cgi { .. } => "cgi",
+                RouteType::Redirect { .. } => "redirect",
+                RouteType::Session => "session",
+                RouteType::Proxy { .. } => "proxy",
+            };
+            println!("         {} -> {} [{}]", route.path, kind, route.methods.join(", "));
+        }
+        for redirect in &vhost.redirects {
+            println!("         {} -> {} ({})", redirect.from, redirect.to, redirect.status);
+        }
     }
     println!();
     
@@ -68,8 +100,8 @@ fn main() {
     println!("🌐 Starting server...");
     println!("Starting HTTP server...");
     
-    // Bind to all configured listeners
-    let mut event_loops = Vec::new();
+    // Listeners are bound once, or inherited, and shared by every worker's event loop
+    let mut bound_listeners = Vec::new();
     let mut successful_binds = 0;
     
     for listener in &config.listeners {
@@ -77,15 +109,21 @@ fn main() {
         println!("🔌 Attempting to bind to {} ({})", addr, 
             if listener.default { "default" } else { "secondary" });
         
-        match EventLoop::new_with_config(
-            &addr,
-            None,
-            Some(session_store.clone()),
-        ) {
-            Ok(el) => {
-                println!("✅ Successfully bound to {}", addr);
-                println!("🔧 Added listener to event manager");
-                event_loops.push(el);
+        let socket = match inherited.take(listener) {
+            Some(socket) => {
+                println!("♻️  Adopted inherited listener for {}", addr);
+                Ok(socket)
+            }
+            None => TcpListener::bind((listener.address.as_str(), listener.port)),
+        };
+        
+        match socket {
+            Ok(socket) => {
+                match socket.local_addr() {
+                    Ok(bound) => println!("✅ Successfully bound to {}", bound),
+                    Err(_) => println!("✅ Successfully bound to {}", addr),
+                }
+                bound_listeners.push((socket, listener.clone()));
                 successful_binds += 1;
             },
             Err(e) => {
@@ -98,6 +136,8 @@ fn main() {
         }
     }
     
+    inherited.close_unused();
+    
     if successful_binds == 0 {
         eprintln!("❌ No listeners could be bound, exiting");
         process::exit(1);
@@ -105,27 +145,53 @@ fn main() {
     
     println!("🎯 Listener setup complete:");
     println!("   ✅ Successful: {} listeners", successful_binds);
-    println!("🚀 Server ready with {} active listener(s)", successful_binds);
+    
+    // Signals are blocked here, before any worker starts, and read by the event loops
+    let signals = match SignalSet::new(&[libc::SIGTERM, libc::SIGINT, libc::SIGHUP, libc::SIGUSR2]) {
+        Ok(signals) => Some(Arc::new(signals)),
+        Err(e) => {
+            eprintln!("⚠️  Failed to set up signal handling, shutdown will not be graceful: {}", e);
+            None
+        }
+    };
+    
+    // Workers serve this snapshot until SIGHUP publishes a reloaded one
+    let shared_config = Arc::new(SharedConfig::new(Arc::new(config), bound_listeners));
+    let mut pool = match WorkerPool::start(shared_config, session_store, signals) {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("❌ Failed to start workers: {}", e);
+            process::exit(1);
+        }
+    };
+    println!("🚀 Server ready with {} active listener(s) and {} worker(s)", successful_binds, pool.len());
     println!("Server started successfully!");
     println!("Listening on {} server(s)", successful_binds);
-    println!("Event loop started, waiting for connections...");
+    println!("Event loops started, waiting for connections...");
     println!();
     
-    // Run the first event loop (for now, single-threaded)
-    // In a full implementation, you'd use multiple threads or a single event loop with multiple listeners
-    if let Some(mut event_loop) = event_loops.into_iter().next() {
-        if let Err(e) = event_loop.event_loop() {
-            eprintln!("Server error: {}", e);
-            process::exit(1);
-        }
+    // Readiness goes to systemd before the server this one replaces drains,
+    // so the service never seems to lose its main process
+    pool.set_notifier(Notifier::from_env());
+    pool.notify_ready();
+    inherited.stop_parent();
+    
+    // Exit 0 after a graceful shutdown so service managers see a clean stop
+    if let Err(e) = pool.wait(config_path.as_deref()) {
+        eprintln!("Server error: {}", e);
+        process::exit(1);
     }
+    println!("👋 Server stopped");
 }
 
-fn load_config() -> ServerConfig {
+/// Load the first configuration file found, returning its path so SIGHUP
+/// can read it again
+fn load_config() -> (ServerConfig, Option<PathBuf>) {
     // Check for config file in command line args or default locations
     let config_paths = vec![
         env::args().nth(1).unwrap_or_default(), // First argument
         "server.toml".to_string(),
+        "server.json".to_string(),
         "test_multi_port.toml".to_string(),
         "config/server.toml".to_string(),
     ];
@@ -142,7 +208,7 @@ fn load_config() -> ServerConfig {
             match parser.parse_file(&path) {
                 Ok(config) => {
                     println!("✅ Configuration file loaded successfully");
-                    return config;
+                    return (config, Some(PathBuf::from(path)));
                 }
                 Err(e) => {
                     eprintln!("⚠️  Failed to parse {}: {}", path, e);
@@ -152,5 +218,5 @@ fn load_config() -> ServerConfig {
     }
     
     println!("ℹ️  No configuration file found, using defaults");
-    ServerConfig::default()
+    (ServerConfig::default(), None)
 }
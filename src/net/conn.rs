@@ -1,15 +1,44 @@
 use std::io::{self, Read, Write, ErrorKind};
 use std::net::{TcpStream, SocketAddr};
-use crate::http::parse::HttpParser;
+use crate::http::parse::{HttpParser, ParseError, ParseLimits};
 use crate::http::request::{HttpRequest, Method};
-use crate::http::response::HttpResponse;
+use crate::http::response::{BodyChunk, FileBody, HttpResponse};
 use crate::fs::static_files::StaticFileServer;
 use crate::routing::router::{Router, VirtualHost};
-use crate::routing::route::RouteConfig;
-use crate::config::server::VirtualHostConfig;
+use crate::routing::redirections::{CacheSettings, CorsSettings};
+use crate::routing::route::{Route, RouteConfig};
+use crate::config::server::{CacheConfig, CorsConfig, SecurityConfig, UploadConfig, VirtualHostConfig, RouteType};
+use crate::net::multi_server::ServerSelector;
 use crate::session::{SessionStore, CookieJar};
-use std::collections::HashMap;
-use std::path::Path;
+use crate::upload::file_storage::{FileStorage, StorageConfig};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Largest piece of a file body sent per write call
+const FILE_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Most pipelined requests parsed ahead of their responses being sent.
+/// Further requests stay unread until the queue drains.
+const MAX_PIPELINED_REQUESTS: usize = 16;
+
+/// Interim response inviting a client to send the body it announced
+const CONTINUE_RESPONSE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+/// Settings from a configuration reload
+pub struct ConfigUpdate {
+    pub virtual_hosts: Arc<HashMap<String, VirtualHostConfig>>,
+    pub default_host: Option<String>,
+    pub uploads: UploadConfig,
+    pub security: SecurityConfig,
+}
+
+/// A response waiting for the ones before it to be sent
+struct QueuedResponse {
+    /// Status line, headers and in-memory body
+    head: Vec<u8>,
+    body_stream: VecDeque<BodyChunk>,
+}
 
 pub struct Connection {
     stream: TcpStream,
@@ -17,42 +46,171 @@ pub struct Connection {
     parser: HttpParser,
     write_buffer: Vec<u8>,
     write_pos: usize,
-    current_request: Option<HttpRequest>,
+    /// File range currently being sent, once `write_buffer` is drained
+    file_body: Option<FileBody>,
+    /// Body pieces still to be sent after the current one
+    body_stream: VecDeque<BodyChunk>,
+    /// Responses to pipelined requests, sent in order after the current one
+    responses: VecDeque<QueuedResponse>,
+    /// Parsed requests not answered yet, in the order they arrived
+    requests: VecDeque<HttpRequest>,
+    /// Status to answer with when the request could not be parsed
+    rejected_status: Option<u16>,
     keep_alive: bool,
+    /// The server is shutting down: close after the next response
+    draining: bool,
+    /// Requests answered so far
+    requests_answered: usize,
     static_server: StaticFileServer,
     router: Router,
     session_store: SessionStore,
+    /// Address of the listener this connection was accepted on
+    listener_addr: SocketAddr,
+    /// Every configured virtual host, shared with the event loop
+    virtual_hosts: Arc<HashMap<String, VirtualHostConfig>>,
+    default_host: Option<String>,
+    /// Virtual host the router and static server are currently built for
     vhost_config: Option<VirtualHostConfig>,
+    /// Global `[upload]` settings used for the router's file storage
+    uploads: UploadConfig,
+    /// Reloaded settings waiting for the request in progress to finish
+    pending_config: Option<ConfigUpdate>,
 }
 
 impl Connection {
     pub fn new(stream: TcpStream, addr: SocketAddr) -> io::Result<Self> {
-        Self::new_with_config(stream, addr, None, None)
+        let listener_addr = stream.local_addr()?;
+        Self::new_with_config(stream, addr, listener_addr, Arc::default(), None, None, UploadConfig::default())
     }
     
     pub fn new_with_config(
         stream: TcpStream,
         addr: SocketAddr,
-        vhost_config: Option<VirtualHostConfig>,
+        listener_addr: SocketAddr,
+        virtual_hosts: Arc<HashMap<String, VirtualHostConfig>>,
+        default_host: Option<String>,
         session_store: Option<SessionStore>,
+        uploads: UploadConfig,
     ) -> io::Result<Self> {
+        // Start with the default virtual host until a request names another one
+        let vhost_config = ServerSelector::select_virtual_host(
+            &virtual_hosts,
+            None,
+            default_host.as_deref(),
+        ).cloned();
+        let (static_server, router) = Self::build_handlers(vhost_config.as_ref(), &uploads);
+        
+        // Use provided session store or create default
+        let session_store = session_store.unwrap_or_else(|| {
+            use crate::session::SessionConfig;
+            SessionStore::new(SessionConfig::default())
+        });
+        
+        let mut parser = HttpParser::new();
+        Self::install_body_limit(&mut parser, &virtual_hosts, &default_host);
+        
+        Ok(Connection {
+            stream,
+            addr,
+            parser,
+            write_buffer: Vec::new(),
+            write_pos: 0,
+            file_body: None,
+            body_stream: VecDeque::new(),
+            responses: VecDeque::new(),
+            requests: VecDeque::new(),
+            rejected_status: None,
+            keep_alive: true,
+            draining: false,
+            requests_answered: 0,
+            static_server,
+            router,
+            session_store,
+            listener_addr,
+            virtual_hosts,
+            default_host,
+            vhost_config,
+            uploads,
+            pending_config: None,
+        })
+    }
+    
+    /// Body limits depend on the route, which is only known once the headers are in
+    fn install_body_limit(parser: &mut HttpParser, virtual_hosts: &Arc<HashMap<String, VirtualHostConfig>>, default_host: &Option<String>) {
+        let limit_hosts = Arc::clone(virtual_hosts);
+        let limit_default = default_host.clone();
+        parser.set_body_limit(move |request| {
+            Self::request_body_limit(&limit_hosts, limit_default.as_deref(), request)
+        });
+    }
+    
+    /// Serve the next request with reloaded settings. A request already
+    /// being read or answered finishes with the settings it started with.
+    pub fn update_config(&mut self, update: ConfigUpdate) {
+        self.pending_config = Some(update);
+        if self.is_idle() {
+            self.apply_pending_config();
+        }
+    }
+    
+    fn apply_pending_config(&mut self) {
+        let update = match self.pending_config.take() {
+            Some(update) => update,
+            None => return,
+        };
+        
+        Self::install_body_limit(&mut self.parser, &update.virtual_hosts, &update.default_host);
+        self.set_security(&update.security);
+        self.virtual_hosts = update.virtual_hosts;
+        self.default_host = update.default_host;
+        self.uploads = update.uploads;
+        
+        // Rebuild the handlers of the current virtual host from its new settings
+        let current = self.vhost_config.as_ref().map(|v| v.server_name.clone());
+        let selected = ServerSelector::select_virtual_host(
+            &self.virtual_hosts,
+            current.as_deref(),
+            self.default_host.as_deref(),
+        ).cloned();
+        let (static_server, router) = Self::build_handlers(selected.as_ref(), &self.uploads);
+        self.static_server = static_server;
+        self.router = router;
+        self.vhost_config = selected;
+    }
+    
+    /// Apply the global `[security]` settings to request parsing
+    pub fn set_security(&mut self, security: &SecurityConfig) {
+        self.parser.set_strict(security.strict_parsing);
+        self.parser.set_limits(Self::convert_parse_limits(security));
+        self.parser.set_allow_encoded_slash(security.allow_encoded_slashes);
+    }
+    
+    /// Build the static file server and router for a virtual host,
+    /// falling back to the built-in routes when none is configured
+    fn build_handlers(vhost_config: Option<&VirtualHostConfig>, uploads: &UploadConfig) -> (StaticFileServer, Router) {
         // Determine document root from config or use default
-        let document_root = vhost_config.as_ref()
+        let document_root = vhost_config
             .map(|c| c.document_root.to_string_lossy().to_string())
             .unwrap_or_else(|| "./www".to_string());
         
         // Create static file server
-        let static_server = StaticFileServer::new(&document_root, None)
+        let mut static_server = StaticFileServer::new(&document_root, None)
             .unwrap_or_else(|_| {
                 StaticFileServer::new("./www", None)
                     .expect("Failed to create static file server")
             });
+        let cache = vhost_config.map(|c| c.cache.clone()).unwrap_or_default();
+        static_server.set_cache_settings(Self::convert_cache_config(&cache));
         
         // Create router with configuration
         let mut router = Router::new();
+        match FileStorage::new(Self::convert_upload_config(uploads)) {
+            Ok(storage) => router.set_file_storage(storage),
+            Err(e) => eprintln!("Failed to create upload directory {}: {}", uploads.directory.display(), e),
+        }
         
         // Configure virtual host from config or use default
-        if let Some(ref config) = vhost_config {
+        if let Some(config) = vhost_config {
             let vhost = Self::config_to_vhost(config);
             router.add_virtual_host(vhost);
         } else {
@@ -68,28 +226,37 @@ impl Connection {
                 path: "/".to_string(),
                 allowed_methods: root_methods,
                 document_root: None,
-                index_file: Some("index.html".to_string()),
+                index_files: vec!["index.html".to_string()],
                 directory_listing: false,
                 redirect: None,
+                redirect_status: None,
                 cgi_extension: None,
+                cgi_directory: None,
                 max_body_size: Some(10 * 1024 * 1024),
+                upload_dir: None,
+                sessions: false,
                 error_pages: HashMap::new(),
             };
             
-            // Route 2: Uploads - GET/DELETE
+            // Route 2: Uploads - GET/PUT/DELETE
             let mut upload_methods = HashSet::new();
             upload_methods.insert(Method::GET);
+            upload_methods.insert(Method::PUT);
             upload_methods.insert(Method::DELETE);
             
             let uploads_route = RouteConfig {
                 path: "/uploads/*".to_string(),
                 allowed_methods: upload_methods,
                 document_root: None,
-                index_file: None,
+                index_files: Vec::new(),
                 directory_listing: false,
                 redirect: None,
+                redirect_status: None,
                 cgi_extension: None,
+                cgi_directory: None,
                 max_body_size: Some(10 * 1024 * 1024),
+                upload_dir: None,
+                sessions: false,
                 error_pages: HashMap::new(),
             };
             
@@ -102,11 +269,15 @@ impl Connection {
                 path: "/upload".to_string(),
                 allowed_methods: upload_post_methods,
                 document_root: None,
-                index_file: None,
+                index_files: Vec::new(),
                 directory_listing: false,
                 redirect: None,
+                redirect_status: None,
                 cgi_extension: None,
+                cgi_directory: None,
                 max_body_size: Some(10 * 1024 * 1024),
+                upload_dir: None,
+                sessions: false,
                 error_pages: HashMap::new(),
             };
             
@@ -120,11 +291,15 @@ impl Connection {
                 path: "/session/*".to_string(),
                 allowed_methods: session_methods,
                 document_root: None,
-                index_file: None,
+                index_files: Vec::new(),
                 directory_listing: false,
                 redirect: None,
+                redirect_status: None,
                 cgi_extension: None,
+                cgi_directory: None,
                 max_body_size: Some(10 * 1024 * 1024),
+                upload_dir: None,
+                sessions: true,
                 error_pages: HashMap::new(),
             };
             
@@ -137,11 +312,15 @@ impl Connection {
                 path: "/cgi-bin/*".to_string(),
                 allowed_methods: cgi_methods,
                 document_root: None,
-                index_file: None,
+                index_files: Vec::new(),
                 directory_listing: false,
                 redirect: None,
+                redirect_status: None,
                 cgi_extension: Some("py".to_string()), // Will match .py, .pl, .sh, etc.
+                cgi_directory: Some(PathBuf::from("./www/cgi-bin")),
                 max_body_size: Some(10 * 1024 * 1024),
+                upload_dir: None,
+                sessions: false,
                 error_pages: HashMap::new(),
             };
             
@@ -151,29 +330,12 @@ impl Connection {
                 document_root: "./www".to_string(),
                 error_pages: HashMap::new(),
                 max_body_size: 10 * 1024 * 1024,
+                cors: CorsSettings::default(),
             };
             router.add_virtual_host(default_vhost);
         }
         
-        // Use provided session store or create default
-        let session_store = session_store.unwrap_or_else(|| {
-            use crate::session::SessionConfig;
-            SessionStore::new(SessionConfig::default())
-        });
-        
-        Ok(Connection {
-            stream,
-            addr,
-            parser: HttpParser::new(),
-            write_buffer: Vec::new(),
-            write_pos: 0,
-            current_request: None,
-            keep_alive: true,
-            static_server,
-            router,
-            session_store,
-            vhost_config,
-        })
+        (static_server, router)
     }
     
     /// Convert VirtualHostConfig to VirtualHost for router
@@ -183,10 +345,15 @@ impl Connection {
             error_pages.insert(*code, path.to_string_lossy().to_string());
         }
         
+        // Redirect rules come first so they win over a route with the same path
+        let mut routes: Vec<RouteConfig> = config.redirects.iter().map(|r| {
+            Self::convert_redirect_config(r)
+        }).collect();
+        
         // Convert routes from config
-        let routes = config.routes.iter().map(|r| {
+        routes.extend(config.routes.iter().map(|r| {
             Self::convert_route_config(r)
-        }).collect();
+        }));
         
         VirtualHost {
             server_name: config.server_name.clone(),
@@ -194,6 +361,68 @@ impl Connection {
             document_root: config.document_root.to_string_lossy().to_string(),
             error_pages,
             max_body_size: config.max_body_size,
+            cors: Self::convert_cors_config(&config.cors),
+        }
+    }
+    
+    /// Convert `[vhost.cache]` to the cache settings used for responses
+    fn convert_cache_config(config: &CacheConfig) -> CacheSettings {
+        CacheSettings {
+            cache_control: None,
+            expires: None,
+            etag_enabled: config.enabled && config.etag,
+            last_modified_enabled: config.enabled && config.last_modified,
+            enabled: config.enabled,
+        }
+    }
+    
+    /// Convert `[vhost.cors]` to the CORS settings used by the router
+    fn convert_cors_config(config: &CorsConfig) -> CorsSettings {
+        CorsSettings {
+            allowed_origins: config.allowed_origins.clone(),
+            allowed_methods: config.allowed_methods.clone(),
+            allowed_headers: config.allowed_headers.clone(),
+            exposed_headers: config.exposed_headers.clone(),
+            allow_credentials: config.allow_credentials,
+            max_age: config.max_age,
+            enabled: config.enabled,
+        }
+    }
+    
+    /// Convert `[security]` limits to the parser's request size limits
+    fn convert_parse_limits(config: &SecurityConfig) -> ParseLimits {
+        ParseLimits {
+            max_request_line: config.max_request_line,
+            max_uri_length: config.max_uri_length,
+            max_header_size: config.max_header_size,
+            max_header_section: config.max_header_section_size,
+            max_headers: config.max_headers,
+        }
+    }
+    
+    /// Convert `[upload]` to the file storage settings used by the router
+    fn convert_upload_config(config: &UploadConfig) -> StorageConfig {
+        StorageConfig {
+            upload_dir: config.directory.clone(),
+            max_file_size: config.max_file_size,
+            allowed_extensions: config.allowed_extensions.clone(),
+            ..StorageConfig::default()
+        }
+    }
+    
+    /// Convert a `[[vhost.redirect]]` rule to a routing RouteConfig
+    fn convert_redirect_config(config: &crate::config::server::RedirectConfig) -> RouteConfig {
+        use crate::http::request::Method;
+        
+        // 307/308 preserve the method, so redirect every method rather than answering 405
+        RouteConfig {
+            path: config.from.clone(),
+            allowed_methods: [Method::GET, Method::HEAD, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS]
+                .into_iter()
+                .collect(),
+            redirect: Some(config.to.clone()),
+            redirect_status: Some(config.status),
+            ..RouteConfig::default()
         }
     }
     
@@ -212,85 +441,128 @@ impl Connection {
             allowed_methods.insert(Method::HEAD);
         } else {
             for method_str in &config.methods {
-                match method_str.to_uppercase().as_str() {
-                    "GET" => { allowed_methods.insert(Method::GET); }
-                    "POST" => { allowed_methods.insert(Method::POST); }
-                    "DELETE" => { allowed_methods.insert(Method::DELETE); }
-                    "PUT" => { allowed_methods.insert(Method::PUT); }
-                    "HEAD" => { allowed_methods.insert(Method::HEAD); }
-                    "OPTIONS" => { allowed_methods.insert(Method::OPTIONS); }
-                    _ => {}
+                if let Some(method) = Method::from_str(&method_str.to_uppercase()) {
+                    allowed_methods.insert(method);
                 }
             }
         }
         
-        RouteConfig {
+        let mut route = RouteConfig {
             path: config.path.clone(),
             allowed_methods,
             document_root: None,
-            index_file: None,
+            index_files: Vec::new(),
             directory_listing: false,
             redirect: None,
+            redirect_status: None,
             cgi_extension: None,
+            cgi_directory: None,
             max_body_size: config.settings.max_body_size,
+            upload_dir: config.settings.upload_dir.clone(),
+            sessions: false,
             error_pages: config.settings.error_pages.clone(),
+        };
+        
+        match &config.route_type {
+            RouteType::Static { directory_listing, index_files, .. } => {
+                route.directory_listing = *directory_listing;
+                route.index_files = index_files.clone();
+            }
+            RouteType::Cgi { script_dir, .. } => {
+                route.cgi_directory = Some(script_dir.clone());
+            }
+            RouteType::Redirect { target, status } => {
+                route.redirect = Some(target.clone());
+                route.redirect_status = Some(*status);
+            }
+            RouteType::Session => {
+                route.sessions = true;
+            }
+            RouteType::Proxy { .. } => {
+                // ConfigValidator rejects proxy routes; should one get here,
+                // answer 405 rather than serving the path as static files
+                route.allowed_methods.clear();
+            }
         }
+        
+        route
     }
     
     pub fn addr(&self) -> SocketAddr {
         self.addr
     }
     
-    /// Handle read event. Returns Ok(true) if request is complete, Ok(false) if more data needed
+    /// Address of the listener this connection was accepted on
+    pub fn listener_addr(&self) -> SocketAddr {
+        self.listener_addr
+    }
+    
+    /// Body size limit for a request: its route's limit, else its virtual host's.
+    /// `None` when no virtual hosts are configured.
+    fn request_body_limit(
+        virtual_hosts: &HashMap<String, VirtualHostConfig>,
+        default_host: Option<&str>,
+        request: &HttpRequest,
+    ) -> Option<usize> {
+        let vhost = ServerSelector::select_virtual_host(virtual_hosts, request.host(), default_host)?;
+        
+        // Most specific matching route wins, as in the router
+        let route_limit = vhost.routes.iter()
+            .filter(|r| Route::new(RouteConfig { path: r.path.clone(), ..RouteConfig::default() }).matches(request.path()))
+            .max_by_key(|r| r.path.len())
+            .and_then(|r| r.settings.max_body_size);
+        
+        Some(route_limit.unwrap_or(vhost.max_body_size))
+    }
+    
+    /// Switch the router and static server to the virtual host named by the
+    /// request's Host header, if it differs from the current one
+    fn select_virtual_host(&mut self, request: &HttpRequest) {
+        let selected = ServerSelector::select_virtual_host(
+            &self.virtual_hosts,
+            request.host(),
+            self.default_host.as_deref(),
+        );
+        
+        let current = self.vhost_config.as_ref().map(|v| v.server_name.as_str());
+        if selected.map(|v| v.server_name.as_str()) == current {
+            return;
+        }
+        
+        let selected = selected.cloned();
+        let (static_server, router) = Self::build_handlers(selected.as_ref(), &self.uploads);
+        self.static_server = static_server;
+        self.router = router;
+        self.vhost_config = selected;
+    }
+    
+    /// Handle read event. Returns Ok(true) once at least one request is
+    /// complete, Ok(false) if more data is needed
     pub fn handle_read(&mut self) -> io::Result<bool> {
+        // A reload waiting for the previous request applies to this one
+        if self.pending_config.is_some() && self.is_idle() {
+            self.apply_pending_config();
+        }
+        
+        // Requests pipelined behind the last one may already be buffered
+        self.parse_requests(&[])?;
+        
         let mut temp_buf = [0u8; 4096];
         
-        loop {
+        while self.accepts_requests() {
             match self.stream.read(&mut temp_buf) {
                 Ok(0) => {
-                    // EOF - connection closed by client
-                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "Client closed connection"));
+                    if self.requests.is_empty() {
+                        // EOF - connection closed by client
+                        return Err(io::Error::new(ErrorKind::UnexpectedEof, "Client closed connection"));
+                    }
+                    // Answer what was sent before the client stopped sending
+                    self.keep_alive = false;
+                    break;
                 }
                 Ok(n) => {
                     // Parse the incoming data
-                    match self.parser.parse(&temp_buf[..n]) {
-                        Ok(Some(request)) => {
-                            // Request parsing complete
-                            println!("Parsed request: {} {}", request.method.as_str(), request.path);
-                            
-                            // Debug: Print all headers to see what we're receiving
-                            println!("Request headers:");
-                            for (name, value) in &request.headers {
-                                println!("  {}: {}", name, value);
-                            }
-                            
-                            // Validate required headers for HTTP/1.1 (be more lenient for local development)
-                            if request.version == "HTTP/1.1" {
-                                match request.host() {
-                                    Some(host) => {
-                                        println!("Host header found: {}", host);
-                                    }
-                                    None => {
-                                        // For local development, allow requests without Host header
-                                        // or provide a default host
-                                        println!("Warning: No Host header found, using default localhost");
-                                        // Don't fail the request - HTTP/1.1 spec allows this for local servers
-                                    }
-                                }
-                            }
-                            
-                            self.keep_alive = request.connection_keep_alive();
-                            self.current_request = Some(request);
-                            return Ok(true);
-                        }
-                        Ok(None) => {
-                            // Need more data, continue reading
-                        }
-                        Err(e) => {
-                            // Parse error
-                            return Err(e);
-                        }
-                    }
+                    self.parse_requests(&temp_buf[..n])?;
                 }
                 Err(e) if e.kind() == ErrorKind::WouldBlock => {
                     // No more data available right now
@@ -302,36 +574,194 @@ impl Connection {
             }
         }
         
-        Ok(false)
+        Ok(!self.requests.is_empty() || self.rejected_status.is_some())
     }
     
-    /// Prepare and queue the HTTP response
-    pub fn send_response(&mut self) -> io::Result<()> {
-        let request = match &self.current_request {
-            Some(req) => req.clone(),
-            None => return Err(io::Error::new(ErrorKind::InvalidInput, "No request to respond to")),
-        };
+    /// Whether another request may be read: the connection stays open, no
+    /// request was rejected, and the pipeline is not full
+    fn accepts_requests(&self) -> bool {
+        let sending = usize::from(self.is_sending());
+        self.keep_alive
+            && self.rejected_status.is_none()
+            && !self.parser.expects_continue()
+            && self.requests.len() + self.responses.len() + sending < MAX_PIPELINED_REQUESTS
+    }
+    
+    /// Feed `data` to the parser and queue every request it completes
+    fn parse_requests(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut parsed = self.parser.parse(data);
         
-        // Generate response based on request
-        let mut response = self.generate_response(&request)?;
+        loop {
+            match parsed {
+                Ok(Some(request)) => {
+                    // Request parsing complete
+                    println!("Parsed request: {} {}", request.method.as_str(), request.path);
+                    
+                    // Anything sent after a request asking to close is ignored
+                    self.keep_alive = request.connection_keep_alive();
+                    self.requests.push_back(request);
+                    self.parser.reset();
+                }
+                Ok(None) => {
+                    // An expectation is answered once every earlier response is out
+                    let answered_all = self.requests.is_empty() && self.responses.is_empty() && !self.is_sending();
+                    if self.parser.expects_continue() && answered_all {
+                        self.answer_expectation();
+                    }
+                    
+                    // Need more data, continue reading
+                    return Ok(());
+                }
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    // Malformed or oversized request: answer it, then close
+                    let status = ParseError::status_of(&e);
+                    println!("Rejecting request with {}: {}", status, e);
+                    self.rejected_status = Some(status);
+                    self.keep_alive = false;
+                    return Ok(());
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+            
+            if !self.accepts_requests() {
+                return Ok(());
+            }
+            parsed = self.parser.parse(&[]);
+        }
+    }
+    
+    /// Answer the `Expect` field of a request whose headers are complete:
+    /// invite the body with `100 Continue`, or reject the request with its
+    /// final status before any of the body is read. The interim response
+    /// goes out through `handle_write` like any other.
+    fn answer_expectation(&mut self) {
+        self.parser.clear_expectation();
+        let request = self.parser.request().clone();
         
-        // Set connection header based on keep-alive preference
-        response.set_keep_alive(self.keep_alive);
+        match self.expectation_status(&request) {
+            Some(status) => {
+                println!("Rejecting {} {} before its body with {}", request.method.as_str(), request.path, status);
+                self.rejected_status = Some(status);
+                self.keep_alive = false;
+            }
+            None => {
+                println!("Sending 100 Continue for {} {}", request.method.as_str(), request.path);
+                self.write_buffer = CONTINUE_RESPONSE.to_vec();
+                self.write_pos = 0;
+            }
+        }
+    }
+    
+    /// Final status for a request whose body is not worth receiving: an
+    /// unknown expectation, a method the route does not serve, or a
+    /// `Content-Length` over the body size limit
+    fn expectation_status(&mut self, request: &HttpRequest) -> Option<u16> {
+        let expect = request.get_header("expect").unwrap_or("");
+        if !expect.eq_ignore_ascii_case("100-continue") {
+            return Some(417);
+        }
         
-        // Convert to bytes and queue for sending
-        self.write_buffer = response.to_bytes();
-        self.write_pos = 0;
+        self.select_virtual_host(request);
+        let route = self.router.match_route(request);
+        if !request.method.is_standard() {
+            return Some(501);
+        }
+        if request.method != Method::OPTIONS && !route.allows_method(&request.method) {
+            return Some(405);
+        }
+        
+        if request.content_length().is_some_and(|length| length > self.parser.max_body_size()) {
+            return Some(413);
+        }
+        None
+    }
+    
+    /// Number of responses the next `send_response` call will queue
+    pub fn pending_responses(&self) -> usize {
+        self.requests.len() + self.rejected_status.is_some() as usize
+    }
+    
+    /// Prepare and queue the responses to every parsed request, in order
+    pub fn send_response(&mut self) -> io::Result<()> {
+        if self.requests.is_empty() && self.rejected_status.is_none() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "No request to respond to"));
+        }
+        
+        while let Some(request) = self.requests.pop_front() {
+            self.requests_answered += 1;
+            
+            // Generate response based on request
+            let mut response = self.generate_response(&request)?;
+            
+            // Only the last response can announce that the connection closes
+            let last = self.requests.is_empty() && self.rejected_status.is_none();
+            response.set_keep_alive((self.keep_alive && !self.draining) || !last);
+            
+            // Compress textual bodies the client accepts an encoding for
+            let accept_encoding = request.headers.get_combined("accept-encoding");
+            response.compress(accept_encoding.as_deref(), self.static_server.mime_types())?;
+            
+            // HEAD responses carry the headers of the GET response only
+            if matches!(request.method, Method::HEAD) {
+                response.body.clear();
+                response.body_stream.clear();
+            }
+            
+            self.queue_response(response);
+        }
+        
+        if let Some(status) = self.rejected_status.take() {
+            let request = self.parser.request().clone();
+            self.select_virtual_host(&request);
+            
+            let mut response = self.router.error_response(status, &request)?;
+            if status == 405 {
+                let route = self.router.match_route(&request);
+                response.set_header("Allow", &Method::allow_header(&route.answered_methods()));
+            }
+            response.set_keep_alive(false);
+            self.queue_response(response);
+        }
         
         Ok(())
     }
     
+    /// Queue the head and in-memory body of a response; its body stream is
+    /// sent after it. It goes out straight away unless another is being sent.
+    fn queue_response(&mut self, mut response: HttpResponse) {
+        let queued = QueuedResponse {
+            head: response.to_bytes(),
+            body_stream: response.body_stream.drain(..).collect(),
+        };
+        
+        if self.is_sending() {
+            self.responses.push_back(queued);
+        } else {
+            self.start_response(queued);
+        }
+    }
+    
+    fn start_response(&mut self, response: QueuedResponse) {
+        self.write_buffer = response.head;
+        self.write_pos = 0;
+        self.file_body = None;
+        self.body_stream = response.body_stream;
+    }
+    
+    /// Whether part of a response is still waiting to be written
+    pub fn is_sending(&self) -> bool {
+        self.write_pos < self.write_buffer.len()
+            || self.file_body.as_ref().is_some_and(|f| f.length > 0)
+            || !self.body_stream.is_empty()
+    }
+    
     fn generate_response(&mut self, request: &HttpRequest) -> io::Result<HttpResponse> {
+        self.select_virtual_host(request);
+        
         // Parse cookies and handle session middleware
-        let cookies = if let Some(cookie_header) = request.get_header("Cookie") {
-            CookieJar::parse_cookie_header(cookie_header)
-        } else {
-            CookieJar::new()
-        };
+        let cookies = CookieJar::from_headers(&request.headers);
         
         // Check for session and update activity
         if let Some(session) = self.session_store.get_session_from_cookies(&cookies) {
@@ -340,7 +770,7 @@ impl Connection {
         }
         
         // Route request based on method and path
-        let response = match request.method {
+        let mut response = match request.method {
             Method::GET | Method::HEAD => {
                 self.handle_get_request(request)
             }
@@ -351,53 +781,42 @@ impl Connection {
                 self.handle_delete_request(request)
             }
             _ => {
-                // Other methods not supported - return 405
-                let mut response = HttpResponse::new(405);
-                response.set_body_string("405 Method Not Allowed");
-                response.set_header("Content-Type", "text/plain");
-                response.set_header("Allow", "GET, HEAD, POST, DELETE");
-                Ok(response)
+                // PUT and OPTIONS are served by the router, which answers
+                // 405 or 501 for methods it has no handler for
+                self.router.route_request(request)
             }
         }?;
         
+        self.router.apply_cors_headers(request, &mut response);
         Ok(response)
     }
     
     fn handle_get_request(&mut self, request: &HttpRequest) -> io::Result<HttpResponse> {
-        let path = request.path();
-        
-        // Check if this is a session endpoint
-        if path.starts_with("/session/") {
-            return self.router.route_request(request);
-        }
-        
-        // Check if this is an uploads endpoint - route through router
-        if path.starts_with("/uploads/") {
-            return self.router.route_request(request);
-        }
-        
-        // Check if path is a CGI script
-        let document_root = self.vhost_config.as_ref()
-            .map(|c| c.document_root.as_path())
-            .unwrap_or_else(|| Path::new("./www"));
-        
-        let file_path = document_root.join(path.trim_start_matches('/'));
+        let route = self.router.match_route(request);
         
-        // Try router first for CGI and special routes
-        if file_path.exists() && self.is_cgi_path(&file_path) {
+        // Only plain static routes are served here; redirects, CGI, session
+        // and upload routes, and disallowed methods go to the router
+        if route.redirect_target().is_some()
+            || route.is_cgi()
+            || route.serves_sessions()
+            || route.accepts_uploads()
+            || !route.allows_method(&request.method)
+        {
             return self.router.route_request(request);
         }
         
-        // Serve static files
-        match self.static_server.serve_file(path) {
-            Ok(mut response) => {
-                // For HEAD requests, remove the body but keep headers
-                if matches!(request.method, Method::HEAD) {
-                    response.body.clear();
-                    response.set_header("Content-Length", "0");
+        // Serve static files with the route's index files and listing settings;
+        // HEAD gets the same headers, send_response drops the body
+        match self.static_server.serve_request(request, route.index_files(), route.directory_listing_enabled()) {
+            Ok(response) if response.status_code >= 400 => {
+                // Use the route or virtual host error page
+                let mut error = self.router.error_response(response.status_code, request)?;
+                if let Some(content_range) = response.headers.get("Content-Range") {
+                    error.set_header("Content-Range", content_range);
                 }
-                Ok(response)
+                Ok(error)
             }
+            Ok(response) => Ok(response),
             Err(_) => {
                 // Try router as fallback (might have custom error pages)
                 self.router.route_request(request)
@@ -417,65 +836,156 @@ impl Connection {
         self.router.route_request(request)
     }
     
-    fn is_cgi_path(&self, path: &Path) -> bool {
-        // Check if path is in cgi-bin directory or has CGI extension
-        if let Some(parent) = path.parent() {
-            if parent.ends_with("cgi-bin") {
-                return true;
-            }
-        }
-        
-        // Check for CGI extensions
-        if let Some(ext) = path.extension() {
-            if let Some(ext_str) = ext.to_str() {
-                return matches!(ext_str, "py" | "pl" | "sh" | "rb" | "php" | "cgi");
-            }
-        }
-        
-        false
-    }
-    
-    /// Handle write event. Returns Ok(true) if all data sent, Ok(false) if more data to send
+    /// Handle write event. Returns Ok(true) once the whole response has been
+    /// sent, Ok(false) if the socket would block with more data to send
     pub fn handle_write(&mut self) -> io::Result<bool> {
-        while self.write_pos < self.write_buffer.len() {
-            match self.stream.write(&self.write_buffer[self.write_pos..]) {
-                Ok(0) => {
-                    // Connection closed by peer
-                    return Err(io::Error::new(ErrorKind::WriteZero, "Write zero bytes"));
-                }
-                Ok(n) => {
-                    self.write_pos += n;
+        loop {
+            // Status line, headers and any in-memory body go first
+            while self.write_pos < self.write_buffer.len() {
+                match self.stream.write(&self.write_buffer[self.write_pos..]) {
+                    Ok(0) => {
+                        // Connection closed by peer
+                        return Err(io::Error::new(ErrorKind::WriteZero, "Write zero bytes"));
+                    }
+                    Ok(n) => {
+                        self.write_pos += n;
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        // Can't write more right now
+                        return Ok(false);
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        return Err(e);
+                    }
                 }
-                Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                    // Can't write more right now
-                    return Ok(false);
+            }
+            
+            if self.file_body.as_ref().is_none_or(|f| f.length == 0) {
+                // Move on to the next piece of the body stream
+                self.file_body = None;
+                match self.body_stream.pop_front() {
+                    Some(BodyChunk::Bytes(bytes)) => {
+                        self.write_buffer = bytes;
+                        self.write_pos = 0;
+                    }
+                    Some(BodyChunk::File(file_body)) => self.file_body = Some(file_body),
+                    None => match self.responses.pop_front() {
+                        // The response to the next pipelined request follows directly
+                        Some(next) => self.start_response(next),
+                        None => break,
+                    },
                 }
-                Err(e) => {
-                    return Err(e);
+                continue;
+            }
+            
+            match self.send_file_chunk() {
+                Ok(0) => {
+                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "File truncated while sending"));
                 }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
             }
         }
         
-        // All data sent - check if we should keep the connection alive
+        // All responses sent - reset for the next request on keep-alive connections
+        self.file_body = None;
         if self.keep_alive {
-            // Reset for next request
             self.reset_for_next_request();
-            Ok(false) // Don't close connection, wait for next request
-        } else {
-            Ok(true) // Close connection
         }
+        Ok(true)
     }
     
+    /// Send the next piece of the file body with sendfile(2), without
+    /// copying it through user space
+    #[cfg(target_os = "linux")]
+    fn send_file_chunk(&mut self) -> io::Result<usize> {
+        use std::os::unix::io::AsRawFd;
+        
+        let file_body = match self.file_body.as_mut() {
+            Some(file_body) => file_body,
+            None => return Ok(0),
+        };
+        
+        let mut offset = file_body.offset as libc::off_t;
+        let count = file_body.length.min(FILE_CHUNK_SIZE * 16) as usize;
+        let sent = unsafe {
+            libc::sendfile(self.stream.as_raw_fd(), file_body.file.as_raw_fd(), &mut offset, count)
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        
+        file_body.offset += sent as u64;
+        file_body.length -= sent as u64;
+        Ok(sent as usize)
+    }
+    
+    /// Read the next piece of the file body into the (reused) write buffer
+    #[cfg(not(target_os = "linux"))]
+    fn send_file_chunk(&mut self) -> io::Result<usize> {
+        use std::os::unix::fs::FileExt;
+        
+        let file_body = match self.file_body.as_mut() {
+            Some(file_body) => file_body,
+            None => return Ok(0),
+        };
+        
+        let count = file_body.length.min(FILE_CHUNK_SIZE) as usize;
+        self.write_buffer.resize(count, 0);
+        let read = file_body.file.read_at(&mut self.write_buffer, file_body.offset)?;
+        self.write_buffer.truncate(read);
+        self.write_pos = 0;
+        
+        file_body.offset += read as u64;
+        file_body.length -= read as u64;
+        Ok(read)
+    }
+    
+    /// Clear the write state once every response has been sent. The parser
+    /// was already reset as each request completed and keeps any bytes of
+    /// the requests that follow.
     fn reset_for_next_request(&mut self) {
-        self.parser.reset();
         self.write_buffer.clear();
         self.write_pos = 0;
-        self.current_request = None;
+        self.file_body = None;
+        self.body_stream.clear();
         // keep_alive stays the same for the connection
     }
     
     pub fn should_keep_alive(&self) -> bool {
-        self.keep_alive
+        self.keep_alive && !self.draining
+    }
+    
+    /// Finish the request in progress, if any, and announce
+    /// `Connection: close` on its response
+    pub fn begin_draining(&mut self) {
+        self.draining = true;
+    }
+    
+    /// Whether a keep-alive connection is waiting for its next request.
+    /// A new connection whose first request has not arrived yet is not.
+    pub fn awaits_next_request(&self) -> bool {
+        self.requests_answered > 0 && self.is_idle()
+    }
+    
+    /// Whether the connection is between requests, with nothing read
+    /// or waiting to be sent
+    pub fn is_idle(&self) -> bool {
+        self.parser.is_idle()
+            && self.requests.is_empty()
+            && self.rejected_status.is_none()
+            && self.responses.is_empty()
+            && !self.is_sending()
+    }
+    
+    /// Whether bytes of further requests were read along with the last
+    /// ones, or a request still waits for its expectation to be answered;
+    /// edge-triggered readiness will not report either again
+    pub fn has_buffered_input(&self) -> bool {
+        self.parser.has_buffered_data() || self.parser.expects_continue()
     }
     
     /// Check if the connection is currently reading request body
@@ -483,3 +993,239 @@ impl Connection {
         self.parser.is_reading_body()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::net::TcpListener;
+    use std::os::unix::io::AsRawFd;
+    use std::sync::Arc;
+    use tempfile::NamedTempFile;
+    
+    fn set_buffer_size(fd: i32, option: i32, size: i32) {
+        unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                option,
+                &size as *const i32 as *const libc::c_void,
+                std::mem::size_of::<i32>() as libc::socklen_t,
+            );
+        }
+    }
+    
+    #[test]
+    fn test_route_types_convert_to_routes() {
+        use crate::config::server::RouteConfig as ConfigRoute;
+        let convert = |path: &str, route_type| Route::new(Connection::convert_route_config(&ConfigRoute {
+            path: path.to_string(),
+            route_type,
+            ..Default::default()
+        }));
+        
+        let session = convert("/session/*", RouteType::Session);
+        assert!(session.serves_sessions());
+        assert!(!session.is_cgi());
+        
+        // Proxying is not implemented, so the route must not serve files
+        let proxy = convert("/api/*", RouteType::Proxy {
+            backend: "http://127.0.0.1:9000".to_string(),
+            timeout: std::time::Duration::from_secs(30),
+        });
+        assert!(!proxy.allows_method(&Method::GET));
+        assert!(!proxy.allows_method(&Method::POST));
+    }
+    
+    #[test]
+    fn test_file_body_streams_with_backpressure() {
+        let content: Vec<u8> = (0..2 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(&content).unwrap();
+        
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, addr) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        client.set_nonblocking(true).unwrap();
+        set_buffer_size(stream.as_raw_fd(), libc::SO_SNDBUF, 16 * 1024);
+        set_buffer_size(client.as_raw_fd(), libc::SO_RCVBUF, 16 * 1024);
+        
+        let mut conn = Connection::new(stream, addr).unwrap();
+        let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", content.len());
+        conn.write_buffer = head.as_bytes().to_vec();
+        conn.file_body = Some(FileBody {
+            file: Arc::new(File::open(temp.path()).unwrap()),
+            offset: 0,
+            length: content.len() as u64,
+        });
+        
+        let mut received = Vec::new();
+        let mut buf = [0u8; 64 * 1024];
+        let mut would_block = 0;
+        
+        while !conn.handle_write().unwrap() {
+            would_block += 1;
+            // Memory held by the connection stays bounded while the client catches up
+            assert!(conn.write_buffer.len() <= FILE_CHUNK_SIZE as usize + head.len());
+            while let Ok(n) = client.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+        }
+        
+        client.set_nonblocking(false).unwrap();
+        while received.len() < head.len() + content.len() {
+            let n = client.read(&mut buf).unwrap();
+            received.extend_from_slice(&buf[..n]);
+        }
+        
+        assert!(would_block > 0);
+        assert_eq!(&received[..head.len()], head.as_bytes());
+        assert!(received[head.len()..] == content[..]);
+    }
+    
+    /// A connection accepted from a local listener, with the client end
+    fn connected_pair() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+        let (stream, addr) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        (Connection::new(stream, addr).unwrap(), client)
+    }
+    
+    /// Wait for what the client sent to arrive, then let the connection read it
+    fn read_from_client(conn: &mut Connection) -> bool {
+        let mut readable = libc::pollfd { fd: conn.stream.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        assert_eq!(unsafe { libc::poll(&mut readable, 1, 5000) }, 1, "nothing arrived from the client");
+        conn.handle_read().unwrap()
+    }
+    
+    /// Close the connection and return everything it sent
+    fn read_until_closed(conn: Connection, client: &mut TcpStream) -> String {
+        drop(conn);
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        received
+    }
+    
+    #[test]
+    fn test_pipelined_responses_are_sent_in_order() {
+        let (mut conn, mut client) = connected_pair();
+        client.write_all(b"OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\nGET /missing HTTP/1.1\r\nHost: a\r\n\r\nGET / HTTP/1.1\r\n\r\n").unwrap();
+        
+        assert!(read_from_client(&mut conn));
+        assert_eq!(conn.requests.len(), 2);
+        conn.send_response().unwrap();
+        assert!(conn.handle_write().unwrap());
+        assert!(!conn.should_keep_alive());
+        
+        let received = read_until_closed(conn, &mut client);
+        let statuses: Vec<&str> = received.match_indices("HTTP/1.1 ").map(|(i, _)| &received[i + 9..i + 12]).collect();
+        assert_eq!(statuses, vec!["204", "404", "400"]);
+    }
+    
+    #[test]
+    fn test_pipeline_depth_is_limited() {
+        let (mut conn, mut client) = connected_pair();
+        let request = "GET /missing HTTP/1.1\r\nHost: a\r\n\r\n";
+        client.write_all(request.repeat(MAX_PIPELINED_REQUESTS + 4).as_bytes()).unwrap();
+        
+        assert!(read_from_client(&mut conn));
+        assert_eq!(conn.requests.len(), MAX_PIPELINED_REQUESTS);
+        assert!(!conn.accepts_requests());
+        
+        // The rest are parsed once the queued responses have been written
+        conn.send_response().unwrap();
+        client.set_nonblocking(true).unwrap();
+        let mut buf = [0u8; 64 * 1024];
+        while !conn.handle_write().unwrap() {
+            while client.read(&mut buf).is_ok_and(|n| n > 0) {}
+        }
+        assert!(conn.has_buffered_input());
+        assert!(conn.handle_read().unwrap());
+        assert_eq!(conn.requests.len(), 4);
+    }
+    
+    #[test]
+    fn test_expect_continue_invites_the_body() {
+        let (mut conn, mut client) = connected_pair();
+        client.write_all(b"PUT /uploads/a.txt HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n").unwrap();
+        
+        assert!(!read_from_client(&mut conn));
+        assert!(conn.is_sending());
+        assert!(conn.handle_write().unwrap());
+        assert!(conn.is_reading_body());
+        let mut interim = [0u8; CONTINUE_RESPONSE.len()];
+        client.read_exact(&mut interim).unwrap();
+        assert_eq!(&interim[..], b"HTTP/1.1 100 Continue\r\n\r\n");
+        
+        client.write_all(b"hello").unwrap();
+        assert!(read_from_client(&mut conn));
+        assert_eq!(conn.requests[0].body, b"hello");
+    }
+    
+    #[test]
+    fn test_expect_continue_rejections() {
+        for (head, status) in [
+            ("POST /uploads/a.txt HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n", "405"),
+            ("PUT /uploads/a.txt HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 999999999\r\n\r\n", "413"),
+            ("PUT /uploads/a.txt HTTP/1.1\r\nHost: a\r\nExpect: 200-ok\r\nContent-Length: 5\r\n\r\n", "417"),
+        ] {
+            let (mut conn, mut client) = connected_pair();
+            client.write_all(head.as_bytes()).unwrap();
+            
+            // The final status is sent without waiting for the body
+            assert!(read_from_client(&mut conn));
+            conn.send_response().unwrap();
+            assert!(conn.handle_write().unwrap());
+            assert!(!conn.should_keep_alive());
+            
+            let received = read_until_closed(conn, &mut client);
+            assert!(received.starts_with(&format!("HTTP/1.1 {}", status)), "{}", received);
+            assert!(!received.contains("100 Continue"));
+            assert_eq!(status == "405", received.contains("\r\nAllow: "));
+        }
+    }
+    
+    #[test]
+    fn test_config_update_waits_for_request_in_progress() {
+        let (mut conn, mut client) = connected_pair();
+        client.write_all(b"GET /missing HTTP/1.1\r\n").unwrap();
+        assert!(!read_from_client(&mut conn));
+        
+        // Lenient parsing would accept a request without Host
+        let security = SecurityConfig { strict_parsing: false, ..SecurityConfig::default() };
+        conn.update_config(ConfigUpdate {
+            virtual_hosts: Arc::default(),
+            default_host: None,
+            uploads: UploadConfig::default(),
+            security,
+        });
+        assert!(conn.pending_config.is_some());
+        
+        // The request in progress is still parsed strictly
+        client.write_all(b"\r\n").unwrap();
+        assert!(read_from_client(&mut conn));
+        conn.send_response().unwrap();
+        assert!(conn.handle_write().unwrap());
+        assert!(read_until_closed(conn, &mut client).starts_with("HTTP/1.1 400"));
+        
+        let (mut conn, mut client) = connected_pair();
+        conn.update_config(ConfigUpdate {
+            virtual_hosts: Arc::default(),
+            default_host: None,
+            uploads: UploadConfig::default(),
+            security: SecurityConfig { strict_parsing: false, ..SecurityConfig::default() },
+        });
+        assert!(conn.pending_config.is_none());
+        client.write_all(b"GET /missing HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_from_client(&mut conn));
+        conn.send_response().unwrap();
+        assert!(conn.handle_write().unwrap());
+        assert!(read_until_closed(conn, &mut client).starts_with("HTTP/1.1 404"));
+    }
+}
@@ -1,26 +1,63 @@
 use std::collections::HashMap;
+use std::fs::File;
 use std::io::{self, ErrorKind};
-use std::net::TcpListener;
+use std::net::{SocketAddr, TcpListener};
 use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use libc::{self, c_int};
-use crate::net::conn::Connection;
+use crate::http::date;
+use crate::net::conn::{ConfigUpdate, Connection};
+use crate::net::multi_server::ListenerInfo;
 use crate::net::timeout::{TimeoutManager, TimeoutConfig, ConnectionState};
-use crate::config::server::VirtualHostConfig;
+use crate::net::reload::{same_address, SharedConfig, Snapshot};
+use crate::net::signals::{signal_name, SignalSet};
+use crate::net::worker::WorkerStats;
+use crate::config::server::{ServerConfig, ListenerConfig, SecurityConfig, UploadConfig, VirtualHostConfig};
 use crate::session::{SessionStore, SessionConfig};
 
 const MAX_EVENTS: usize = 1024;
 const TIMEOUT_MS: c_int = 1000;
 
+/// A bound listening socket together with the configuration it came from
+struct BoundListener {
+    socket: TcpListener,
+    info: ListenerInfo,
+}
+
 pub struct EventLoop {
-    listener: TcpListener,
+    listeners: HashMap<RawFd, BoundListener>,
     #[cfg(target_os = "macos")]
     kqueue_fd: RawFd,
     #[cfg(target_os = "linux")]
     epoll_fd: RawFd,
     connections: HashMap<RawFd, Connection>,
     timeout_manager: TimeoutManager,
-    vhost_config: Option<VirtualHostConfig>,
+    virtual_hosts: Arc<HashMap<String, VirtualHostConfig>>,
+    default_host: Option<String>,
     session_store: SessionStore,
+    /// Global `[upload]` settings handed to every connection
+    uploads: UploadConfig,
+    /// Global `[security]` settings applied to every connection
+    security: SecurityConfig,
+    /// Counters of the worker running this loop
+    stats: Arc<WorkerStats>,
+    /// Set to make the loop drain its connections and return
+    shutdown: Arc<AtomicBool>,
+    /// Signals this loop watches, shared with the other workers
+    signals: Option<Arc<SignalSet>>,
+    /// How long in-flight requests may take once shutdown starts
+    shutdown_timeout: Duration,
+    /// When draining gives up on the remaining connections
+    drain_deadline: Option<Instant>,
+    /// Configuration shared with the other workers and replaced on reload
+    shared_config: Option<Arc<SharedConfig>>,
+    /// Generation of the snapshot this loop currently serves
+    config_generation: u64,
+    /// Spare descriptor given up to turn a connection away when the
+    /// process has run out of descriptors
+    reserve_fd: Option<File>,
 }
 
 impl EventLoop {
@@ -33,44 +70,284 @@ impl EventLoop {
         vhost_config: Option<VirtualHostConfig>,
         session_store: Option<SessionStore>,
     ) -> io::Result<Self> {
-        // Create and bind listener socket
-        let listener = TcpListener::bind(addr)?;
-        listener.set_nonblocking(true)?;
+        let (address, port) = addr.rsplit_once(':')
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Listener address must be host:port"))?;
+        let port = port.parse::<u16>()
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Invalid listener port"))?;
+        
+        let mut virtual_hosts = HashMap::new();
+        let default_host = vhost_config.as_ref().map(|v| v.server_name.clone());
+        if let Some(vhost) = vhost_config {
+            virtual_hosts.insert(vhost.server_name.clone(), vhost);
+        }
+        
+        let mut event_loop = Self::with_virtual_hosts(Arc::new(virtual_hosts), default_host, session_store)?;
+        event_loop.add_listener(&ListenerConfig {
+            address: address.to_string(),
+            port,
+            default: true,
+            name: None,
+        })?;
+        Ok(event_loop)
+    }
+    
+    /// Create an event loop serving every virtual host in `config`.
+    /// Listeners are bound separately with `add_listener`.
+    pub fn new_for_server(config: &ServerConfig, session_store: Option<SessionStore>) -> io::Result<Self> {
+        let virtual_hosts = config.virtual_hosts.iter()
+            .map(|vhost| (vhost.server_name.clone(), vhost.clone()))
+            .collect();
         
+        let mut event_loop = Self::with_virtual_hosts(Arc::new(virtual_hosts), config.default_host.clone(), session_store)?;
+        event_loop.uploads = config.global.uploads.clone();
+        event_loop.security = config.global.security.clone();
+        event_loop.shutdown_timeout = config.global.timeouts.shutdown;
+        Ok(event_loop)
+    }
+    
+    fn with_virtual_hosts(
+        virtual_hosts: Arc<HashMap<String, VirtualHostConfig>>,
+        default_host: Option<String>,
+        session_store: Option<SessionStore>,
+    ) -> io::Result<Self> {
         #[cfg(target_os = "macos")]
-        let event_fd = Self::create_kqueue(&listener)?;
+        let event_fd = Self::create_kqueue()?;
         
         #[cfg(target_os = "linux")]
-        let event_fd = Self::create_epoll(&listener)?;
+        let event_fd = Self::create_epoll()?;
         
         let session_store = session_store.unwrap_or_else(|| {
             SessionStore::new(SessionConfig::default())
         });
         
         Ok(EventLoop {
-            listener,
+            listeners: HashMap::new(),
             #[cfg(target_os = "macos")]
             kqueue_fd: event_fd,
             #[cfg(target_os = "linux")]
             epoll_fd: event_fd,
             connections: HashMap::new(),
             timeout_manager: TimeoutManager::new(TimeoutConfig::default()),
-            vhost_config,
+            virtual_hosts,
+            default_host,
             session_store,
+            uploads: UploadConfig::default(),
+            security: SecurityConfig::default(),
+            stats: Arc::new(WorkerStats::default()),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            signals: None,
+            shutdown_timeout: Duration::from_secs(30),
+            drain_deadline: None,
+            shared_config: None,
+            config_generation: 0,
+            reserve_fd: File::open("/dev/null").ok(),
         })
     }
     
+    /// Bind a listening socket and register it with the event loop.
+    /// Returns the address the socket is actually bound to.
+    pub fn add_listener(&mut self, config: &ListenerConfig) -> io::Result<SocketAddr> {
+        let socket = TcpListener::bind((config.address.as_str(), config.port))?;
+        self.add_bound_listener(socket, config)
+    }
+    
+    /// Register a socket that is already bound and listening, such as one
+    /// shared with the other workers
+    pub fn add_bound_listener(&mut self, socket: TcpListener, config: &ListenerConfig) -> io::Result<SocketAddr> {
+        socket.set_nonblocking(true)?;
+        
+        let addr = socket.local_addr()?;
+        let fd = socket.as_raw_fd();
+        self.register_listener(fd)?;
+        
+        self.listeners.insert(fd, BoundListener {
+            socket,
+            info: ListenerInfo {
+                config: config.clone(),
+                addr,
+                is_default: config.default,
+            },
+        });
+        
+        Ok(addr)
+    }
+    
+    /// Number of listening sockets registered with this loop
+    pub fn listener_count(&self) -> usize {
+        self.listeners.len()
+    }
+    
+    /// Run this loop as a worker: record into `stats` and return once
+    /// `shutdown` is set
+    pub fn attach_worker(&mut self, stats: Arc<WorkerStats>, shutdown: Arc<AtomicBool>) {
+        self.stats = stats;
+        self.shutdown = shutdown;
+    }
+    
+    /// Serve the listeners and settings of the shared snapshot, switching
+    /// to a new one whenever a reload publishes it
+    pub fn follow_config(&mut self, shared: Arc<SharedConfig>) -> io::Result<()> {
+        let snapshot = shared.current();
+        self.sync_listeners(&snapshot)?;
+        self.config_generation = snapshot.generation;
+        self.shared_config = Some(shared);
+        Ok(())
+    }
+    
+    /// Register the snapshot's listeners this loop lacks and drop the ones
+    /// it no longer lists
+    fn sync_listeners(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let removed: Vec<RawFd> = self.listeners.iter()
+            .filter(|(_, bound)| !snapshot.listeners.iter().any(|(_, config)| same_address(config, &bound.info.config)))
+            .map(|(&fd, _)| fd)
+            .collect();
+        for fd in removed {
+            #[cfg(target_os = "macos")]
+            self.remove_from_kqueue(fd);
+            
+            #[cfg(target_os = "linux")]
+            self.remove_from_epoll(fd);
+            
+            self.listeners.remove(&fd);
+        }
+        
+        for (socket, config) in &snapshot.listeners {
+            let existing = self.listeners.values_mut().find(|bound| same_address(&bound.info.config, config));
+            match existing {
+                Some(bound) => {
+                    bound.info.is_default = config.default;
+                    bound.info.config = config.clone();
+                }
+                None => {
+                    self.add_bound_listener(socket.try_clone()?, config)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Switch to a newly published configuration. Connections apply it
+    /// from their next request on.
+    fn check_config(&mut self) {
+        let shared = match &self.shared_config {
+            Some(shared) if shared.generation() != self.config_generation => Arc::clone(shared),
+            _ => return,
+        };
+        
+        let snapshot = shared.current();
+        self.config_generation = snapshot.generation;
+        let config = &snapshot.config;
+        
+        self.virtual_hosts = Arc::new(config.virtual_hosts.iter()
+            .map(|vhost| (vhost.server_name.clone(), vhost.clone()))
+            .collect());
+        self.default_host = config.default_host.clone();
+        self.uploads = config.global.uploads.clone();
+        self.security = config.global.security.clone();
+        self.shutdown_timeout = config.global.timeouts.shutdown;
+        
+        if let Err(e) = self.sync_listeners(&snapshot) {
+            eprintln!("Failed to update listeners: {}", e);
+        }
+        
+        for conn in self.connections.values_mut() {
+            conn.update_config(ConfigUpdate {
+                virtual_hosts: Arc::clone(&self.virtual_hosts),
+                default_host: self.default_host.clone(),
+                uploads: self.uploads.clone(),
+                security: self.security.clone(),
+            });
+        }
+        println!("Configuration generation {} applied, {} listener(s)", snapshot.generation, self.listeners.len());
+    }
+    
+    /// Watch `signals`: SIGTERM and SIGINT start a graceful shutdown,
+    /// SIGHUP asks for the configuration to be reloaded and SIGUSR2 for a
+    /// new binary to take over
+    #[cfg(target_os = "linux")]
+    pub fn watch_signals(&mut self, signals: Arc<SignalSet>) -> io::Result<()> {
+        // Level-triggered: whichever loop reads a signal first takes it
+        let mut event = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: signals.fd() as u64,
+        };
+        
+        let result = unsafe {
+            libc::epoll_ctl(
+                self.epoll_fd,
+                libc::EPOLL_CTL_ADD,
+                signals.fd(),
+                &mut event as *mut libc::epoll_event,
+            )
+        };
+        
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        self.signals = Some(signals);
+        Ok(())
+    }
+    
+    /// Watch `signals`: SIGTERM and SIGINT start a graceful shutdown,
+    /// SIGHUP asks for the configuration to be reloaded and SIGUSR2 for a
+    /// new binary to take over
+    #[cfg(target_os = "macos")]
+    pub fn watch_signals(&mut self, signals: Arc<SignalSet>) -> io::Result<()> {
+        for &signal in signals.signals() {
+            let mut kevent = libc::kevent {
+                ident: signal as libc::uintptr_t,
+                filter: libc::EVFILT_SIGNAL,
+                flags: libc::EV_ADD | libc::EV_ENABLE,
+                fflags: 0,
+                data: 0,
+                udata: std::ptr::null_mut(),
+            };
+            
+            let result = unsafe {
+                libc::kevent(
+                    self.kqueue_fd,
+                    &mut kevent as *mut libc::kevent,
+                    1,
+                    std::ptr::null_mut(),
+                    0,
+                    std::ptr::null(),
+                )
+            };
+            
+            if result == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        self.signals = Some(signals);
+        Ok(())
+    }
+    
     #[cfg(target_os = "macos")]
-    fn create_kqueue(listener: &TcpListener) -> io::Result<RawFd> {
+    fn create_kqueue() -> io::Result<RawFd> {
         // Create kqueue instance
         let kqueue_fd = unsafe { libc::kqueue() };
         if kqueue_fd == -1 {
             return Err(io::Error::last_os_error());
         }
         
-        // Add listener to kqueue
+        Ok(kqueue_fd)
+    }
+    
+    #[cfg(target_os = "linux")]
+    fn create_epoll() -> io::Result<RawFd> {
+        // Create epoll instance
+        let epoll_fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll_fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        
+        Ok(epoll_fd)
+    }
+    
+    #[cfg(target_os = "macos")]
+    fn register_listener(&mut self, fd: RawFd) -> io::Result<()> {
         let mut kevent = libc::kevent {
-            ident: listener.as_raw_fd() as libc::uintptr_t,
+            ident: fd as libc::uintptr_t,
             filter: libc::EVFILT_READ,
             flags: libc::EV_ADD | libc::EV_ENABLE,
             fflags: 0,
@@ -80,7 +357,7 @@ impl EventLoop {
         
         let result = unsafe {
             libc::kevent(
-                kqueue_fd,
+                self.kqueue_fd,
                 &mut kevent as *mut libc::kevent,
                 1,
                 std::ptr::null_mut(),
@@ -90,42 +367,32 @@ impl EventLoop {
         };
         
         if result == -1 {
-            unsafe { libc::close(kqueue_fd); }
             return Err(io::Error::last_os_error());
         }
-        
-        Ok(kqueue_fd)
+        Ok(())
     }
     
     #[cfg(target_os = "linux")]
-    fn create_epoll(listener: &TcpListener) -> io::Result<RawFd> {
-        // Create epoll instance
-        let epoll_fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
-        if epoll_fd == -1 {
-            return Err(io::Error::last_os_error());
-        }
-        
-        // Add listener to epoll
+    fn register_listener(&mut self, fd: RawFd) -> io::Result<()> {
+        // Workers share listeners; wake only one of them per connection
         let mut event = libc::epoll_event {
-            events: (libc::EPOLLIN | libc::EPOLLET) as u32,
-            u64: listener.as_raw_fd() as u64,
+            events: (libc::EPOLLIN | libc::EPOLLET | libc::EPOLLEXCLUSIVE) as u32,
+            u64: fd as u64,
         };
         
         let result = unsafe {
             libc::epoll_ctl(
-                epoll_fd,
+                self.epoll_fd,
                 libc::EPOLL_CTL_ADD,
-                listener.as_raw_fd(),
+                fd,
                 &mut event as *mut libc::epoll_event,
             )
         };
         
         if result == -1 {
-            unsafe { libc::close(epoll_fd); }
             return Err(io::Error::last_os_error());
         }
-        
-        Ok(epoll_fd)
+        Ok(())
     }
     
     pub fn event_loop(&mut self) -> io::Result<()> {
@@ -140,12 +407,22 @@ impl EventLoop {
     fn kqueue_event_loop(&mut self) -> io::Result<()> {
         let mut events: [libc::kevent; MAX_EVENTS] = unsafe { std::mem::zeroed() };
         
-        loop {
+        while !self.drained() {
+            // Pick up a reloaded configuration
+            if self.drain_deadline.is_none() {
+                self.check_config();
+            }
+            
+            // Tells the watchdog this loop still turns
+            self.stats.record_tick();
+            
             // Check for timed-out connections first
             self.handle_timeouts();
             
-            // Calculate timeout based on next timeout check
-            let timeout_duration = self.timeout_manager.next_timeout_check();
+            // Calculate timeout based on next timeout check, waking up
+            // often enough to notice a shutdown request
+            let timeout_duration = self.timeout_manager.next_timeout_check()
+                .min(std::time::Duration::from_millis(TIMEOUT_MS as u64));
             let timeout = libc::timespec {
                 tv_sec: timeout_duration.as_secs() as libc::time_t,
                 tv_nsec: (timeout_duration.subsec_nanos()) as libc::c_long,
@@ -170,31 +447,47 @@ impl EventLoop {
                 return Err(err);
             }
             
+            // Responses built during this wakeup share one formatted Date header
+            date::update_cached_date(SystemTime::now());
+            
             // Process events
-            for i in 0..nfds as usize {
-                let event = events[i];
+            for event in events.iter().take(nfds as usize) {
                 let fd = event.ident as RawFd;
                 
-                if fd == self.listener.as_raw_fd() {
-                    self.accept_connections()?;
+                if event.filter == libc::EVFILT_SIGNAL {
+                    self.handle_signal(event.ident as c_int);
+                } else if self.listeners.contains_key(&fd) {
+                    self.accept_connections(fd)?;
                 } else {
                     self.handle_kqueue_connection_event(fd, event.filter)?;
                 }
             }
         }
+        
+        self.close_all_connections();
+        Ok(())
     }
     
     #[cfg(target_os = "linux")]
     fn epoll_event_loop(&mut self) -> io::Result<()> {
         let mut events: [libc::epoll_event; MAX_EVENTS] = unsafe { std::mem::zeroed() };
         
-        loop {
+        while !self.drained() {
+            // Pick up a reloaded configuration
+            if self.drain_deadline.is_none() {
+                self.check_config();
+            }
+            
+            // Tells the watchdog this loop still turns
+            self.stats.record_tick();
+            
             // Check for timed-out connections first
             self.handle_timeouts();
             
-            // Calculate timeout based on next timeout check
+            // Calculate timeout based on next timeout check, waking up
+            // often enough to notice a shutdown request
             let timeout_duration = self.timeout_manager.next_timeout_check();
-            let timeout_ms = timeout_duration.as_millis().min(i32::MAX as u128) as c_int;
+            let timeout_ms = timeout_duration.as_millis().min(TIMEOUT_MS as u128) as c_int;
             
             // Wait for events
             let nfds = unsafe {
@@ -214,35 +507,150 @@ impl EventLoop {
                 return Err(err);
             }
             
+            // Responses built during this wakeup share one formatted Date header
+            date::update_cached_date(SystemTime::now());
+            
             // Process events
-            for i in 0..nfds as usize {
-                let event = events[i];
+            for event in events.iter().take(nfds as usize) {
                 let fd = event.u64 as RawFd;
                 
-                if fd == self.listener.as_raw_fd() {
-                    self.accept_connections()?;
+                if self.signals.as_ref().is_some_and(|signals| signals.fd() == fd) {
+                    self.read_signals();
+                } else if self.listeners.contains_key(&fd) {
+                    self.accept_connections(fd)?;
                 } else {
                     self.handle_epoll_connection_event(fd, event.events)?;
                 }
             }
         }
+        
+        self.close_all_connections();
+        Ok(())
     }
     
-    fn accept_connections(&mut self) -> io::Result<()> {
+    /// Take every pending signal off the shared signalfd
+    #[cfg(target_os = "linux")]
+    fn read_signals(&mut self) {
         loop {
-            match self.listener.accept() {
+            let signal = match self.signals.as_ref().map(|signals| signals.read()) {
+                Some(Ok(Some(signal))) => signal,
+                Some(Err(e)) => {
+                    eprintln!("Failed to read signal: {}", e);
+                    return;
+                }
+                _ => return,
+            };
+            self.handle_signal(signal);
+        }
+    }
+    
+    fn handle_signal(&mut self, signal: c_int) {
+        match signal {
+            libc::SIGTERM | libc::SIGINT => {
+                println!("Received {}, shutting down gracefully", signal_name(signal));
+                self.shutdown.store(true, Ordering::Relaxed);
+            }
+            libc::SIGHUP => match &self.shared_config {
+                Some(shared) => {
+                    println!("Received SIGHUP, reloading configuration");
+                    shared.request_reload();
+                }
+                None => println!("Ignoring SIGHUP, no shared configuration to reload"),
+            },
+            libc::SIGUSR2 => match &self.shared_config {
+                Some(shared) => {
+                    println!("Received SIGUSR2, starting a new binary");
+                    shared.request_upgrade();
+                }
+                None => println!("Ignoring SIGUSR2, no listeners to hand over"),
+            },
+            _ => println!("Ignoring {}", signal_name(signal)),
+        }
+    }
+    
+    /// Whether the loop should stop. Once shutdown is requested the
+    /// listeners close, and the loop runs until every connection has
+    /// finished its request or the drain deadline passes.
+    fn drained(&mut self) -> bool {
+        if !self.shutdown.load(Ordering::Relaxed) {
+            return false;
+        }
+        
+        let deadline = match self.drain_deadline {
+            Some(deadline) => deadline,
+            None => self.begin_drain(),
+        };
+        
+        if self.connections.is_empty() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            println!("Shutdown timeout passed, closing {} connection(s)", self.connections.len());
+            return true;
+        }
+        false
+    }
+    
+    /// Stop accepting connections and close the ones idling between
+    /// requests; the others, including just accepted ones, close after
+    /// their next response
+    fn begin_drain(&mut self) -> Instant {
+        let deadline = Instant::now() + self.shutdown_timeout;
+        self.drain_deadline = Some(deadline);
+        
+        let listener_fds: Vec<RawFd> = self.listeners.keys().copied().collect();
+        for fd in listener_fds {
+            #[cfg(target_os = "macos")]
+            self.remove_from_kqueue(fd);
+            
+            #[cfg(target_os = "linux")]
+            self.remove_from_epoll(fd);
+        }
+        self.listeners.clear();
+        
+        let mut idle = Vec::new();
+        for (&fd, conn) in self.connections.iter_mut() {
+            conn.begin_draining();
+            if conn.awaits_next_request() {
+                idle.push(fd);
+            }
+        }
+        for fd in idle {
+            if let Err(e) = self.close_connection(fd) {
+                eprintln!("Error closing idle connection {}: {}", fd, e);
+            }
+        }
+        
+        if !self.connections.is_empty() {
+            println!("Waiting up to {:?} for {} connection(s) to finish", self.shutdown_timeout, self.connections.len());
+        }
+        deadline
+    }
+    
+    fn accept_connections(&mut self, listener_fd: RawFd) -> io::Result<()> {
+        loop {
+            let (accepted, listener_addr) = match self.listeners.get(&listener_fd) {
+                Some(listener) => (listener.socket.accept(), listener.info.addr),
+                None => return Ok(()),
+            };
+            
+            match accepted {
                 Ok((stream, addr)) => {
-                    println!("New connection from: {}", addr);
+                    println!("New connection from: {} on {}", addr, listener_addr);
                     
                     // Set non-blocking
                     stream.set_nonblocking(true)?;
                     
                     let fd = stream.as_raw_fd();
-                    let conn = match Connection::new_with_config(
+                    self.stats.record_accept();
+                    let mut conn = match Connection::new_with_config(
                         stream,
                         addr,
-                        self.vhost_config.clone(),
+                        listener_addr,
+                        Arc::clone(&self.virtual_hosts),
+                        self.default_host.clone(),
                         Some(self.session_store.clone()),
+                        self.uploads.clone(),
                     ) {
                         Ok(c) => c,
                         Err(e) => {
@@ -250,6 +658,7 @@ impl EventLoop {
                             continue;
                         }
                     };
+                    conn.set_security(&self.security);
                     
                     // Add to event system
                     self.add_connection_to_events(fd)?;
@@ -263,6 +672,18 @@ impl EventLoop {
                     // No more connections to accept
                     break;
                 }
+                Err(e) if matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::ConnectionAborted) => {
+                    continue;
+                }
+                Err(e) if matches!(e.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE)) => {
+                    // The listener is edge-triggered, so the queue must still
+                    // be drained: free the spare descriptor to accept the
+                    // connection and close it straight away
+                    if !self.shed_connection(listener_fd) {
+                        eprintln!("Accept error: {}", e);
+                        break;
+                    }
+                }
                 Err(e) => {
                     eprintln!("Accept error: {}", e);
                     break;
@@ -272,6 +693,23 @@ impl EventLoop {
         Ok(())
     }
     
+    /// Accept one pending connection on the spare descriptor and close it.
+    /// Returns false when there is no spare descriptor to give up.
+    fn shed_connection(&mut self, listener_fd: RawFd) -> bool {
+        let Some(reserve) = self.reserve_fd.take() else {
+            return false;
+        };
+        drop(reserve);
+        
+        if let Some(listener) = self.listeners.get(&listener_fd) {
+            if let Ok((_, addr)) = listener.socket.accept() {
+                eprintln!("Out of file descriptors, closing connection from {}", addr);
+            }
+        }
+        self.reserve_fd = File::open("/dev/null").ok();
+        true
+    }
+    
     #[cfg(target_os = "macos")]
     fn add_connection_to_events(&mut self, fd: RawFd) -> io::Result<()> {
         let mut kevent = libc::kevent {
@@ -329,60 +767,33 @@ impl EventLoop {
         
         let should_close = if let Some(conn) = self.connections.get_mut(&fd) {
             if filter == libc::EVFILT_READ {
-                // Set reading state based on current parser state
-                let state = if conn.is_reading_body() {
-                    ConnectionState::ReadingBody
-                } else {
-                    ConnectionState::ReadingHeaders
-                };
-                self.timeout_manager.set_connection_state(fd, state);
-                
-                match conn.handle_read() {
-                    Ok(true) => {
-                        // Ready to write response
-                        println!("Request parsed, generating response...");
-                        self.timeout_manager.set_connection_state(fd, ConnectionState::Writing);
-                        
-                        match conn.send_response() {
-                            Ok(()) => {
-                                println!("Response generated successfully, enabling write events...");
-                                match self.enable_write_events_kqueue(fd) {
-                                    Ok(()) => {
-                                        println!("Write events enabled successfully");
-                                        false
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Failed to enable write events: {}", e);
-                                        true
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to generate response: {}", e);
-                                true
-                            }
-                        }
-                    }
-                    Ok(false) => false,
-                    Err(e) => {
-                        eprintln!("Error in handle_read: {}", e);
-                        true
-                    }
-                }
+                self.read_requests_kqueue(fd)
             } else if filter == libc::EVFILT_WRITE {
                 self.timeout_manager.set_connection_state(fd, ConnectionState::Writing);
                 
                 match conn.handle_write() {
-                    Ok(keep_alive) => {
-                        if keep_alive {
+                    // Only a 100 Continue went out; the request body follows
+                    Ok(true) if conn.is_reading_body() => {
+                        self.timeout_manager.set_connection_state(fd, ConnectionState::ReadingBody);
+                        false
+                    }
+                    Ok(true) => {
+                        if conn.should_keep_alive() {
+                            let pipelined = conn.has_buffered_input();
+                            
                             // Reset for next request on keep-alive connection
                             self.timeout_manager.reset_connection_for_new_request(fd);
-                            false
+                            
+                            // The next request may already have arrived with the last one
+                            pipelined && self.read_requests_kqueue(fd)
                         } else {
                             true
                         }
                     }
-                    Ok(false) => false,
+                    Ok(false) => {
+                        // Socket buffer full; the one-shot write filter has to be re-armed
+                        self.enable_write_events_kqueue(fd).is_err()
+                    }
                     Err(_) => true,
                 }
             } else {
@@ -405,45 +816,40 @@ impl EventLoop {
         
         let should_close = if let Some(conn) = self.connections.get_mut(&fd) {
             if events & libc::EPOLLIN as u32 != 0 {
-                // Set reading state based on current parser state
-                let state = if conn.is_reading_body() {
-                    ConnectionState::ReadingBody
-                } else {
-                    ConnectionState::ReadingHeaders
-                };
-                self.timeout_manager.set_connection_state(fd, state);
-                
-                match conn.handle_read() {
-                    Ok(true) => {
-                        // Ready to write response
-                        self.timeout_manager.set_connection_state(fd, ConnectionState::Writing);
-                        conn.send_response()?;
-                        self.enable_write_events_epoll(fd)?;
-                        false
-                    }
-                    Ok(false) => false,
-                    Err(_) => true,
-                }
+                self.read_requests_epoll(fd)
             } else if events & libc::EPOLLOUT as u32 != 0 {
                 self.timeout_manager.set_connection_state(fd, ConnectionState::Writing);
                 
                 match conn.handle_write() {
-                    Ok(keep_alive) => {
-                        if keep_alive {
+                    // Only a 100 Continue went out; the request body follows
+                    Ok(true) if conn.is_reading_body() => {
+                        self.timeout_manager.set_connection_state(fd, ConnectionState::ReadingBody);
+                        self.enable_read_events_epoll(fd).is_err()
+                    }
+                    Ok(true) => {
+                        if conn.should_keep_alive() {
+                            let pipelined = conn.has_buffered_input();
+                            
                             // Reset for next request on keep-alive connection
                             self.timeout_manager.reset_connection_for_new_request(fd);
-                            false
+                            if self.enable_read_events_epoll(fd).is_err() {
+                                true
+                            } else if pipelined {
+                                // The next request already arrived with the last one
+                                self.read_requests_epoll(fd)
+                            } else {
+                                false
+                            }
                         } else {
                             true
                         }
                     }
+                    // Socket buffer full; EPOLLOUT fires again once it drains
                     Ok(false) => false,
                     Err(_) => true,
                 }
-            } else if events & (libc::EPOLLHUP | libc::EPOLLERR) as u32 != 0 {
-                true
             } else {
-                false
+                events & (libc::EPOLLHUP | libc::EPOLLERR) as u32 != 0
             }
         } else {
             true
@@ -455,6 +861,87 @@ impl EventLoop {
         Ok(())
     }
     
+    /// Read whatever requests a connection has ready and queue their
+    /// responses. Returns whether the connection should be closed.
+    #[cfg(target_os = "macos")]
+    fn read_requests_kqueue(&mut self, fd: RawFd) -> bool {
+        let conn = match self.connections.get_mut(&fd) {
+            Some(conn) => conn,
+            None => return true,
+        };
+        
+        // Set reading state based on current parser state
+        let state = if conn.is_reading_body() {
+            ConnectionState::ReadingBody
+        } else {
+            ConnectionState::ReadingHeaders
+        };
+        self.timeout_manager.set_connection_state(fd, state);
+        
+        match conn.handle_read() {
+            Ok(true) => {
+                self.stats.record_requests(conn.pending_responses());
+                // Ready to write response
+                self.timeout_manager.set_connection_state(fd, ConnectionState::Writing);
+                match conn.send_response() {
+                    Ok(()) => match self.enable_write_events_kqueue(fd) {
+                        Ok(()) => false,
+                        Err(e) => {
+                            eprintln!("Failed to enable write events: {}", e);
+                            true
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Failed to generate response: {}", e);
+                        true
+                    }
+                }
+            }
+            // A 100 Continue may be waiting to go out
+            Ok(false) => conn.is_sending() && self.enable_write_events_kqueue(fd).is_err(),
+            Err(e) => {
+                eprintln!("Error in handle_read: {}", e);
+                true
+            }
+        }
+    }
+    
+    /// Read whatever requests a connection has ready and queue their
+    /// responses. Returns whether the connection should be closed.
+    #[cfg(target_os = "linux")]
+    fn read_requests_epoll(&mut self, fd: RawFd) -> bool {
+        let conn = match self.connections.get_mut(&fd) {
+            Some(conn) => conn,
+            None => return true,
+        };
+        
+        // Set reading state based on current parser state
+        let state = if conn.is_reading_body() {
+            ConnectionState::ReadingBody
+        } else {
+            ConnectionState::ReadingHeaders
+        };
+        self.timeout_manager.set_connection_state(fd, state);
+        
+        match conn.handle_read() {
+            Ok(true) => {
+                self.stats.record_requests(conn.pending_responses());
+                // Ready to write response
+                self.timeout_manager.set_connection_state(fd, ConnectionState::Writing);
+                match conn.send_response() {
+                    Ok(()) => self.enable_write_events_epoll(fd).is_err(),
+                    Err(e) => {
+                        eprintln!("Failed to generate response: {}", e);
+                        true
+                    }
+                }
+            }
+            // A 100 Continue may be waiting to go out
+            Ok(false) => conn.is_sending() && self.enable_write_events_epoll(fd).is_err(),
+            Err(_) => true,
+        }
+    }
+    
     #[cfg(target_os = "macos")]
     fn enable_write_events_kqueue(&mut self, fd: RawFd) -> io::Result<()> {
         let mut kevent = libc::kevent {
@@ -497,6 +984,29 @@ impl EventLoop {
         Ok(())
     }
     
+    /// Switch a connection back to waiting for the next request
+    #[cfg(target_os = "linux")]
+    fn enable_read_events_epoll(&mut self, fd: RawFd) -> io::Result<()> {
+        let mut event = libc::epoll_event {
+            events: (libc::EPOLLIN | libc::EPOLLET) as u32,
+            u64: fd as u64,
+        };
+        
+        let result = unsafe {
+            libc::epoll_ctl(
+                self.epoll_fd,
+                libc::EPOLL_CTL_MOD,
+                fd,
+                &mut event as *mut libc::epoll_event,
+            )
+        };
+        
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    
     fn handle_timeouts(&mut self) {
         let timed_out_fds = self.timeout_manager.check_timeouts();
         
@@ -519,12 +1029,23 @@ impl EventLoop {
         self.timeout_manager.remove_connection(fd);
         
         if let Some(conn) = self.connections.remove(&fd) {
-            println!("Closed connection from: {}", conn.addr());
+            self.stats.record_close();
+            println!("Closed connection from: {} on {}", conn.addr(), conn.listener_addr());
         }
         
         Ok(())
     }
     
+    /// Close every open connection, as when the loop stops
+    fn close_all_connections(&mut self) {
+        let fds: Vec<RawFd> = self.connections.keys().copied().collect();
+        for fd in fds {
+            if let Err(e) = self.close_connection(fd) {
+                eprintln!("Error closing connection {}: {}", fd, e);
+            }
+        }
+    }
+    
     #[cfg(target_os = "macos")]
     fn remove_from_kqueue(&mut self, fd: RawFd) {
         let mut kevents = [
@@ -579,3 +1100,193 @@ impl Drop for EventLoop {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn listener(port: u16, default: bool) -> ListenerConfig {
+        ListenerConfig {
+            address: "127.0.0.1".to_string(),
+            port,
+            default,
+            name: None,
+        }
+    }
+    
+    #[test]
+    fn test_single_loop_registers_all_listeners() {
+        let config = ServerConfig::default();
+        let mut event_loop = EventLoop::new_for_server(&config, None).unwrap();
+        
+        let first = event_loop.add_listener(&listener(0, true)).unwrap();
+        let second = event_loop.add_listener(&listener(0, false)).unwrap();
+        
+        assert_ne!(first.port(), second.port());
+        assert_eq!(event_loop.listener_count(), 2);
+        assert!(event_loop.virtual_hosts.contains_key("localhost"));
+    }
+    
+    #[test]
+    fn test_shutdown_drains_in_flight_requests() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::sync::mpsc;
+        
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(WorkerStats::default());
+        let (addr_tx, addr_rx) = mpsc::channel();
+        let (flag, worker_stats) = (Arc::clone(&shutdown), Arc::clone(&stats));
+        let server = std::thread::spawn(move || {
+            let mut event_loop = EventLoop::new_for_server(&ServerConfig::default(), None).unwrap();
+            addr_tx.send(event_loop.add_listener(&listener(0, true)).unwrap()).unwrap();
+            event_loop.attach_worker(worker_stats, flag);
+            event_loop.event_loop()
+        });
+        let addr = addr_rx.recv().unwrap();
+        
+        // One connection idles between requests, the other is mid-request
+        let mut idle = TcpStream::connect(addr).unwrap();
+        idle.write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut buf = [0u8; 4096];
+        assert!(idle.read(&mut buf).unwrap() > 0);
+        
+        let mut busy = TcpStream::connect(addr).unwrap();
+        busy.write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\n").unwrap();
+        let accepted_by = Instant::now() + Duration::from_secs(5);
+        while stats.accepted() < 2 {
+            assert!(Instant::now() < accepted_by, "busy connection was not accepted");
+            std::thread::yield_now();
+        }
+        
+        shutdown.store(true, Ordering::Relaxed);
+        idle.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(idle.read(&mut buf).unwrap(), 0);
+        
+        // The request in progress is still answered, announcing the close
+        busy.write_all(b"\r\n").unwrap();
+        let mut response = String::new();
+        busy.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        assert!(response.to_lowercase().contains("connection: close"), "{}", response);
+        
+        server.join().unwrap().unwrap();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+    
+    #[test]
+    fn test_shutdown_answers_new_connections_and_closes_silent_ones() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::sync::mpsc;
+        
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(WorkerStats::default());
+        let (addr_tx, addr_rx) = mpsc::channel();
+        let (flag, worker_stats) = (Arc::clone(&shutdown), Arc::clone(&stats));
+        let server = std::thread::spawn(move || {
+            let mut event_loop = EventLoop::new_for_server(&ServerConfig::default(), None).unwrap();
+            event_loop.shutdown_timeout = Duration::from_millis(500);
+            addr_tx.send(event_loop.add_listener(&listener(0, true)).unwrap()).unwrap();
+            event_loop.attach_worker(worker_stats, flag);
+            event_loop.event_loop()
+        });
+        let addr = addr_rx.recv().unwrap();
+        
+        // Neither connection has sent its first request when shutdown starts
+        let mut fresh = TcpStream::connect(addr).unwrap();
+        let mut silent = TcpStream::connect(addr).unwrap();
+        let accepted_by = Instant::now() + Duration::from_secs(5);
+        while stats.accepted() < 2 {
+            assert!(Instant::now() < accepted_by, "connections were not accepted");
+            std::thread::yield_now();
+        }
+        shutdown.store(true, Ordering::Relaxed);
+        let started = Instant::now();
+        
+        // The first request is still answered before the connection closes
+        fresh.write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        fresh.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        
+        // A connection that never sends anything is closed at the deadline
+        silent.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(silent.read(&mut [0u8; 64]).unwrap(), 0);
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        server.join().unwrap().unwrap();
+    }
+    
+    #[test]
+    fn test_accept_resumes_after_descriptors_run_out() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::process::Command;
+        use std::sync::mpsc;
+        
+        // The descriptor limit is per process, so the body runs in a child
+        // to keep it away from the tests running alongside
+        const CHILD: &str = "EVENT_LOOP_DESCRIPTOR_TEST";
+        if std::env::var_os(CHILD).is_none() {
+            let output = Command::new(std::env::current_exe().unwrap())
+                .args(["net::event_loop::tests::test_accept_resumes_after_descriptors_run_out", "--exact", "--test-threads=1"])
+                .env(CHILD, "1")
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{}{}",
+                String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+            return;
+        }
+        
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let (addr_tx, addr_rx) = mpsc::channel();
+        let flag = Arc::clone(&shutdown);
+        let server = std::thread::spawn(move || {
+            let mut event_loop = EventLoop::new_for_server(&ServerConfig::default(), None).unwrap();
+            addr_tx.send(event_loop.add_listener(&listener(0, true)).unwrap()).unwrap();
+            event_loop.attach_worker(Arc::new(WorkerStats::default()), flag);
+            event_loop.event_loop()
+        });
+        let addr = addr_rx.recv().unwrap();
+        
+        // Lower the limit and take every descriptor but the one the client needs
+        let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        assert_eq!(unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) }, 0);
+        let lowered = libc::rlimit { rlim_cur: 64, ..limit };
+        assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &lowered) }, 0);
+        let mut held = Vec::new();
+        while let Ok(file) = File::open("/dev/null") {
+            held.push(file);
+        }
+        held.pop();
+        
+        // The server cannot take this connection, but it must not leave it
+        // hanging in the queue either
+        let mut refused = TcpStream::connect(addr).unwrap();
+        refused.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        match refused.read(&mut [0u8; 64]) {
+            Ok(n) => assert_eq!(n, 0),
+            Err(e) => assert_eq!(e.kind(), ErrorKind::ConnectionReset, "{}", e),
+        }
+        drop(refused);
+        
+        // Once descriptors are free again, connections are served as usual
+        held.clear();
+        assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) }, 0);
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        
+        shutdown.store(true, Ordering::Relaxed);
+        server.join().unwrap().unwrap();
+    }
+    
+    #[test]
+    fn test_new_with_config_rejects_bad_address() {
+        assert!(EventLoop::new("127.0.0.1").is_err());
+        assert!(EventLoop::new("127.0.0.1:notaport").is_err());
+    }
+}
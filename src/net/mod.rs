@@ -1,5 +1,11 @@
+#[cfg(target_os = "macos")]
 pub mod epoll;
 pub mod event_loop;
 pub mod conn;
 pub mod timeout;
 pub mod multi_server;
+pub mod notify;
+pub mod reload;
+pub mod signals;
+pub mod upgrade;
+pub mod worker;
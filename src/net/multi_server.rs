@@ -116,13 +116,16 @@ impl MultiServer {
     pub fn run(&mut self) -> io::Result<()> {
         println!("Multi-listener server starting with {} listeners", self.listeners.len());
         
-        // For now, delegate to the original EventLoop-based server
-        // This is a temporary solution until full integration is complete
         use crate::net::event_loop::EventLoop;
         
-        let mut event_loop = EventLoop::new("127.0.0.1:8080")?;
-        println!("Server listening on http://127.0.0.1:8080");
-        println!("Multi-listener features available but using single listener for now");
+        // Sockets bound by start() are handed over to a single event loop
+        self.listeners.clear();
+        
+        let mut event_loop = EventLoop::new_for_server(&self.config, None)?;
+        for listener_config in &self.config.listeners {
+            let addr = event_loop.add_listener(listener_config)?;
+            println!("Server listening on http://{}", addr);
+        }
         
         event_loop.event_loop()
     }
@@ -348,8 +351,7 @@ impl ServerSelector {
             
             // Wildcard matching (*.example.com)
             for (server_name, vhost) in virtual_hosts {
-                if server_name.starts_with("*.") {
-                    let domain = &server_name[2..];
+                if let Some(domain) = server_name.strip_prefix("*.") {
                     if host.ends_with(domain) {
                         return Some(vhost);
                     }
@@ -399,8 +401,7 @@ impl ServerSelector {
             return true;
         }
         
-        if pattern.starts_with("*.") {
-            let domain = &pattern[2..];
+        if let Some(domain) = pattern.strip_prefix("*.") {
             return host.ends_with(domain);
         }
         
@@ -429,31 +430,29 @@ mod tests {
                     address: "127.0.0.1".to_string(),
                     port: 8080,
                     default: true,
+                    name: None,
                 },
                 ListenerConfig {
                     address: "127.0.0.1".to_string(),
                     port: 8081,
                     default: false,
+                    name: None,
                 },
             ],
             virtual_hosts: vec![
                 VirtualHostConfig {
                     server_name: "localhost".to_string(),
                     document_root: std::path::PathBuf::from("/var/www/localhost"),
-                    index_files: vec!["index.html".to_string()],
                     max_body_size: 1024 * 1024,
                     routes: vec![],
-                    access_log: None,
-                    error_log: None,
+                    ..VirtualHostConfig::default()
                 },
                 VirtualHostConfig {
                     server_name: "example.com".to_string(),
                     document_root: std::path::PathBuf::from("/var/www/example"),
-                    index_files: vec!["index.html".to_string()],
                     max_body_size: 1024 * 1024,
                     routes: vec![],
-                    access_log: None,
-                    error_log: None,
+                    ..VirtualHostConfig::default()
                 },
             ],
             default_host: Some("localhost".to_string()),
@@ -466,6 +465,7 @@ mod tests {
                     write: Duration::from_secs(5),
                     keep_alive: Duration::from_secs(10),
                     request: Duration::from_secs(30),
+                    shutdown: Duration::from_secs(30),
                 },
                 ..Default::default()
             },
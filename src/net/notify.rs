@@ -0,0 +1,211 @@
+use std::env;
+use std::io::{self, ErrorKind};
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::process;
+use std::time::{Duration, Instant};
+
+/// Datagram socket the service manager reads state changes from
+pub const NOTIFY_SOCKET_VAR: &str = "NOTIFY_SOCKET";
+/// Watchdog timeout in microseconds, set with `WatchdogSec=`
+pub const WATCHDOG_USEC_VAR: &str = "WATCHDOG_USEC";
+/// Process the watchdog timeout is meant for
+pub const WATCHDOG_PID_VAR: &str = "WATCHDOG_PID";
+
+/// Reports state changes to systemd with the `sd_notify` protocol: one
+/// datagram of newline-separated `KEY=value` assignments per message.
+pub struct Notifier {
+    socket: UnixDatagram,
+    target: SocketAddr,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Notifier for the socket named in `NOTIFY_SOCKET`, or `None` when the
+    /// server was not started by a service manager. The variables stay set
+    /// so a replacement binary can take over the notifications.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var(NOTIFY_SOCKET_VAR).ok()?;
+        match Notifier::new(&path) {
+            Ok(notifier) => Some(notifier.with_watchdog(watchdog_from_env())),
+            Err(e) => {
+                eprintln!("⚠️  Ignoring {}={}: {}", NOTIFY_SOCKET_VAR, path, e);
+                None
+            }
+        }
+    }
+    
+    /// Notifier for a socket path, or an abstract socket name starting with '@'
+    pub fn new(path: &str) -> io::Result<Self> {
+        let target = match path.strip_prefix('@') {
+            Some(name) => abstract_address(name)?,
+            None if path.starts_with('/') => SocketAddr::from_pathname(path)?,
+            None => return Err(io::Error::new(ErrorKind::InvalidInput, "expected an absolute path or an abstract name")),
+        };
+        Ok(Notifier { socket: UnixDatagram::unbound()?, target, watchdog: None })
+    }
+    
+    pub fn with_watchdog(mut self, interval: Option<Duration>) -> Self {
+        self.watchdog = interval;
+        self
+    }
+    
+    /// How long the service manager waits for a `WATCHDOG=1` ping
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog
+    }
+    
+    /// Send one message to the address resolved in `new`
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        self.socket.send_to_addr(state.as_bytes(), &self.target)?;
+        Ok(())
+    }
+    
+    /// The server accepts connections. `MAINPID` lets a replacement binary
+    /// become the process the service manager tracks.
+    pub fn ready(&self, status: &str) -> io::Result<()> {
+        self.notify(&format!("READY=1\nMAINPID={}\nSTATUS={}", process::id(), status))
+    }
+    
+    /// A configuration reload starts; `ready` reports the end of it
+    pub fn reloading(&self) -> io::Result<()> {
+        self.notify(&format!("RELOADING=1\nMONOTONIC_USEC={}\nSTATUS=Reloading configuration", monotonic_usec()))
+    }
+    
+    pub fn stopping(&self, status: &str) -> io::Result<()> {
+        self.notify(&format!("STOPPING=1\nSTATUS={}", status))
+    }
+    
+    pub fn watchdog(&self) -> io::Result<()> {
+        self.notify("WATCHDOG=1")
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_address(name: &str) -> io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name.as_bytes())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn abstract_address(_name: &str) -> io::Result<SocketAddr> {
+    Err(io::Error::new(ErrorKind::Unsupported, "abstract sockets are only available on Linux"))
+}
+
+/// `CLOCK_MONOTONIC` in microseconds, which systemd compares reload
+/// notifications against
+fn monotonic_usec() -> u64 {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now);
+    }
+    now.tv_sec as u64 * 1_000_000 + now.tv_nsec as u64 / 1_000
+}
+
+fn watchdog_from_env() -> Option<Duration> {
+    parse_watchdog(
+        env::var(WATCHDOG_USEC_VAR).ok().as_deref(),
+        env::var(WATCHDOG_PID_VAR).ok().as_deref(),
+    )
+}
+
+/// The watchdog timeout, if it applies to this process. Without
+/// `WATCHDOG_PID` it is meant for whoever reads it.
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.trim().parse::<u32>().ok() != Some(process::id()) {
+            return None;
+        }
+    }
+    match usec?.trim().parse::<u64>() {
+        Ok(0) | Err(_) => None,
+        Ok(usec) => Some(Duration::from_micros(usec)),
+    }
+}
+
+/// Decides when to ping the watchdog. A ping is sent every half timeout,
+/// and only once every event loop has turned since the last one, so a
+/// stuck worker lets the service manager restart the server. Idle loops
+/// still wake up once a second, so `WatchdogSec=` should be a few seconds.
+pub struct Watchdog {
+    period: Duration,
+    last_ping: Instant,
+    ticks: Vec<u64>,
+}
+
+impl Watchdog {
+    pub fn new(timeout: Duration, ticks: Vec<u64>) -> Self {
+        Watchdog { period: timeout / 2, last_ping: Instant::now(), ticks }
+    }
+    
+    /// Whether to ping now, given each worker's current tick count
+    pub fn due(&mut self, ticks: Vec<u64>) -> bool {
+        if self.last_ping.elapsed() < self.period {
+            return false;
+        }
+        if ticks.len() != self.ticks.len() || ticks.iter().zip(&self.ticks).any(|(now, then)| now == then) {
+            return false;
+        }
+        self.last_ping = Instant::now();
+        self.ticks = ticks;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn receive(socket: &UnixDatagram) -> String {
+        let mut buf = [0u8; 512];
+        let n = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..n]).into_owned()
+    }
+    
+    #[test]
+    fn test_notifications_reach_the_socket() {
+        let path = env::temp_dir().join(format!("localhost-notify-{}.sock", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        
+        let notifier = Notifier::new(path.to_str().unwrap()).unwrap();
+        notifier.ready("Serving 1 listener(s)").unwrap();
+        assert_eq!(receive(&receiver), format!("READY=1\nMAINPID={}\nSTATUS=Serving 1 listener(s)", process::id()));
+        
+        notifier.reloading().unwrap();
+        assert!(receive(&receiver).starts_with("RELOADING=1\nMONOTONIC_USEC="));
+        notifier.stopping("Draining").unwrap();
+        assert_eq!(receive(&receiver), "STOPPING=1\nSTATUS=Draining");
+        notifier.watchdog().unwrap();
+        assert_eq!(receive(&receiver), "WATCHDOG=1");
+        std::fs::remove_file(&path).unwrap();
+        
+        assert!(Notifier::new("relative.sock").is_err());
+    }
+    
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_abstract_socket_name() {
+        use std::os::linux::net::SocketAddrExt;
+        let name = format!("localhost-notify-{}", process::id());
+        let receiver = UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(name.as_bytes()).unwrap()).unwrap();
+        
+        Notifier::new(&format!("@{}", name)).unwrap().notify("STATUS=ok").unwrap();
+        assert_eq!(receive(&receiver), "STATUS=ok");
+    }
+    
+    #[test]
+    fn test_watchdog_settings() {
+        let pid = process::id().to_string();
+        assert_eq!(parse_watchdog(Some("4000000"), Some(&pid)), Some(Duration::from_secs(4)));
+        assert_eq!(parse_watchdog(Some("4000000"), None), Some(Duration::from_secs(4)));
+        assert_eq!(parse_watchdog(Some("4000000"), Some("1")), None);
+        assert_eq!(parse_watchdog(Some("0"), None), None);
+        assert_eq!(parse_watchdog(None, None), None);
+        
+        // No ping until every worker has ticked since the last one
+        let mut watchdog = Watchdog::new(Duration::ZERO, vec![0, 0]);
+        assert!(!watchdog.due(vec![1, 0]));
+        assert!(watchdog.due(vec![1, 1]));
+        assert!(!watchdog.due(vec![2, 1]));
+    }
+}
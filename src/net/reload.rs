@@ -0,0 +1,211 @@
+use std::io;
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::config::parser::{ConfigFormat, ConfigParser};
+use crate::config::server::{ListenerConfig, ServerConfig};
+use crate::config::validation::ConfigValidator;
+
+/// A configuration together with the listening sockets bound for it
+pub struct Snapshot {
+    pub generation: u64,
+    pub config: Arc<ServerConfig>,
+    pub listeners: Vec<(TcpListener, ListenerConfig)>,
+}
+
+/// The snapshot every worker serves. A reload publishes a new one; each
+/// worker picks it up on its next wakeup, and requests already in progress
+/// finish with the settings they started with.
+pub struct SharedConfig {
+    generation: AtomicU64,
+    current: Mutex<Arc<Snapshot>>,
+    reload_requested: AtomicBool,
+    upgrade_requested: AtomicBool,
+}
+
+impl SharedConfig {
+    pub fn new(config: Arc<ServerConfig>, listeners: Vec<(TcpListener, ListenerConfig)>) -> Self {
+        SharedConfig {
+            generation: AtomicU64::new(0),
+            current: Mutex::new(Arc::new(Snapshot { generation: 0, config, listeners })),
+            reload_requested: AtomicBool::new(false),
+            upgrade_requested: AtomicBool::new(false),
+        }
+    }
+    
+    /// Generation of the current snapshot, cheap enough to check on every wakeup
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+    
+    pub fn current(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.current.lock().unwrap())
+    }
+    
+    /// Ask for the configuration file to be read again, as SIGHUP does
+    pub fn request_reload(&self) {
+        self.reload_requested.store(true, Ordering::Relaxed);
+    }
+    
+    pub fn take_reload_request(&self) -> bool {
+        self.reload_requested.swap(false, Ordering::Relaxed)
+    }
+    
+    /// Ask for a new binary to take over the listeners, as SIGUSR2 does
+    pub fn request_upgrade(&self) {
+        self.upgrade_requested.store(true, Ordering::Relaxed);
+    }
+    
+    pub fn take_upgrade_request(&self) -> bool {
+        self.upgrade_requested.swap(false, Ordering::Relaxed)
+    }
+    
+    /// Read and validate the configuration file, then publish it. Listeners
+    /// that are still configured keep their sockets; new ones are bound.
+    /// On any error the current configuration stays in place.
+    pub fn reload(&self, path: &Path) -> io::Result<u64> {
+        let config = ConfigParser::new(ConfigFormat::Auto).parse_file(path)?;
+        
+        let mut validator = ConfigValidator::new();
+        if let Err(errors) = validator.validate(&config) {
+            let messages: Vec<String> = errors.iter()
+                .map(|error| format!("{} - {}", error.field, error.message))
+                .collect();
+            return Err(io::Error::new(io::ErrorKind::InvalidData, messages.join("; ")));
+        }
+        for warning in validator.warnings() {
+            eprintln!("   WARNING: {} - {}", warning.field, warning.message);
+        }
+        
+        let current = self.current();
+        if config.global.workers != current.config.global.workers {
+            eprintln!("⚠️  Changing the number of workers needs a restart, keeping {}", current.config.global.workers);
+        }
+        
+        let listeners = rebind_listeners(&current, &config.listeners, bind_listener)?;
+        let generation = current.generation + 1;
+        
+        *self.current.lock().unwrap() = Arc::new(Snapshot {
+            generation,
+            config: Arc::new(config),
+            listeners,
+        });
+        self.generation.store(generation, Ordering::Release);
+        Ok(generation)
+    }
+}
+
+/// Whether two listener entries name the same socket address
+pub fn same_address(a: &ListenerConfig, b: &ListenerConfig) -> bool {
+    a.address == b.address && a.port == b.port
+}
+
+fn bind_listener(listener: &ListenerConfig) -> io::Result<TcpListener> {
+    TcpListener::bind((listener.address.as_str(), listener.port))
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to bind {}:{}: {}", listener.address, listener.port, e)))
+}
+
+/// Sockets for `wanted`: existing ones are shared with the current
+/// snapshot, the others are created with `bind`
+fn rebind_listeners(
+    current: &Snapshot,
+    wanted: &[ListenerConfig],
+    bind: impl Fn(&ListenerConfig) -> io::Result<TcpListener>,
+) -> io::Result<Vec<(TcpListener, ListenerConfig)>> {
+    let mut listeners = Vec::with_capacity(wanted.len());
+    
+    for listener in wanted {
+        let existing = current.listeners.iter().find(|(_, config)| same_address(config, listener));
+        let socket = match existing {
+            Some((socket, _)) => socket.try_clone()?,
+            None => {
+                let socket = bind(listener)?;
+                println!("✅ Bound new listener {}:{}", listener.address, listener.port);
+                socket
+            }
+        };
+        listeners.push((socket, listener.clone()));
+    }
+    
+    for (_, config) in &current.listeners {
+        if !wanted.iter().any(|listener| same_address(config, listener)) {
+            println!("🔌 Closing listener {}:{}", config.address, config.port);
+        }
+    }
+    Ok(listeners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    /// A socket on a free port, kept bound so no other test can take the port
+    fn bound_listener() -> (TcpListener, ListenerConfig) {
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        (socket, ListenerConfig { address: "127.0.0.1".to_string(), port, default: true, name: None })
+    }
+    
+    fn config_file(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("localhost-reload-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+    
+    fn listener_section(port: u16, default: bool) -> String {
+        format!("[[listener]]\naddress = \"127.0.0.1\"\nport = {}\ndefault = {}\n", port, default)
+    }
+    
+    const VHOST: &str = "[[vhost]]\nserver_name = \"localhost\"\ndocument_root = \"./www\"\ndefault = true\n";
+    
+    #[test]
+    fn test_reload_keeps_existing_listeners_and_binds_new_ones() {
+        let ((kept, kept_config), (added, added_config)) = (bound_listener(), bound_listener());
+        let (kept_port, added_port) = (kept_config.port, added_config.port);
+        
+        // Only the entry missing from the snapshot is bound, here to the socket the test holds
+        let snapshot = Snapshot {
+            generation: 0,
+            config: Arc::new(ServerConfig::default()),
+            listeners: vec![(kept.try_clone().unwrap(), kept_config.clone())],
+        };
+        let wanted = vec![kept_config.clone(), added_config.clone()];
+        let listeners = rebind_listeners(&snapshot, &wanted, |listener| {
+            assert_eq!(listener.port, added_port);
+            added.try_clone()
+        }).unwrap();
+        let ports: Vec<u16> = listeners.iter().map(|(socket, _)| socket.local_addr().unwrap().port()).collect();
+        assert_eq!(ports, vec![kept_port, added_port]);
+        
+        // Binding the kept port again would fail, so reloading must reuse it;
+        // the listener left out of the file is dropped
+        let shared = SharedConfig::new(Arc::new(ServerConfig::default()), vec![(kept, kept_config), (added, added_config)]);
+        let path = config_file("keep", &format!("{}{}", listener_section(kept_port, true), VHOST));
+        assert_eq!(shared.reload(&path).unwrap(), 1);
+        assert_eq!(shared.generation(), 1);
+        let snapshot = shared.current();
+        assert_eq!(snapshot.listeners.len(), 1);
+        assert_eq!(snapshot.listeners[0].0.local_addr().unwrap().port(), kept_port);
+        fs::remove_file(&path).unwrap();
+    }
+    
+    #[test]
+    fn test_invalid_config_keeps_current_snapshot() {
+        let shared = SharedConfig::new(Arc::new(ServerConfig::default()), Vec::new());
+        
+        // The port stays taken, so even a reload that got as far as binding would fail
+        let (_socket, listener) = bound_listener();
+        let path = config_file("invalid", &format!("[global]\nworkers = 0\n{}{}", listener_section(listener.port, true), VHOST));
+        assert!(shared.reload(&path).is_err());
+        assert_eq!(shared.generation(), 0);
+        
+        fs::write(&path, "[[listener]]\nport = \"not a port\"\n").unwrap();
+        assert!(shared.reload(&path).is_err());
+        assert_eq!(shared.current().generation, 0);
+        fs::remove_file(&path).unwrap();
+        
+        assert!(shared.reload(Path::new("/nonexistent/server.toml")).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::RawFd;
+use libc::c_int;
+
+/// Signals delivered through the event loops instead of handlers.
+///
+/// On Linux the signals are blocked and read from a `signalfd` that every
+/// worker registers with its epoll instance. On macOS they are ignored and
+/// each kqueue watches them with `EVFILT_SIGNAL`. Create the set before
+/// starting any thread so the blocked mask is inherited.
+pub struct SignalSet {
+    #[cfg(target_os = "macos")]
+    signals: Vec<c_int>,
+    #[cfg(target_os = "linux")]
+    fd: RawFd,
+}
+
+impl SignalSet {
+    #[cfg(target_os = "linux")]
+    pub fn new(signals: &[c_int]) -> io::Result<Self> {
+        let fd = unsafe {
+            let mut mask: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut mask);
+            for &signal in signals {
+                libc::sigaddset(&mut mask, signal);
+            }
+            
+            let result = libc::pthread_sigmask(libc::SIG_BLOCK, &mask, std::ptr::null_mut());
+            if result != 0 {
+                return Err(io::Error::from_raw_os_error(result));
+            }
+            libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC)
+        };
+        
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SignalSet { fd })
+    }
+    
+    #[cfg(target_os = "macos")]
+    pub fn new(signals: &[c_int]) -> io::Result<Self> {
+        for &signal in signals {
+            // kqueue still reports ignored signals
+            if unsafe { libc::signal(signal, libc::SIG_IGN) } == libc::SIG_ERR {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(SignalSet { signals: signals.to_vec() })
+    }
+    
+    #[cfg(target_os = "macos")]
+    pub fn signals(&self) -> &[c_int] {
+        &self.signals
+    }
+    
+    #[cfg(target_os = "linux")]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+    
+    /// Take the next pending signal, if any. Several loops watch the same
+    /// descriptor, so only one of them receives each signal.
+    #[cfg(target_os = "linux")]
+    pub fn read(&self) -> io::Result<Option<c_int>> {
+        let mut info: libc::signalfd_siginfo = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::signalfd_siginfo>();
+        
+        let n = unsafe { libc::read(self.fd, &mut info as *mut _ as *mut libc::c_void, size) };
+        if n == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        
+        if n as usize != size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Short read from signalfd"));
+        }
+        Ok(Some(info.ssi_signo as c_int))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for SignalSet {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Name of a signal for log messages
+pub fn signal_name(signal: c_int) -> &'static str {
+    match signal {
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGINT => "SIGINT",
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGUSR2 => "SIGUSR2",
+        _ => "signal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_signalfd_receives_blocked_signal() {
+        // SIGUSR2 is sent to this thread only, so other tests never see it
+        let signals = SignalSet::new(&[libc::SIGUSR2]).unwrap();
+        assert_eq!(signals.read().unwrap(), None);
+        
+        unsafe {
+            libc::pthread_kill(libc::pthread_self(), libc::SIGUSR2);
+        }
+        assert_eq!(signals.read().unwrap(), Some(libc::SIGUSR2));
+        assert_eq!(signals.read().unwrap(), None);
+    }
+}
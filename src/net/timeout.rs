@@ -130,8 +130,10 @@ impl TimeoutManager {
                 continue;
             }
             
-            // Check overall request timeout
-            if now.duration_since(conn.request_start) > self.config.request_timeout {
+            // Check overall request timeout. Responses are exempt: a large file
+            // may take longer to send and is bounded by the write idle timeout.
+            let writing = matches!(conn.state, ConnectionState::Writing);
+            if !writing && now.duration_since(conn.request_start) > self.config.request_timeout {
                 timed_out.push(*fd);
                 continue;
             }
@@ -209,8 +211,10 @@ mod tests {
     
     #[test]
     fn test_timeout_detection() {
-        let mut config = TimeoutConfig::default();
-        config.read_header_timeout = Duration::from_millis(10);
+        let config = TimeoutConfig {
+            read_header_timeout: Duration::from_millis(10),
+            ..Default::default()
+        };
         
         let mut manager = TimeoutManager::new(config);
         manager.add_connection(1);
@@ -228,8 +232,10 @@ mod tests {
     
     #[test]
     fn test_activity_update() {
-        let mut config = TimeoutConfig::default();
-        config.read_header_timeout = Duration::from_millis(20);
+        let config = TimeoutConfig {
+            read_header_timeout: Duration::from_millis(20),
+            ..Default::default()
+        };
         
         let mut manager = TimeoutManager::new(config);
         manager.add_connection(1);
@@ -263,4 +269,24 @@ mod tests {
         // Should still be tracked
         assert_eq!(manager.connection_count(), 1);
     }
+    
+    #[test]
+    fn test_request_timeout_skips_writing() {
+        let config = TimeoutConfig {
+            request_timeout: Duration::from_millis(10),
+            ..Default::default()
+        };
+        
+        let mut manager = TimeoutManager::new(config);
+        manager.add_connection(1);
+        manager.add_connection(2);
+        manager.set_connection_state(2, ConnectionState::Writing);
+        
+        thread::sleep(Duration::from_millis(15));
+        manager.update_activity(2);
+        
+        // A long response keeps going while it makes progress
+        let timed_out = manager.check_timeouts();
+        assert_eq!(timed_out, vec![1]);
+    }
 }
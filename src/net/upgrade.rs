@@ -0,0 +1,309 @@
+use std::env;
+use std::io::{self, ErrorKind};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{self, Child, Command};
+use libc::c_int;
+use crate::config::server::ListenerConfig;
+use crate::net::notify::WATCHDOG_PID_VAR;
+
+/// Comma-separated listening socket descriptors handed to a replacement process
+pub const INHERITED_FDS_VAR: &str = "LOCALHOST_LISTEN_FDS";
+/// Process id of the server being replaced, stopped once the replacement is ready
+pub const UPGRADE_PARENT_VAR: &str = "LOCALHOST_UPGRADE_PARENT";
+/// systemd socket activation: number of sockets passed, starting at descriptor 3
+pub const LISTEN_FDS_VAR: &str = "LISTEN_FDS";
+/// Process the passed sockets are meant for
+pub const LISTEN_PID_VAR: &str = "LISTEN_PID";
+/// Colon-separated `FileDescriptorName=` of each passed socket
+pub const LISTEN_FDNAMES_VAR: &str = "LISTEN_FDNAMES";
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Listening sockets passed down by the process this one replaces, or by
+/// systemd, waiting to be matched to `[[listener]]` entries
+pub struct InheritedListeners {
+    /// Each socket with the name systemd gave it, if any
+    sockets: Vec<(TcpListener, Option<String>)>,
+    /// Process to stop once this one serves the sockets
+    parent: Option<libc::pid_t>,
+}
+
+impl InheritedListeners {
+    /// Take the sockets named in the environment, from a server being
+    /// replaced or else from systemd. The variables are removed so CGI
+    /// scripts and later replacements do not see them; call this before
+    /// starting any thread.
+    pub fn from_env() -> io::Result<Self> {
+        let fds = env::var(INHERITED_FDS_VAR).ok();
+        let parent = env::var(UPGRADE_PARENT_VAR).ok();
+        env::remove_var(INHERITED_FDS_VAR);
+        env::remove_var(UPGRADE_PARENT_VAR);
+        
+        let activated = Self::from_systemd_env()?;
+        let mut inherited = match fds {
+            Some(fds) => Self::from_fd_list(&fds)?,
+            None => activated,
+        };
+        inherited.parent = parent.and_then(|pid| pid.parse().ok());
+        Ok(inherited)
+    }
+    
+    /// Adopt the sockets of systemd socket activation, unless they were
+    /// meant for another process
+    fn from_systemd_env() -> io::Result<Self> {
+        let pid = env::var(LISTEN_PID_VAR).ok();
+        let count = env::var(LISTEN_FDS_VAR).ok();
+        let names = env::var(LISTEN_FDNAMES_VAR).ok();
+        env::remove_var(LISTEN_PID_VAR);
+        env::remove_var(LISTEN_FDS_VAR);
+        env::remove_var(LISTEN_FDNAMES_VAR);
+        
+        let for_us = pid.is_some_and(|pid| pid.trim().parse::<u32>().ok() == Some(process::id()));
+        let count = match count {
+            Some(count) if for_us => count.trim().parse::<RawFd>()
+                .map_err(|_| io::Error::new(ErrorKind::InvalidInput, format!("Invalid {} '{}'", LISTEN_FDS_VAR, count)))?,
+            _ => 0,
+        };
+        Self::from_descriptors(LISTEN_FDS_START..LISTEN_FDS_START + count, names.as_deref())
+    }
+    
+    /// Adopt descriptors, naming them in order from a colon-separated list
+    fn from_descriptors(fds: impl IntoIterator<Item = RawFd>, names: Option<&str>) -> io::Result<Self> {
+        let mut names = names.unwrap_or("").split(':');
+        let mut sockets = Vec::new();
+        for fd in fds {
+            let name = names.next().filter(|name| !name.is_empty()).map(str::to_string);
+            sockets.push((adopt_listener(fd)?, name));
+        }
+        Ok(InheritedListeners { sockets, parent: None })
+    }
+    
+    /// Adopt every descriptor of a comma-separated list. Each one must be a
+    /// listening TCP socket; it is marked close-on-exec again.
+    pub fn from_fd_list(list: &str) -> io::Result<Self> {
+        let mut sockets = Vec::new();
+        for item in list.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let fd: RawFd = item.parse()
+                .map_err(|_| io::Error::new(ErrorKind::InvalidInput, format!("Invalid inherited descriptor '{}'", item)))?;
+            sockets.push((adopt_listener(fd)?, None));
+        }
+        Ok(InheritedListeners { sockets, parent: None })
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.sockets.is_empty()
+    }
+    
+    /// Whether the sockets come from a server being replaced rather than
+    /// from systemd
+    pub fn is_upgrade(&self) -> bool {
+        self.parent.is_some()
+    }
+    
+    /// Remove and return the inherited socket with the listener's name, or
+    /// else the one bound to its address
+    pub fn take(&mut self, listener: &ListenerConfig) -> Option<TcpListener> {
+        let named = listener.name.as_ref()
+            .and_then(|wanted| self.sockets.iter().position(|(_, name)| name.as_ref() == Some(wanted)));
+        let index = match named {
+            Some(index) => index,
+            None => {
+                let wanted: Vec<_> = (listener.address.as_str(), listener.port).to_socket_addrs().ok()?.collect();
+                self.sockets.iter()
+                    .position(|(socket, _)| socket.local_addr().is_ok_and(|addr| wanted.contains(&addr)))?
+            }
+        };
+        Some(self.sockets.remove(index).0)
+    }
+    
+    /// Close the sockets no listener claimed
+    pub fn close_unused(&mut self) {
+        for (socket, _) in self.sockets.drain(..) {
+            if let Ok(addr) = socket.local_addr() {
+                println!("🔌 Closing inherited listener {}, it is no longer configured", addr);
+            }
+        }
+    }
+    
+    /// Tell the process being replaced that this one now serves its
+    /// sockets, so it stops accepting and drains
+    pub fn stop_parent(&self) {
+        let parent = match self.parent {
+            Some(parent) => parent,
+            None => return,
+        };
+        
+        // Only the process that started this one is signalled
+        if unsafe { libc::getppid() } != parent {
+            eprintln!("⚠️  Process {} that started the upgrade is gone, nothing to stop", parent);
+            return;
+        }
+        if unsafe { libc::kill(parent, libc::SIGTERM) } == -1 {
+            eprintln!("⚠️  Failed to stop process {}: {}", parent, io::Error::last_os_error());
+        } else {
+            println!("🔁 Took over from process {}, asked it to drain", parent);
+        }
+    }
+}
+
+/// Check that `fd` is a listening stream socket and take ownership of it
+fn adopt_listener(fd: RawFd) -> io::Result<TcpListener> {
+    let invalid = |reason: &str| io::Error::new(ErrorKind::InvalidInput, format!("Inherited descriptor {} {}", fd, reason));
+    
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(invalid("is not open"));
+    }
+    if socket_option(fd, libc::SO_TYPE)? != libc::SOCK_STREAM {
+        return Err(invalid("is not a stream socket"));
+    }
+    if socket_option(fd, libc::SO_ACCEPTCONN)? == 0 {
+        return Err(invalid("is not listening"));
+    }
+    set_cloexec(fd, true)?;
+    
+    Ok(unsafe { TcpListener::from_raw_fd(fd) })
+}
+
+fn socket_option(fd: RawFd, option: c_int) -> io::Result<c_int> {
+    let mut value: c_int = 0;
+    let mut length = std::mem::size_of::<c_int>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(fd, libc::SOL_SOCKET, option, &mut value as *mut c_int as *mut libc::c_void, &mut length)
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+fn set_cloexec(fd: RawFd, cloexec: bool) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let flags = if cloexec { flags | libc::FD_CLOEXEC } else { flags & !libc::FD_CLOEXEC };
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Path of the running binary. A deploy that replaced the file leaves
+/// Linux reporting the old one as deleted; the new file at that path is
+/// the one to start.
+fn current_binary() -> io::Result<PathBuf> {
+    let exe = env::current_exe()?;
+    let path = exe.to_string_lossy();
+    match path.strip_suffix(" (deleted)") {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Ok(exe),
+    }
+}
+
+/// Start the binary again with the same arguments, handing it `listeners`.
+/// The replacement stops this process once it is serving them.
+pub fn spawn_replacement(listeners: &[(TcpListener, ListenerConfig)]) -> io::Result<Child> {
+    let fds: Vec<RawFd> = listeners.iter().map(|(socket, _)| socket.as_raw_fd()).collect();
+    let list: Vec<String> = fds.iter().map(|fd| fd.to_string()).collect();
+    
+    let mut command = Command::new(current_binary()?);
+    command.args(env::args_os().skip(1))
+        .env(INHERITED_FDS_VAR, list.join(","))
+        .env(UPGRADE_PARENT_VAR, process::id().to_string())
+        // The systemd watchdog passes to the replacement along with the service
+        .env_remove(WATCHDOG_PID_VAR);
+    
+    // Only the new process gets the sockets, not CGI scripts started meanwhile
+    unsafe {
+        command.pre_exec(move || {
+            for &fd in &fds {
+                set_cloexec(fd, false)?;
+            }
+            Ok(())
+        });
+    }
+    command.spawn()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::io::IntoRawFd;
+    
+    fn listener(port: u16) -> ListenerConfig {
+        ListenerConfig { address: "127.0.0.1".to_string(), port, default: true, name: None }
+    }
+    
+    #[test]
+    fn test_inherited_sockets_match_listeners() {
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let fd = socket.into_raw_fd();
+        
+        let mut inherited = InheritedListeners::from_fd_list(&format!(" {},", fd)).unwrap();
+        assert!(inherited.take(&listener(port.wrapping_add(1))).is_none());
+        
+        let adopted = inherited.take(&listener(port)).unwrap();
+        assert_eq!(adopted.local_addr().unwrap().port(), port);
+        assert!(inherited.is_empty());
+        
+        // The adopted socket is not passed on to other programs
+        let flags = unsafe { libc::fcntl(adopted.as_raw_fd(), libc::F_GETFD) };
+        assert_ne!(flags & libc::FD_CLOEXEC, 0);
+    }
+    
+    #[test]
+    fn test_upgrade_environment_is_taken_once() {
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        env::set_var(INHERITED_FDS_VAR, socket.into_raw_fd().to_string());
+        env::set_var(UPGRADE_PARENT_VAR, "4242");
+        
+        let mut inherited = InheritedListeners::from_env().unwrap();
+        assert!(inherited.is_upgrade());
+        assert_eq!(inherited.parent, Some(4242));
+        assert_eq!(inherited.take(&listener(port)).unwrap().local_addr().unwrap().port(), port);
+        
+        // CGI scripts and later replacements do not see the variables
+        assert!(env::var(INHERITED_FDS_VAR).is_err());
+        assert!(env::var(UPGRADE_PARENT_VAR).is_err());
+        let again = InheritedListeners::from_env().unwrap();
+        assert!(again.is_empty() && !again.is_upgrade());
+    }
+    
+    #[test]
+    fn test_only_listening_sockets_are_adopted() {
+        assert!(InheritedListeners::from_fd_list("abc").is_err());
+        assert!(InheritedListeners::from_fd_list("1000000").is_err());
+        
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(InheritedListeners::from_fd_list(&udp.as_raw_fd().to_string()).is_err());
+        
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = std::net::TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        assert!(InheritedListeners::from_fd_list(&client.as_raw_fd().to_string()).is_err());
+    }
+    
+    #[test]
+    fn test_activated_sockets_match_by_name() {
+        let (first, second) = (TcpListener::bind("127.0.0.1:0").unwrap(), TcpListener::bind("127.0.0.1:0").unwrap());
+        let (first_port, second_port) = (first.local_addr().unwrap().port(), second.local_addr().unwrap().port());
+        let fds = [first.into_raw_fd(), second.into_raw_fd()];
+        
+        let mut inherited = InheritedListeners::from_descriptors(fds, Some("web:admin")).unwrap();
+        assert!(!inherited.is_upgrade());
+        
+        // The name wins over the configured address
+        let mut admin = listener(first_port);
+        admin.name = Some("admin".to_string());
+        assert_eq!(inherited.take(&admin).unwrap().local_addr().unwrap().port(), second_port);
+        
+        // A name systemd did not pass falls back to the address
+        let mut web = listener(first_port);
+        web.name = Some("missing".to_string());
+        assert!(inherited.take(&web).is_some());
+        assert!(inherited.is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+use std::io;
+use std::path::Path;
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use crate::net::event_loop::EventLoop;
+use crate::net::notify::{Notifier, Watchdog};
+use crate::net::reload::SharedConfig;
+use crate::net::signals::SignalSet;
+use crate::net::upgrade::spawn_replacement;
+use crate::session::SessionStore;
+
+/// Counters kept by one worker's event loop
+#[derive(Debug, Default)]
+pub struct WorkerStats {
+    accepted: AtomicU64,
+    closed: AtomicU64,
+    requests: AtomicU64,
+    ticks: AtomicU64,
+}
+
+impl WorkerStats {
+    pub fn record_accept(&self) {
+        self.accepted.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    pub fn record_close(&self) {
+        self.closed.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    pub fn record_requests(&self, count: usize) {
+        self.requests.fetch_add(count as u64, Ordering::Relaxed);
+    }
+    
+    /// Count one pass of the event loop
+    pub fn record_tick(&self) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    pub fn ticks(&self) -> u64 {
+        self.ticks.load(Ordering::Relaxed)
+    }
+    
+    /// Connections accepted since the worker started
+    pub fn accepted(&self) -> u64 {
+        self.accepted.load(Ordering::Relaxed)
+    }
+    
+    /// Connections currently open
+    pub fn active(&self) -> u64 {
+        self.accepted().saturating_sub(self.closed.load(Ordering::Relaxed))
+    }
+    
+    /// Requests answered, including rejected ones
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+struct Worker {
+    id: usize,
+    stats: Arc<WorkerStats>,
+    handle: JoinHandle<io::Result<()>>,
+}
+
+/// `workers` threads, each running its own event loop over the same
+/// listening sockets. The kernel wakes one loop per incoming connection
+/// (EPOLLEXCLUSIVE), so a slow CGI script or file read only holds up the
+/// clients of the worker handling it.
+pub struct WorkerPool {
+    workers: Vec<Worker>,
+    shutdown: Arc<AtomicBool>,
+    shared_config: Arc<SharedConfig>,
+    notifier: Option<Notifier>,
+}
+
+impl WorkerPool {
+    /// Start the workers. Every worker gets its own handle to each bound
+    /// listener of the shared configuration, and shares the session store
+    /// and the signals that shut the pool down or reload it.
+    pub fn start(
+        shared_config: Arc<SharedConfig>,
+        session_store: SessionStore,
+        signals: Option<Arc<SignalSet>>,
+    ) -> io::Result<Self> {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let workers = shared_config.current().config.global.workers.max(1);
+        let mut pool = WorkerPool { workers: Vec::new(), shutdown, shared_config, notifier: None };
+        
+        for id in 0..workers {
+            let stats = Arc::new(WorkerStats::default());
+            let (shared_config, session_store) = (Arc::clone(&pool.shared_config), session_store.clone());
+            let (worker_stats, shutdown) = (Arc::clone(&stats), Arc::clone(&pool.shutdown));
+            let signals = signals.clone();
+            
+            let spawned = thread::Builder::new()
+                .name(format!("worker-{}", id))
+                .spawn(move || {
+                    let config = Arc::clone(&shared_config.current().config);
+                    let mut event_loop = EventLoop::new_for_server(&config, Some(session_store))?;
+                    event_loop.follow_config(shared_config)?;
+                    event_loop.attach_worker(worker_stats, shutdown);
+                    if let Some(signals) = signals {
+                        event_loop.watch_signals(signals)?;
+                    }
+                    println!("Worker {} serving {} listener(s)", id, event_loop.listener_count());
+                    event_loop.event_loop()
+                });
+            
+            match spawned {
+                Ok(handle) => pool.workers.push(Worker { id, stats, handle }),
+                Err(e) => {
+                    let _ = pool.shutdown();
+                    return Err(e);
+                }
+            }
+        }
+        
+        println!("🧵 Started {} worker thread(s)", pool.workers.len());
+        Ok(pool)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+    
+    /// Report readiness, reloads and shutdown to systemd through `notifier`
+    pub fn set_notifier(&mut self, notifier: Option<Notifier>) {
+        self.notifier = notifier;
+    }
+    
+    /// Tell systemd the server accepts connections
+    pub fn notify_ready(&self) {
+        let status = self.status();
+        self.notify(|notifier| notifier.ready(&status));
+    }
+    
+    fn notify(&self, send: impl FnOnce(&Notifier) -> io::Result<()>) {
+        if let Some(notifier) = &self.notifier {
+            if let Err(e) = send(notifier) {
+                eprintln!("⚠️  Failed to notify systemd: {}", e);
+            }
+        }
+    }
+    
+    fn status(&self) -> String {
+        let snapshot = self.shared_config.current();
+        format!(
+            "Serving {} listener(s) with {} worker(s), configuration generation {}",
+            snapshot.listeners.len(), self.workers.len(), snapshot.generation
+        )
+    }
+    
+    fn ticks(&self) -> Vec<u64> {
+        self.workers.iter().map(|worker| worker.stats.ticks()).collect()
+    }
+    
+    /// Run until a worker stops, reloading `config_path` whenever a worker
+    /// receives SIGHUP and starting a replacement binary on SIGUSR2. The
+    /// first worker to stop, whether from an error or a shutdown signal,
+    /// stops the rest once they have drained their connections; its error
+    /// is returned.
+    pub fn wait(self, config_path: Option<&Path>) -> io::Result<()> {
+        let mut replacement: Option<Child> = None;
+        let mut watchdog = self.notifier.as_ref()
+            .and_then(Notifier::watchdog_interval)
+            .map(|timeout| Watchdog::new(timeout, self.ticks()));
+        
+        while !self.shutdown.load(Ordering::Relaxed) {
+            if self.workers.iter().any(|worker| worker.handle.is_finished()) {
+                break;
+            }
+            if self.shared_config.take_reload_request() {
+                self.reload(config_path);
+            }
+            if self.shared_config.take_upgrade_request() {
+                replacement = self.upgrade(replacement);
+            }
+            
+            // A replacement that exits before taking over leaves this process serving
+            if let Some(Ok(Some(status))) = replacement.as_mut().map(|child| child.try_wait()) {
+                eprintln!("❌ Replacement process exited with {}, still serving", status);
+                replacement = None;
+            }
+            
+            if watchdog.as_mut().is_some_and(|watchdog| watchdog.due(self.ticks())) {
+                self.notify(Notifier::watchdog);
+            }
+            thread::park_timeout(std::time::Duration::from_millis(200));
+        }
+        
+        // A replacement that took over is the service now, and still running
+        match replacement {
+            Some(child) => println!("🔁 Handing over to replacement process {}", child.id()),
+            None => self.notify(|notifier| notifier.stopping("Draining connections")),
+        }
+        self.shutdown()
+    }
+    
+    /// Start the binary again on the current listeners. It sends SIGTERM
+    /// to this process once it serves them, which then drains.
+    fn upgrade(&self, running: Option<Child>) -> Option<Child> {
+        if let Some(child) = running {
+            println!("ℹ️  Replacement process {} is already starting", child.id());
+            return Some(child);
+        }
+        
+        match spawn_replacement(&self.shared_config.current().listeners) {
+            Ok(child) => {
+                println!("🔁 Started replacement process {}", child.id());
+                Some(child)
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to start replacement process: {}", e);
+                None
+            }
+        }
+    }
+    
+    /// Publish the configuration file's current contents, keeping the
+    /// running configuration if it cannot be read or is invalid
+    fn reload(&self, config_path: Option<&Path>) {
+        let path = match config_path {
+            Some(path) => path,
+            None => {
+                println!("ℹ️  Started without a configuration file, nothing to reload");
+                return;
+            }
+        };
+        
+        println!("📄 Reloading configuration from: {}", path.display());
+        self.notify(Notifier::reloading);
+        match self.shared_config.reload(path) {
+            Ok(generation) => println!("✅ Configuration reloaded (generation {})", generation),
+            Err(e) => eprintln!("❌ Keeping the current configuration: {}", e),
+        }
+        self.notify_ready();
+    }
+    
+    /// Stop every worker and wait for them to finish the requests in
+    /// progress. Returns the first error a worker ended with.
+    pub fn shutdown(self) -> io::Result<()> {
+        self.shutdown.store(true, Ordering::Relaxed);
+        for worker in &self.workers {
+            if worker.stats.active() > 0 {
+                println!("Stopping worker {} with {} open connection(s)", worker.id, worker.stats.active());
+            }
+        }
+        
+        let mut result = Ok(());
+        for worker in self.workers {
+            let outcome = worker.handle.join()
+                .unwrap_or_else(|_| Err(io::Error::other("worker thread panicked")));
+            
+            println!(
+                "Worker {} stopped: {} connections, {} requests",
+                worker.id, worker.stats.accepted(), worker.stats.requests()
+            );
+            if let Err(e) = outcome {
+                eprintln!("Worker {} failed: {}", worker.id, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use crate::config::server::{ListenerConfig, ServerConfig};
+    
+    #[test]
+    fn test_workers_share_listeners_and_stop() {
+        let mut config = ServerConfig::default();
+        config.global.workers = 3;
+        
+        let listener = ListenerConfig { address: "127.0.0.1".to_string(), port: 0, default: true, name: None };
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        
+        let store = SessionStore::new(crate::session::SessionConfig::default());
+        let shared = Arc::new(SharedConfig::new(Arc::new(config), vec![(socket, listener)]));
+        let pool = WorkerPool::start(Arc::clone(&shared), store, None).unwrap();
+        assert_eq!(pool.len(), 3);
+        
+        for _ in 0..6 {
+            let mut client = TcpStream::connect(addr).unwrap();
+            client.write_all(b"GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        }
+        
+        let stats: Vec<&WorkerStats> = pool.workers.iter().map(|worker| worker.stats.as_ref()).collect();
+        assert_eq!(stats.iter().map(|s| s.requests()).sum::<u64>(), 6);
+        assert_eq!(stats.iter().map(|s| s.accepted()).sum::<u64>(), 6);
+        
+        pool.shutdown().unwrap();
+        drop(shared);
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::http::request::HttpRequest;
+use crate::http::request::{HttpRequest, Method};
 use crate::http::response::HttpResponse;
 use std::io;
 
@@ -38,7 +38,7 @@ impl StaticFileHandler {
 }
 
 impl Handler for StaticFileHandler {
-    fn handle(&mut self, request: &HttpRequest) -> HandlerResult {
+    fn handle(&mut self, _request: &HttpRequest) -> HandlerResult {
         // For now, delegate to existing static file serving logic
         // This will be integrated with the existing StaticFileServer
         HandlerResult::Continue
@@ -95,17 +95,17 @@ impl MethodFilterHandler {
 
 impl Handler for MethodFilterHandler {
     fn handle(&mut self, request: &HttpRequest) -> HandlerResult {
-        if self.allowed_methods.contains(&request.method()) {
+        let method = request.method();
+        let allowed = self.allowed_methods.contains(method)
+            || (*method == Method::HEAD && self.allowed_methods.contains(&Method::GET));
+        
+        if allowed {
             HandlerResult::Continue
         } else {
             let mut response = HttpResponse::method_not_allowed();
             
             // Set Allow header with supported methods
-            let methods: Vec<String> = self.allowed_methods
-                .iter()
-                .map(|m| format!("{:?}", m))
-                .collect();
-            response.set_header("Allow", &methods.join(", "));
+            response.set_header("Allow", &Method::allow_header(&self.allowed_methods));
             
             HandlerResult::Response(response)
         }
@@ -222,7 +222,7 @@ mod tests {
         
         match handler.handle(&request) {
             HandlerResult::Response(response) => {
-                assert_eq!(response.status_code(), 301);
+                assert_eq!(response.status_code, 301);
                 assert_eq!(response.get_header("Location"), Some("https://example.com"));
             }
             _ => panic!("Expected response"),
@@ -236,14 +236,13 @@ mod tests {
         allowed_methods.insert(Method::POST);
         
         let mut handler = MethodFilterHandler::new(allowed_methods);
-        
-        // Test allowed method
-        let request = HttpRequest::new();
-        // Note: We'd need to set the method on the request, but HttpRequest::new() doesn't allow this
-        // This test would need to be updated when HttpRequest has a proper constructor
-        
-        // For now, just test that the handler exists
         assert_eq!(handler.name(), "MethodFilterHandler");
+        
+        // Allowed methods pass on to the next handler, others get 405
+        let request = HttpRequest::with_headers(Method::POST, "/", &[]);
+        assert!(matches!(handler.handle(&request), HandlerResult::Continue));
+        let request = HttpRequest::with_headers(Method::DELETE, "/", &[]);
+        assert!(matches!(handler.handle(&request), HandlerResult::Response(response) if response.status_code == 405));
     }
     
     #[test]
@@ -253,9 +252,10 @@ mod tests {
         
         match handler.handle(&request) {
             HandlerResult::Response(response) => {
-                assert_eq!(response.status_code(), 404);
-                assert!(response.body().contains("404"));
-                assert!(response.body().contains("Not Found"));
+                let body = String::from_utf8_lossy(&response.body);
+                assert_eq!(response.status_code, 404);
+                assert!(body.contains("404"));
+                assert!(body.contains("Not Found"));
             }
             _ => panic!("Expected response"),
         }
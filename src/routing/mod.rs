@@ -1,5 +1,5 @@
 pub mod router;
 pub mod route;
 mod handler;
-mod redirections;
+pub mod redirections;
 
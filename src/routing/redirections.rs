@@ -1,5 +1,7 @@
 use crate::http::response::HttpResponse;
-use crate::http::request::HttpRequest;
+use crate::http::request::{HttpRequest, Method};
+use crate::http::date;
+use crate::routing::route::Route;
 use std::collections::HashMap;
 use std::fmt;
 
@@ -449,6 +451,18 @@ impl Default for CorsSettings {
     }
 }
 
+impl CorsSettings {
+    /// Whether requests from `origin` may read responses
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+    
+    /// Whether a request header may be sent; header names are case-insensitive
+    pub fn allows_header(&self, name: &str) -> bool {
+        self.allowed_headers.iter().any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(name))
+    }
+}
+
 /// Security headers configuration
 #[derive(Debug, Clone)]
 pub struct SecurityHeaders {
@@ -547,7 +561,7 @@ pub struct RequestModifications {
 }
 
 /// Route settings processor
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RouteSettingsProcessor {
     /// Default settings
     default_settings: RouteSettings,
@@ -587,47 +601,89 @@ impl RouteSettingsProcessor {
         self.apply_response_modifications(response, &settings.modifications);
     }
     
-    /// Apply CORS headers
-    fn apply_cors_headers(&self, response: &mut HttpResponse, cors: &CorsSettings, request: &HttpRequest) {
-        // Access-Control-Allow-Origin
-        let origin = if cors.allowed_origins.contains(&"*".to_string()) {
-            "*"
-        } else if let Some(origin) = request.headers.get("Origin") {
-            if cors.allowed_origins.iter().any(|allowed| allowed == origin) {
-                origin
-            } else {
-                return; // Origin not allowed
-            }
-        } else {
-            return;
+    /// Apply CORS headers to the response of an actual (non-preflight)
+    /// request. Nothing is added unless the request names an allowed origin.
+    pub fn apply_cors_headers(&self, response: &mut HttpResponse, cors: &CorsSettings, request: &HttpRequest) {
+        let origin = match request.get_header("Origin") {
+            Some(origin) if cors.allows_origin(origin) => origin,
+            _ => return,
         };
         
-        response.set_header("Access-Control-Allow-Origin", origin);
+        self.apply_allow_origin(response, cors, origin);
         
-        // Access-Control-Allow-Methods
-        if !cors.allowed_methods.is_empty() {
-            response.set_header("Access-Control-Allow-Methods", &cors.allowed_methods.join(", "));
+        // Access-Control-Expose-Headers
+        if !cors.exposed_headers.is_empty() {
+            response.set_header("Access-Control-Expose-Headers", &cors.exposed_headers.join(", "));
         }
+    }
+    
+    /// Validate a CORS preflight request against the settings and the
+    /// methods the route serves, adding the preflight response headers.
+    /// Returns why the preflight was rejected when it is not allowed.
+    pub fn apply_preflight_headers(&self, response: &mut HttpResponse, cors: &CorsSettings, request: &HttpRequest, route: &Route) -> Result<(), String> {
+        // The answer depends on these request headers, so caches must key on them
+        response.add_vary("Origin");
+        response.add_vary("Access-Control-Request-Method");
+        response.add_vary("Access-Control-Request-Headers");
         
-        // Access-Control-Allow-Headers
-        if !cors.allowed_headers.is_empty() {
-            response.set_header("Access-Control-Allow-Headers", &cors.allowed_headers.join(", "));
+        let origin = request.get_header("Origin").unwrap_or("");
+        if !cors.allows_origin(origin) {
+            return Err(format!("origin '{}' is not allowed", origin));
         }
         
-        // Access-Control-Expose-Headers
-        if !cors.exposed_headers.is_empty() {
-            response.set_header("Access-Control-Expose-Headers", &cors.exposed_headers.join(", "));
+        // The method must be allowed for cross-origin use and served by the route
+        let requested_method = request.get_header("Access-Control-Request-Method").unwrap_or("").trim();
+        let method_allowed = cors.allowed_methods.iter().any(|allowed| allowed == requested_method)
+            && Method::from_str(requested_method).is_some_and(|method| route.allows_method(&method));
+        if !method_allowed {
+            return Err(format!("method '{}' is not allowed", requested_method));
         }
         
-        // Access-Control-Allow-Credentials
-        if cors.allow_credentials {
-            response.set_header("Access-Control-Allow-Credentials", "true");
+        let requested_list = request.headers.get_combined("Access-Control-Request-Headers").unwrap_or_default();
+        let requested_headers: Vec<&str> = requested_list.split(',')
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .collect();
+        if let Some(name) = requested_headers.iter().find(|name| !cors.allows_header(name)) {
+            return Err(format!("header '{}' is not allowed", name));
+        }
+        
+        self.apply_allow_origin(response, cors, origin);
+        
+        // Access-Control-Allow-Methods: the allowed methods this route serves
+        let methods: Vec<&str> = cors.allowed_methods.iter()
+            .filter(|name| Method::from_str(name).is_some_and(|method| route.allows_method(&method)))
+            .map(|name| name.as_str())
+            .collect();
+        response.set_header("Access-Control-Allow-Methods", &methods.join(", "));
+        
+        // Access-Control-Allow-Headers: echo the validated request headers
+        if !requested_headers.is_empty() {
+            response.set_header("Access-Control-Allow-Headers", &requested_headers.join(", "));
         }
         
         // Access-Control-Max-Age
         if let Some(max_age) = cors.max_age {
             response.set_header("Access-Control-Max-Age", &max_age.to_string());
         }
+        
+        Ok(())
+    }
+    
+    /// Access-Control-Allow-Origin and Access-Control-Allow-Credentials.
+    /// A wildcard cannot be combined with credentials, so the origin is
+    /// echoed back instead whenever credentials are allowed.
+    fn apply_allow_origin(&self, response: &mut HttpResponse, cors: &CorsSettings, origin: &str) {
+        if cors.allowed_origins.iter().any(|allowed| allowed == "*") && !cors.allow_credentials {
+            response.set_header("Access-Control-Allow-Origin", "*");
+        } else {
+            response.set_header("Access-Control-Allow-Origin", origin);
+            response.add_vary("Origin");
+        }
+        
+        if cors.allow_credentials {
+            response.set_header("Access-Control-Allow-Credentials", "true");
+        }
     }
     
     /// Apply security headers
@@ -665,9 +721,7 @@ impl RouteSettingsProcessor {
         
         if let Some(expires) = cache.expires {
             let expires_time = std::time::SystemTime::now() + std::time::Duration::from_secs(expires as u64);
-            if let Ok(duration) = expires_time.duration_since(std::time::UNIX_EPOCH) {
-                response.set_header("Expires", &format_http_date(duration.as_secs()));
-            }
+            response.set_header("Expires", &date::format_system_time(expires_time));
         }
     }
     
@@ -749,12 +803,6 @@ fn html_escape(text: &str) -> String {
         .replace('\'', "&#x27;")
 }
 
-/// Format HTTP date
-fn format_http_date(timestamp: u64) -> String {
-    // Simple HTTP date formatting
-    format!("{}", timestamp)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -819,6 +867,63 @@ mod tests {
         assert_eq!(cors.max_age, Some(86400));
     }
     
+    #[test]
+    fn test_cors_preflight() {
+        use crate::routing::route::RouteConfig;
+        
+        let cors = CorsSettings {
+            allowed_origins: vec!["https://app.local".to_string()],
+            allowed_methods: vec!["GET".to_string(), "PUT".to_string(), "DELETE".to_string()],
+            allowed_headers: vec!["Content-Type".to_string()],
+            exposed_headers: vec!["ETag".to_string()],
+            allow_credentials: true,
+            max_age: Some(600),
+            enabled: true,
+        };
+        let config = RouteConfig {
+            allowed_methods: [Method::GET, Method::PUT].into_iter().collect(),
+            ..Default::default()
+        };
+        let route = Route::new(config);
+        let processor = RouteSettingsProcessor::new(RouteSettings::default());
+        
+        let preflight = |headers: &[(&str, &str)]| {
+            let request = HttpRequest::with_headers(Method::OPTIONS, "/", headers);
+            let mut response = HttpResponse::new(204);
+            processor.apply_preflight_headers(&mut response, &cors, &request, &route).map(|_| response)
+        };
+        
+        let response = preflight(&[
+            ("Origin", "https://app.local"),
+            ("Access-Control-Request-Method", "PUT"),
+            ("Access-Control-Request-Headers", "content-type"),
+        ]).unwrap();
+        assert_eq!(response.headers.get("Access-Control-Allow-Origin"), Some("https://app.local"));
+        assert_eq!(response.headers.get("Access-Control-Allow-Credentials"), Some("true"));
+        assert_eq!(response.headers.get("Access-Control-Allow-Methods"), Some("GET, PUT"));
+        assert_eq!(response.headers.get("Access-Control-Allow-Headers"), Some("content-type"));
+        assert_eq!(response.headers.get("Access-Control-Max-Age"), Some("600"));
+        
+        // Wrong origin, a method the route does not serve, a header not allowed
+        assert!(preflight(&[("Origin", "https://evil.local"), ("Access-Control-Request-Method", "GET")]).is_err());
+        assert!(preflight(&[("Origin", "https://app.local"), ("Access-Control-Request-Method", "DELETE")]).is_err());
+        assert!(preflight(&[("Origin", "https://app.local"), ("Access-Control-Request-Method", "put")]).is_err());
+        assert!(preflight(&[
+            ("Origin", "https://app.local"),
+            ("Access-Control-Request-Method", "GET"),
+            ("Access-Control-Request-Headers", "Content-Type, X-Secret"),
+        ]).is_err());
+        
+        // Actual requests only get the origin, credential and exposed headers
+        let request = HttpRequest::with_headers(Method::GET, "/", &[("Origin", "https://app.local")]);
+        let mut response = HttpResponse::ok();
+        processor.apply_cors_headers(&mut response, &cors, &request);
+        assert_eq!(response.headers.get("Access-Control-Allow-Origin"), Some("https://app.local"));
+        assert_eq!(response.headers.get("Access-Control-Expose-Headers"), Some("ETag"));
+        assert_eq!(response.headers.get("Vary"), Some("Origin"));
+        assert!(response.headers.get("Access-Control-Allow-Methods").is_none());
+    }
+    
     #[test]
     fn test_security_headers() {
         let security = SecurityHeaders::default();
@@ -13,8 +13,9 @@ pub struct RouteConfig {
     /// Document root for this route (overrides server default)
     pub document_root: Option<PathBuf>,
     
-    /// Default file to serve for directories
-    pub index_file: Option<String>,
+    /// Files to serve for directories, tried in order (empty for the
+    /// server default)
+    pub index_files: Vec<String>,
     
     /// Enable directory listing
     pub directory_listing: bool,
@@ -22,12 +23,24 @@ pub struct RouteConfig {
     /// HTTP redirection target
     pub redirect: Option<String>,
     
+    /// Status code for the redirection (defaults to 302)
+    pub redirect_status: Option<u16>,
+    
     /// CGI extension mapping (e.g., ".php" -> "/usr/bin/php")
     pub cgi_extension: Option<String>,
     
+    /// Directory CGI scripts for this route are executed from
+    pub cgi_directory: Option<PathBuf>,
+    
     /// Maximum request body size for uploads
     pub max_body_size: Option<usize>,
     
+    /// Directory PUT writes into (defaults to the global upload directory)
+    pub upload_dir: Option<PathBuf>,
+    
+    /// Serve the session endpoints below the route path
+    pub sessions: bool,
+    
     /// Custom error pages for this route
     pub error_pages: std::collections::HashMap<u16, PathBuf>,
 }
@@ -42,11 +55,15 @@ impl Default for RouteConfig {
             path: "/".to_string(),
             allowed_methods,
             document_root: None,
-            index_file: Some("index.html".to_string()),
+            index_files: vec!["index.html".to_string()],
             directory_listing: false,
             redirect: None,
+            redirect_status: None,
             cgi_extension: None,
+            cgi_directory: None,
             max_body_size: Some(1024 * 1024), // 1MB default
+            upload_dir: None,
+            sessions: false,
             error_pages: std::collections::HashMap::new(),
         }
     }
@@ -100,9 +117,19 @@ impl Route {
         route_path == "/"
     }
     
-    /// Check if the given HTTP method is allowed for this route
+    /// Check if the given HTTP method is allowed for this route.
+    /// HEAD is always allowed where GET is.
     pub fn allows_method(&self, method: &Method) -> bool {
         self.config.allowed_methods.contains(method)
+            || (*method == Method::HEAD && self.config.allowed_methods.contains(&Method::GET))
+    }
+    
+    /// Methods answered on this route: the configured ones plus OPTIONS,
+    /// which the router serves for every route
+    pub fn answered_methods(&self) -> HashSet<Method> {
+        let mut methods = self.config.allowed_methods.clone();
+        methods.insert(Method::OPTIONS);
+        methods
     }
     
     /// Get the document root for this route
@@ -110,9 +137,9 @@ impl Route {
         self.config.document_root.as_ref()
     }
     
-    /// Get the index file for this route
-    pub fn index_file(&self) -> Option<&str> {
-        self.config.index_file.as_deref()
+    /// Get the index files for this route
+    pub fn index_files(&self) -> &[String] {
+        &self.config.index_files
     }
     
     /// Check if directory listing is enabled
@@ -125,16 +152,63 @@ impl Route {
         self.config.redirect.as_deref()
     }
     
+    /// Get redirect status code if configured
+    pub fn redirect_status(&self) -> Option<u16> {
+        self.config.redirect_status
+    }
+    
     /// Get CGI extension if configured
     pub fn cgi_extension(&self) -> Option<&str> {
         self.config.cgi_extension.as_deref()
     }
     
+    /// Get the CGI script directory if configured
+    pub fn cgi_directory(&self) -> Option<&PathBuf> {
+        self.config.cgi_directory.as_ref()
+    }
+    
+    /// Check if this route executes CGI scripts
+    pub fn is_cgi(&self) -> bool {
+        self.config.cgi_extension.is_some() || self.config.cgi_directory.is_some()
+    }
+    
+    /// Map a request path onto the route's CGI directory, stripping the
+    /// route prefix ("/cgi-bin/test.py" -> "<cgi_directory>/test.py")
+    pub fn cgi_script_path(&self, request_path: &str) -> Option<PathBuf> {
+        let dir = self.config.cgi_directory.as_ref()?;
+        let prefix = self.config.path.trim_end_matches("/*").trim_end_matches('/');
+        let relative = request_path.strip_prefix(prefix).unwrap_or(request_path);
+        Some(dir.join(relative.trim_start_matches('/')))
+    }
+    
     /// Get maximum body size for this route
     pub fn max_body_size(&self) -> Option<usize> {
         self.config.max_body_size
     }
     
+    /// Get the upload directory if the route has its own
+    pub fn upload_dir(&self) -> Option<&PathBuf> {
+        self.config.upload_dir.as_ref()
+    }
+    
+    /// Check if this route is backed by an upload directory: routes that
+    /// accept PUT also serve GET and DELETE from where PUT writes
+    pub fn accepts_uploads(&self) -> bool {
+        self.config.allowed_methods.contains(&Method::PUT) || self.config.upload_dir.is_some()
+    }
+    
+    /// Check if this route serves the session endpoints
+    pub fn serves_sessions(&self) -> bool {
+        self.config.sessions
+    }
+    
+    /// The part of a request path below the route prefix
+    /// ("/uploads/a.txt" on "/uploads/*" -> "a.txt")
+    pub fn relative_path<'a>(&self, request_path: &'a str) -> Option<&'a str> {
+        let prefix = self.config.path.trim_end_matches("/*").trim_end_matches('/');
+        request_path.strip_prefix(prefix)?.strip_prefix('/')
+    }
+    
     /// Get custom error page for a status code
     pub fn error_page(&self, status_code: u16) -> Option<&PathBuf> {
         self.config.error_pages.get(&status_code)
@@ -157,8 +231,10 @@ mod tests {
     
     #[test]
     fn test_route_matching() {
-        let mut config = RouteConfig::default();
-        config.path = "/api".to_string();
+        let config = RouteConfig {
+            path: "/api".to_string(),
+            ..Default::default()
+        };
         let route = Route::new(config);
         
         assert!(route.matches("/api"));
@@ -191,9 +267,41 @@ mod tests {
     }
     
     #[test]
-    fn test_exact_matching() {
+    fn test_head_allowed_with_get() {
         let mut config = RouteConfig::default();
-        config.path = "/exact".to_string();
+        config.allowed_methods.clear();
+        config.allowed_methods.insert(Method::GET);
+        let route = Route::new(config);
+        
+        assert!(route.allows_method(&Method::HEAD));
+    }
+    
+    #[test]
+    fn test_cgi_script_path() {
+        let config = RouteConfig {
+            path: "/cgi-bin/*".to_string(),
+            cgi_directory: Some(PathBuf::from("./www/cgi-bin")),
+            ..Default::default()
+        };
+        let route = Route::new(config);
+        
+        assert!(route.is_cgi());
+        assert_eq!(
+            route.cgi_script_path("/cgi-bin/test.py"),
+            Some(PathBuf::from("./www/cgi-bin/test.py"))
+        );
+        
+        let plain = Route::new(RouteConfig::default());
+        assert!(!plain.is_cgi());
+        assert_eq!(plain.cgi_script_path("/cgi-bin/test.py"), None);
+    }
+    
+    #[test]
+    fn test_exact_matching() {
+        let config = RouteConfig {
+            path: "/exact".to_string(),
+            ..Default::default()
+        };
         let route = Route::new(config);
         
         assert!(route.matches("/exact"));
@@ -1,5 +1,7 @@
 use crate::routing::route::{Route, RouteConfig};
 use crate::routing::handler::{Handler, HandlerResult, MethodFilterHandler, RedirectHandler, ErrorHandler};
+use crate::routing::redirections::{CorsSettings, RouteSettings, RouteSettingsProcessor};
+use crate::http::conditional::Validators;
 use crate::http::request::{HttpRequest, Method};
 use crate::http::response::HttpResponse;
 use crate::upload::multipart::{MultipartParser, FieldType};
@@ -10,7 +12,7 @@ use crate::cgi::{CgiExecutor, CgiConfig};
 use crate::mime::MimeTypes;
 use std::collections::HashMap;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Virtual host configuration
 #[derive(Debug, Clone)]
@@ -25,6 +27,8 @@ pub struct VirtualHost {
     pub error_pages: HashMap<u16, String>,
     /// Default maximum body size
     pub max_body_size: usize,
+    /// Cross-origin resource sharing settings
+    pub cors: CorsSettings,
 }
 
 impl Default for VirtualHost {
@@ -35,6 +39,7 @@ impl Default for VirtualHost {
             document_root: "./www".to_string(),
             error_pages: HashMap::new(),
             max_body_size: 1024 * 1024, // 1MB
+            cors: CorsSettings::default(),
         }
     }
 }
@@ -54,6 +59,8 @@ pub struct Router {
     cgi_executor: CgiExecutor,
     /// MIME type resolver
     mime_types: MimeTypes,
+    /// Applies CORS headers to responses
+    settings_processor: RouteSettingsProcessor,
 }
 
 impl Router {
@@ -77,9 +84,15 @@ impl Router {
             session_store,
             cgi_executor,
             mime_types,
+            settings_processor: RouteSettingsProcessor::new(RouteSettings::default()),
         }
     }
     
+    /// Replace the file storage used for uploads
+    pub fn set_file_storage(&mut self, file_storage: FileStorage) {
+        self.file_storage = file_storage;
+    }
+    
     /// Get MIME type for a file path
     fn get_mime_type(&self, path: &Path) -> &'static str {
         self.mime_types.get_mime_type(path)
@@ -99,42 +112,40 @@ impl Router {
     
     /// Route an HTTP request and generate a response
     pub fn route_request(&mut self, request: &HttpRequest) -> io::Result<HttpResponse> {
-        // Parse cookies from request
-        let cookies = if let Some(cookie_header) = request.get_header("Cookie") {
-            CookieJar::parse_cookie_header(cookie_header)
-        } else {
-            CookieJar::new()
-        };
-        
         // Cleanup expired sessions periodically
         self.session_store.cleanup_expired_sessions();
         
         // Determine which virtual host to use and clone the necessary data
-        let (vhost_name, document_root, error_pages, routes) = {
-            let vhost = self.select_virtual_host(request);
-            (vhost.server_name.clone(), vhost.document_root.clone(), 
-             vhost.error_pages.clone(), vhost.routes.clone())
-        };
-        
-        // Create a temporary virtual host for processing
-        let temp_vhost = VirtualHost {
-            server_name: vhost_name,
-            document_root,
-            error_pages,
-            routes,
-            max_body_size: 1024 * 1024, // Default
-        };
+        let temp_vhost = self.select_virtual_host(request).clone();
         
         // Find matching route
         let route = self.find_matching_route(&temp_vhost, request.path());
         
-        // Process request through handler chain with session support
-        let mut response = self.process_request_with_route(request, &route, &temp_vhost)?;
-        
-        // Handle session-specific routes
-        self.handle_session_routes(request, &mut response, &cookies, &route, &temp_vhost)?;
-        
-        Ok(response)
+        // Process request through handler chain
+        self.process_request_with_route(request, &route, &temp_vhost)
+    }
+    
+    /// Find the route that would handle a request, without processing it
+    pub fn match_route(&self, request: &HttpRequest) -> Route {
+        let vhost = self.select_virtual_host(request);
+        self.find_matching_route(vhost, request.path())
+    }
+    
+    /// Generate an error response for a request, using the custom error
+    /// page of its route or virtual host when one is configured
+    pub fn error_response(&mut self, status_code: u16, request: &HttpRequest) -> io::Result<HttpResponse> {
+        let vhost = self.select_virtual_host(request).clone();
+        let route = self.find_matching_route(&vhost, request.path());
+        self.generate_error_response(status_code, &route, &vhost)
+    }
+    
+    /// Add CORS headers to the response of a cross-origin request when the
+    /// virtual host enables CORS. Preflight responses already carry theirs.
+    pub fn apply_cors_headers(&self, request: &HttpRequest, response: &mut HttpResponse) {
+        let vhost = self.select_virtual_host(request);
+        if vhost.cors.enabled && !response.headers.contains_key("Access-Control-Allow-Origin") {
+            self.settings_processor.apply_cors_headers(response, &vhost.cors, request);
+        }
     }
     
     /// Select the appropriate virtual host based on the Host header
@@ -187,17 +198,29 @@ impl Router {
             Route::new(config.clone())
         } else {
             // Create default route if no match found
-            let mut default_config = RouteConfig::default();
-            default_config.document_root = Some(vhost.document_root.clone().into());
+            let default_config = RouteConfig {
+                document_root: Some(vhost.document_root.clone().into()),
+                ..Default::default()
+            };
             Route::new(default_config)
         }
     }
     
     /// Process request through the handler chain for a specific route
     fn process_request_with_route(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
+        // Methods this server does not recognize cannot be allowed anywhere
+        if !request.method().is_standard() {
+            return self.generate_error_response(501, route, vhost);
+        }
+        
+        // OPTIONS is answered for every route, whatever methods it lists
+        if *request.method() == Method::OPTIONS {
+            return self.handle_options_request(request, route, vhost);
+        }
+        
         // Check if method is allowed
-        if !route.allows_method(&request.method()) {
-            let mut handler = MethodFilterHandler::new(route.config().allowed_methods.clone());
+        if !route.allows_method(request.method()) {
+            let mut handler = MethodFilterHandler::new(route.answered_methods());
             match handler.handle(request) {
                 HandlerResult::Response(response) => return Ok(response),
                 HandlerResult::Error(e) => return Err(e),
@@ -207,7 +230,7 @@ impl Router {
         
         // Check for redirect
         if let Some(redirect_target) = route.redirect_target() {
-            let mut handler = RedirectHandler::new(redirect_target.to_string(), None);
+            let mut handler = RedirectHandler::new(redirect_target.to_string(), route.redirect_status());
             match handler.handle(request) {
                 HandlerResult::Response(response) => return Ok(response),
                 HandlerResult::Error(e) => return Err(e),
@@ -215,6 +238,11 @@ impl Router {
             }
         }
         
+        // Session routes answer their endpoints for every allowed method
+        if route.serves_sessions() {
+            return self.handle_session_request(request, route, vhost);
+        }
+        
         // Handle different request types
         match request.method() {
             Method::GET | Method::HEAD => {
@@ -223,44 +251,89 @@ impl Router {
             Method::POST => {
                 self.handle_post_request(request, route, vhost)
             }
+            Method::PUT => {
+                self.handle_put_request(request, route, vhost)
+            }
             Method::DELETE => {
                 self.handle_delete_request(request, route, vhost)
             }
-            Method::PUT | Method::OPTIONS => {
-                // Return 405 Method Not Allowed for unsupported methods
-                self.generate_error_response(405, route, vhost)
+            _ => {
+                // Allowed by the route, but there is no handler for it
+                self.generate_error_response(501, route, vhost)
             }
         }
     }
     
+    /// Handle OPTIONS requests: CORS preflights are validated against the
+    /// virtual host's CORS settings, anything else gets the `Allow` set of
+    /// the route, or of the whole server for `OPTIONS *`
+    fn handle_options_request(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
+        let is_preflight = vhost.cors.enabled
+            && request.path() != "*"
+            && request.get_header("Origin").is_some()
+            && request.get_header("Access-Control-Request-Method").is_some();
+        
+        if is_preflight {
+            let mut response = HttpResponse::new(204);
+            return match self.settings_processor.apply_preflight_headers(&mut response, &vhost.cors, request, route) {
+                Ok(()) => Ok(response),
+                Err(reason) => {
+                    println!("🚫 CORS preflight for {} rejected: {}", request.path(), reason);
+                    self.generate_error_response(403, route, vhost)
+                }
+            };
+        }
+        
+        let allow = if request.path() == "*" {
+            let methods: Vec<Method> = vhost.routes.iter()
+                .flat_map(|config| config.allowed_methods.iter().cloned())
+                .chain([Method::OPTIONS])
+                .collect();
+            Method::allow_header(&methods)
+        } else {
+            Method::allow_header(&route.answered_methods())
+        };
+        
+        let mut response = HttpResponse::new(204);
+        response.set_header("Allow", &allow);
+        Ok(response)
+    }
+    
     /// Handle GET and HEAD requests (static files, directory listing, CGI)
     fn handle_get_request(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
         let path = request.path();
         
-        // Special handling for /uploads/* paths - serve from uploads directory
-        let file_path = if path.starts_with("/uploads/") {
-            let filename = path.trim_start_matches("/uploads/");
-            self.file_storage.config().upload_dir.join(filename)
+        // CGI routes run the script the path maps to, and nothing else
+        if route.is_cgi() {
+            let script_path = self.cgi_script_path(path, route, vhost);
+            if script_path.is_file() && self.cgi_executor.is_cgi_script(&script_path) {
+                return self.execute_cgi_script(request, &script_path, route, vhost);
+            }
+            return self.generate_error_response(404, route, vhost);
+        }
+        
+        // Upload routes serve what PUT stored, other routes the document root
+        let file_path = if route.accepts_uploads() {
+            let filename = route.relative_path(path).unwrap_or("");
+            match self.upload_storage(route).put_path(filename) {
+                Ok(file_path) => file_path,
+                Err(_) => return self.generate_error_response(404, route, vhost),
+            }
         } else {
             Path::new(&vhost.document_root).join(path.trim_start_matches('/'))
         };
         
-        // Check if this is a CGI script
-        if self.cgi_executor.is_cgi_script(&file_path) {
-            return self.execute_cgi_script(request, &file_path, route, vhost);
-        }
-        
         // Check if file exists for static serving
         if file_path.exists() && file_path.is_file() {
-            // Read file content
-            use std::fs;
-            let content = fs::read(&file_path)?;
+            // Open the file; the connection streams its contents
+            let file = std::fs::File::open(&file_path)?;
+            let length = file.metadata()?.len();
             
             // Determine MIME type
             let mime_type = self.get_mime_type(&file_path);
             
             let mut response = HttpResponse::ok();
-            response.set_body(&content);
+            response.set_file_body(std::sync::Arc::new(file), 0, length);
             response.set_header("Content-Type", mime_type);
             return Ok(response);
         }
@@ -271,21 +344,21 @@ impl Router {
     
     /// Handle POST requests (uploads, form processing, CGI)
     fn handle_post_request(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
-        // Check body size limit
-        if let Some(max_size) = route.max_body_size() {
-            if let Some(content_length) = request.content_length() {
-                if content_length > max_size {
-                    return self.generate_error_response(413, route, vhost);
-                }
-            }
+        // The parser rejects oversized bodies as soon as the headers are in;
+        // this covers requests that did not come through it
+        let max_size = route.max_body_size().unwrap_or(vhost.max_body_size);
+        let body_size = request.content_length().unwrap_or(request.body.len());
+        if body_size > max_size {
+            return self.generate_error_response(413, route, vhost);
         }
         
-        // Check if this is a CGI script
-        let path = request.path();
-        let file_path = Path::new(&vhost.document_root).join(path.trim_start_matches('/'));
-        
-        if self.cgi_executor.is_cgi_script(&file_path) {
-            return self.execute_cgi_script(request, &file_path, route, vhost);
+        // CGI routes run the script the path maps to
+        if route.is_cgi() {
+            let script_path = self.cgi_script_path(request.path(), route, vhost);
+            if script_path.is_file() && self.cgi_executor.is_cgi_script(&script_path) {
+                return self.execute_cgi_script(request, &script_path, route, vhost);
+            }
+            return self.generate_error_response(404, route, vhost);
         }
         
         // Get request body
@@ -308,7 +381,7 @@ impl Router {
     }
     
     /// Handle multipart form data with file uploads
-    fn handle_multipart_upload(&mut self, request: &HttpRequest, body: &[u8], content_type: &str, route: &Route) -> io::Result<HttpResponse> {
+    fn handle_multipart_upload(&mut self, _request: &HttpRequest, body: &[u8], content_type: &str, route: &Route) -> io::Result<HttpResponse> {
         // Extract boundary from Content-Type header
         let boundary = self.extract_boundary(content_type)?;
         
@@ -402,8 +475,7 @@ impl Router {
     fn extract_boundary(&self, content_type: &str) -> io::Result<String> {
         for part in content_type.split(';') {
             let part = part.trim();
-            if part.starts_with("boundary=") {
-                let boundary = &part[9..]; // Skip "boundary="
+            if let Some(boundary) = part.strip_prefix("boundary=") {
                 return Ok(boundary.trim_matches('"').to_string());
             }
         }
@@ -414,25 +486,89 @@ impl Router {
         ))
     }
     
-    /// Handle DELETE requests
-    fn handle_delete_request(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
-        let path = request.path();
+    /// Handle PUT requests: create or replace a file in the upload directory.
+    /// Answers 201 with a Location for a new file and 204 for a replaced one.
+    fn handle_put_request(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
+        // Check body size limit, falling back to the virtual host's limit
+        let max_size = route.max_body_size().unwrap_or(vhost.max_body_size);
+        let body_size = request.content_length().unwrap_or(request.body.len());
+        if body_size > max_size || body_size > self.file_storage.config().max_file_size {
+            return self.generate_error_response(413, route, vhost);
+        }
         
-        // Security check: only allow deletion in upload directory or specific paths
-        if !path.starts_with("/uploads/") && !path.starts_with("/delete/") {
-            return self.generate_error_response(403, route, vhost);
+        // Files go to the route's upload directory, else the global one,
+        // named by the path below the route prefix
+        let storage = self.upload_storage(route);
+        let filename = route.relative_path(request.path()).unwrap_or("");
+        let file_path = match storage.put_path(filename) {
+            Ok(path) => path,
+            Err(_) => return self.generate_error_response(400, route, vhost),
+        };
+        if storage.check_extension(filename).is_err() {
+            return self.generate_error_response(415, route, vhost);
         }
         
-        // Extract filename from path
-        let filename = path.trim_start_matches("/uploads/").trim_start_matches("/delete/");
+        // If-Match needs a current representation to match; If-None-Match: *
+        // asks that the file be created only if it does not exist yet
+        let create_only = request.get_header("If-None-Match").is_some_and(|value| value.trim() == "*");
+        match std::fs::metadata(&file_path) {
+            Ok(metadata) if metadata.is_file() => {
+                if let Some(status) = Validators::for_file(&metadata).evaluate(request).status_code() {
+                    return self.generate_error_response(status, route, vhost);
+                }
+            }
+            Ok(_) => return self.generate_error_response(409, route, vhost),
+            Err(_) if request.get_header("If-Match").is_some() => {
+                return self.generate_error_response(412, route, vhost);
+            }
+            Err(_) => {}
+        }
         
+        let body = request.body().unwrap_or(&[]);
+        match storage.put_file(filename, body, create_only) {
+            Ok(true) => {
+                let location = request.path();
+                println!("📝 Created {} ({} bytes)", file_path.display(), body.len());
+                
+                let mut response = HttpResponse::new(201);
+                response.set_header("Location", location);
+                response.set_header("Content-Type", "text/plain");
+                response.set_body(format!("Created {}\n", location).as_bytes());
+                Ok(response)
+            }
+            Ok(false) => {
+                println!("📝 Replaced {} ({} bytes)", file_path.display(), body.len());
+                Ok(HttpResponse::new(204))
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                // Another request created the file after the precondition check
+                self.generate_error_response(412, route, vhost)
+            }
+            Err(e) => {
+                eprintln!("Failed to store '{}': {}", filename, e);
+                self.generate_error_response(500, route, vhost)
+            }
+        }
+    }
+    
+    /// Handle DELETE requests: remove a file from the route's upload directory
+    fn handle_delete_request(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
+        // Security check: only routes backed by an upload directory delete files
+        if !route.accepts_uploads() {
+            return self.generate_error_response(403, route, vhost);
+        }
+        
+        // The file is named by the path below the route prefix
+        let filename = route.relative_path(request.path()).unwrap_or("");
         if filename.is_empty() {
             return self.generate_error_response(400, route, vhost);
         }
         
-        // Construct file path in upload directory
-        let upload_dir = self.file_storage.config().upload_dir.clone();
-        let file_path = upload_dir.join(filename);
+        let storage = self.upload_storage(route);
+        let file_path = match storage.put_path(filename) {
+            Ok(file_path) => file_path,
+            Err(_) => return self.generate_error_response(400, route, vhost),
+        };
         
         // Check if file exists
         if !file_path.exists() {
@@ -440,7 +576,7 @@ impl Router {
         }
         
         // Attempt to delete the file
-        match self.file_storage.delete_file(&file_path) {
+        match storage.delete_file(&file_path) {
             Ok(()) => {
                 let mut response = HttpResponse::ok();
                 let response_body = format!("File '{}' deleted successfully", filename);
@@ -455,6 +591,21 @@ impl Router {
         }
     }
     
+    /// Storage for the route's upload directory, else the global one
+    fn upload_storage(&self, route: &Route) -> FileStorage {
+        match route.upload_dir() {
+            Some(dir) => self.file_storage.with_upload_dir(dir),
+            None => self.file_storage.clone(),
+        }
+    }
+    
+    /// Script a CGI route runs for a request path: the path below the route
+    /// prefix in its script directory, else the path in the document root
+    fn cgi_script_path(&self, path: &str, route: &Route, vhost: &VirtualHost) -> PathBuf {
+        route.cgi_script_path(path)
+            .unwrap_or_else(|| Path::new(&vhost.document_root).join(path.trim_start_matches('/')))
+    }
+    
     /// Generate an error response using custom error pages if available
     fn generate_error_response(&mut self, status_code: u16, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
         // Try route-specific error page first
@@ -487,123 +638,86 @@ impl Router {
         self.default_host.as_deref()
     }
     
-    /// Handle session-specific routes and add session cookies to response
-    fn handle_session_routes(&mut self, request: &HttpRequest, response: &mut HttpResponse, 
-                           cookies: &CookieJar, route: &Route, vhost: &VirtualHost) -> io::Result<()> {
-        let path = request.path();
-        
-        // Handle session creation endpoint
-        if path == "/session/create" {
-            let session = self.session_store.create_session()?;
-            let cookie = self.session_store.create_session_cookie(&session.id);
-            response.set_header("Set-Cookie", &cookie.to_header_value());
-            
-            let response_body = format!("Session created: {}", session.id);
-            response.set_body(response_body.as_bytes());
-            response.set_header("Content-Type", "text/plain");
-            return Ok(());
-        }
+    /// Answer the session endpoints below a session route's path, adding
+    /// session cookies to the response
+    fn handle_session_request(&mut self, request: &HttpRequest, route: &Route, vhost: &VirtualHost) -> io::Result<HttpResponse> {
+        let cookies = CookieJar::from_headers(&request.headers);
+        let endpoint = route.relative_path(request.path()).unwrap_or("");
+        let mut response = HttpResponse::ok();
+        response.set_header("Content-Type", "text/plain");
         
-        // Handle session info endpoint
-        if path == "/session/info" {
-            println!("DEBUG: Session info requested");
-            println!("DEBUG: Cookies in jar: {:?}", cookies.cookies());
-            
-            if let Some(session) = self.session_store.get_session_from_cookies(cookies) {
+        match endpoint {
+            "create" => {
+                let session = self.session_store.create_session()?;
+                let cookie = self.session_store.create_session_cookie(&session.id);
+                response.append_header("Set-Cookie", &cookie.to_header_value());
+                response.set_body(format!("Session created: {}", session.id).as_bytes());
+            }
+            "info" => {
+                if let Some(session) = self.session_store.get_session_from_cookies(&cookies) {
+                    let response_body = format!(
+                        "Session ID: {}\nCreated: {:?}\nLast Accessed: {:?}\nData: {} items",
+                        session.id,
+                        session.data.created_at(),
+                        session.data.last_accessed(),
+                        session.data.len()
+                    );
+                    response.set_body(response_body.as_bytes());
+                } else {
+                    response.set_body(b"No active session");
+                }
+            }
+            "destroy" => {
+                if let Some(session_id) = cookies.get_value(self.session_store.config().cookie_name.as_str()) {
+                    self.session_store.delete_session(session_id);
+                    let deletion_cookie = self.session_store.create_deletion_cookie();
+                    response.append_header("Set-Cookie", &deletion_cookie.to_header_value());
+                    response.set_body(b"Session destroyed");
+                } else {
+                    response.set_body(b"No session to destroy");
+                }
+            }
+            "stats" => {
+                let stats = self.session_store.get_stats();
                 let response_body = format!(
-                    "Session ID: {}\nCreated: {:?}\nLast Accessed: {:?}\nData: {} items",
-                    session.id,
-                    session.data.created_at(),
-                    session.data.last_accessed(),
-                    session.data.len()
+                    "Session Statistics:\nTotal: {}\nActive: {}\nExpired: {}",
+                    stats.total_sessions,
+                    stats.active_sessions,
+                    stats.expired_sessions
                 );
                 response.set_body(response_body.as_bytes());
-            } else {
-                println!("DEBUG: No session found in cookies");
-                response.set_body(b"No active session");
-            }
-            response.set_header("Content-Type", "text/plain");
-            return Ok(());
-        }
-        
-        // Handle session destruction endpoint
-        if path == "/session/destroy" {
-            if let Some(session_id) = cookies.get_value(self.session_store.config().cookie_name.as_str()) {
-                self.session_store.delete_session(session_id);
-                let deletion_cookie = self.session_store.create_deletion_cookie();
-                response.set_header("Set-Cookie", &deletion_cookie.to_header_value());
-                response.set_body(b"Session destroyed");
-            } else {
-                response.set_body(b"No session to destroy");
-            }
-            response.set_header("Content-Type", "text/plain");
-            return Ok(());
-        }
-        
-        // Handle session data endpoints
-        if path.starts_with("/session/set/") {
-            if let Some(mut session) = self.session_store.get_session_from_cookies(cookies) {
-                // Extract key from path: /session/set/key_name
-                if let Some(key) = path.strip_prefix("/session/set/") {
-                    if !key.is_empty() {
-                        // For POST requests, use body as value
-                        if request.method() == &crate::http::request::Method::POST {
-                            if let Some(body) = request.body() {
-                                let value = String::from_utf8_lossy(body).to_string();
-                                session.data.set(key.to_string(), value.clone());
-                                self.session_store.update_session(session)?;
-                                
-                                let response_body = format!("Set session[{}] = {}", key, value);
-                                response.set_body(response_body.as_bytes());
-                                response.set_header("Content-Type", "text/plain");
-                                return Ok(());
-                            }
-                        }
-                    }
-                }
             }
-            response.set_body(b"Failed to set session data");
-            response.set_header("Content-Type", "text/plain");
-            return Ok(());
-        }
-        
-        if path.starts_with("/session/get/") {
-            if let Some(mut session) = self.session_store.get_session_from_cookies(cookies) {
-                // Extract key from path: /session/get/key_name
-                if let Some(key) = path.strip_prefix("/session/get/") {
-                    if !key.is_empty() {
-                        if let Some(value) = session.data.get(key) {
-                            let value_bytes = value.as_bytes().to_vec();
+            _ => {
+                if let Some(key) = endpoint.strip_prefix("set/").filter(|key| !key.is_empty()) {
+                    // For POST requests, use body as value
+                    let session = self.session_store.get_session_from_cookies(&cookies);
+                    match (session, request.body()) {
+                        (Some(mut session), Some(body)) if *request.method() == Method::POST => {
+                            let value = String::from_utf8_lossy(body).to_string();
+                            session.data.set(key.to_string(), value.clone());
                             self.session_store.update_session(session)?;
-                            response.set_body(&value_bytes);
-                        } else {
-                            response.set_body(b"Key not found in session");
+                            response.set_body(format!("Set session[{}] = {}", key, value).as_bytes());
                         }
-                        response.set_header("Content-Type", "text/plain");
-                        return Ok(());
+                        _ => response.set_body(b"Failed to set session data"),
                     }
+                } else if let Some(key) = endpoint.strip_prefix("get/").filter(|key| !key.is_empty()) {
+                    match self.session_store.get_session_from_cookies(&cookies) {
+                        Some(mut session) => match session.data.get(key).map(|value| value.as_bytes().to_vec()) {
+                            Some(value_bytes) => {
+                                self.session_store.update_session(session)?;
+                                response.set_body(&value_bytes);
+                            }
+                            None => response.set_body(b"Key not found in session"),
+                        },
+                        None => response.set_body(b"No session or invalid key"),
+                    }
+                } else {
+                    return self.generate_error_response(404, route, vhost);
                 }
             }
-            response.set_body(b"No session or invalid key");
-            response.set_header("Content-Type", "text/plain");
-            return Ok(());
-        }
-        
-        // Handle session statistics endpoint
-        if path == "/session/stats" {
-            let stats = self.session_store.get_stats();
-            let response_body = format!(
-                "Session Statistics:\nTotal: {}\nActive: {}\nExpired: {}",
-                stats.total_sessions,
-                stats.active_sessions,
-                stats.expired_sessions
-            );
-            response.set_body(response_body.as_bytes());
-            response.set_header("Content-Type", "text/plain");
-            return Ok(());
         }
         
-        Ok(())
+        Ok(response)
     }
     
     /// Execute CGI script and return response
@@ -647,23 +761,28 @@ impl Default for Router {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashSet;
     
     #[test]
     fn test_virtual_host_selection() {
         let mut router = Router::new();
         
-        let mut vhost1 = VirtualHost::default();
-        vhost1.server_name = "example.com".to_string();
+        let vhost1 = VirtualHost {
+            server_name: "example.com".to_string(),
+            ..Default::default()
+        };
         router.add_virtual_host(vhost1);
         
-        let mut vhost2 = VirtualHost::default();
-        vhost2.server_name = "test.com".to_string();
+        let vhost2 = VirtualHost {
+            server_name: "test.com".to_string(),
+            ..Default::default()
+        };
         router.add_virtual_host(vhost2);
         
-        // Test Host header matching
-        let mut request = HttpRequest::new();
-        // Note: We'd need a way to set headers on HttpRequest for proper testing
+        // Test Host header matching, with and without a port
+        let request = HttpRequest::with_headers(Method::GET, "/", &[("Host", "test.com:8080")]);
+        assert_eq!(router.select_virtual_host(&request).server_name, "test.com");
+        let request = HttpRequest::with_headers(Method::GET, "/", &[("Host", "other.com")]);
+        assert_eq!(router.select_virtual_host(&request).server_name, "example.com");
         
         assert_eq!(router.default_host(), Some("example.com"));
     }
@@ -689,4 +808,295 @@ mod tests {
         let result = router.route_request(&request);
         assert!(result.is_ok());
     }
+    
+    #[test]
+    fn test_unknown_and_disallowed_methods() {
+        let api_route = RouteConfig {
+            path: "/api".to_string(),
+            allowed_methods: [Method::GET, Method::POST, Method::PATCH].into_iter().collect(),
+            ..Default::default()
+        };
+        
+        let mut vhost = VirtualHost::default();
+        vhost.routes.push(api_route);
+        
+        let mut router = Router::new();
+        router.add_virtual_host(vhost);
+        
+        let mut request = HttpRequest::new();
+        request.path = "/api".to_string();
+        
+        // Known but not allowed: 405 listing what is
+        request.method = Method::DELETE;
+        let response = router.route_request(&request).unwrap();
+        assert_eq!(response.status_code, 405);
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD, POST, PATCH, OPTIONS"));
+        
+        // Unknown to the server: 501
+        request.method = Method::Extension("PROPFIND".to_string());
+        assert_eq!(router.route_request(&request).unwrap().status_code, 501);
+        
+        // Allowed by the route, but nothing serves it
+        request.method = Method::PATCH;
+        assert_eq!(router.route_request(&request).unwrap().status_code, 501);
+    }
+    
+    #[test]
+    fn test_options_and_cors_preflight() {
+        let api_route = RouteConfig {
+            path: "/api".to_string(),
+            allowed_methods: [Method::GET, Method::PUT].into_iter().collect(),
+            ..Default::default()
+        };
+        
+        let mut vhost = VirtualHost {
+            routes: vec![api_route],
+            ..Default::default()
+        };
+        vhost.cors.enabled = true;
+        vhost.cors.allowed_origins = vec!["https://app.local".to_string()];
+        
+        let mut router = Router::new();
+        router.add_virtual_host(vhost);
+        
+        let mut options = |path: &str, headers: &[(&str, &str)]| {
+            router.route_request(&HttpRequest::with_headers(Method::OPTIONS, path, headers)).unwrap()
+        };
+        
+        // Plain OPTIONS lists what the route answers, even though OPTIONS is not configured
+        let response = options("/api", &[]);
+        assert_eq!(response.status_code, 204);
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD, PUT, OPTIONS"));
+        
+        // Preflights are validated
+        let response = options("/api", &[("Origin", "https://app.local"), ("Access-Control-Request-Method", "PUT")]);
+        assert_eq!(response.status_code, 204);
+        assert_eq!(response.headers.get("Access-Control-Allow-Methods"), Some("GET, PUT"));
+        let response = options("/api", &[("Origin", "https://evil.local"), ("Access-Control-Request-Method", "PUT")]);
+        assert_eq!(response.status_code, 403);
+        assert!(response.headers.get("Access-Control-Allow-Origin").is_none());
+        
+        // Actual responses carry CORS headers for allowed origins only
+        let request = HttpRequest::with_headers(Method::GET, "/api", &[("Origin", "https://app.local")]);
+        let mut response = HttpResponse::ok();
+        router.apply_cors_headers(&request, &mut response);
+        assert_eq!(response.headers.get("Access-Control-Allow-Origin"), Some("https://app.local"));
+        
+        let request = HttpRequest::with_headers(Method::GET, "/api", &[("Origin", "https://evil.local")]);
+        let mut response = HttpResponse::ok();
+        router.apply_cors_headers(&request, &mut response);
+        assert!(response.headers.get("Access-Control-Allow-Origin").is_none());
+    }
+    
+    #[test]
+    fn test_put_into_upload_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let uploads_route = RouteConfig {
+            path: "/uploads/*".to_string(),
+            allowed_methods: [Method::GET, Method::PUT].into_iter().collect(),
+            max_body_size: Some(8),
+            ..Default::default()
+        };
+        
+        let mut vhost = VirtualHost::default();
+        vhost.routes.push(uploads_route);
+        
+        let mut router = Router::new();
+        router.add_virtual_host(vhost);
+        router.set_file_storage(FileStorage::new(StorageConfig {
+            upload_dir: dir.path().to_path_buf(),
+            allowed_extensions: Some(vec!["txt".to_string()]),
+            ..StorageConfig::default()
+        }).unwrap());
+        
+        let mut put = |path: &str, body: &[u8], headers: &[(&str, &str)]| {
+            let mut request = HttpRequest::with_headers(Method::PUT, path, headers);
+            request.body = body.to_vec();
+            router.route_request(&request).unwrap()
+        };
+        
+        // Create, then replace
+        let response = put("/uploads/a.txt", b"one", &[]);
+        assert_eq!(response.status_code, 201);
+        assert_eq!(response.headers.get("Location"), Some("/uploads/a.txt"));
+        assert_eq!(put("/uploads/a.txt", b"two", &[]).status_code, 204);
+        assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"two");
+        
+        // Create-only and update-only preconditions
+        assert_eq!(put("/uploads/a.txt", b"three", &[("If-None-Match", "*")]).status_code, 412);
+        assert_eq!(put("/uploads/b.txt", b"new", &[("If-Match", "*")]).status_code, 412);
+        assert_eq!(put("/uploads/b.txt", b"new", &[("If-None-Match", "*")]).status_code, 201);
+        assert_eq!(put("/uploads/a.txt", b"four", &[("If-Match", "\"stale\"")]).status_code, 412);
+        assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"two");
+        
+        // Limits and names
+        assert_eq!(put("/uploads/a.txt", b"too large", &[]).status_code, 413);
+        assert_eq!(put("/uploads/run.sh", b"x", &[]).status_code, 415);
+        assert_eq!(put("/uploads/../a.txt", b"x", &[]).status_code, 400);
+    }
+    
+    #[test]
+    fn test_put_uses_route_upload_directory() {
+        let (global_dir, docs_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let docs_route = RouteConfig {
+            path: "/docs".to_string(),
+            allowed_methods: [Method::GET, Method::PUT].into_iter().collect(),
+            upload_dir: Some(docs_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        
+        let mut vhost = VirtualHost::default();
+        vhost.routes.push(docs_route);
+        
+        let mut router = Router::new();
+        router.add_virtual_host(vhost);
+        router.set_file_storage(FileStorage::new(StorageConfig {
+            upload_dir: global_dir.path().to_path_buf(),
+            ..StorageConfig::default()
+        }).unwrap());
+        
+        let mut request = HttpRequest::with_headers(Method::PUT, "/docs/notes.txt", &[]);
+        request.body = b"hello".to_vec();
+        
+        let response = router.route_request(&request).unwrap();
+        assert_eq!(response.status_code, 201);
+        assert_eq!(response.headers.get("Location"), Some("/docs/notes.txt"));
+        assert_eq!(std::fs::read(docs_dir.path().join("notes.txt")).unwrap(), b"hello");
+        assert!(!global_dir.path().join("notes.txt").exists());
+        
+        // The route itself names no file
+        request.path = "/docs".to_string();
+        assert_eq!(router.route_request(&request).unwrap().status_code, 400);
+    }
+    
+    #[test]
+    fn test_upload_route_serves_get_and_delete() {
+        let (global_dir, files_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        std::fs::write(files_dir.path().join("a.txt"), b"stored").unwrap();
+        let files_route = RouteConfig {
+            path: "/files/*".to_string(),
+            allowed_methods: [Method::GET, Method::PUT, Method::DELETE].into_iter().collect(),
+            upload_dir: Some(files_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let static_route = RouteConfig {
+            path: "/static/*".to_string(),
+            allowed_methods: [Method::GET, Method::DELETE].into_iter().collect(),
+            ..Default::default()
+        };
+        
+        let mut router = Router::new();
+        router.add_virtual_host(VirtualHost {
+            routes: vec![files_route, static_route],
+            ..VirtualHost::default()
+        });
+        router.set_file_storage(FileStorage::new(StorageConfig {
+            upload_dir: global_dir.path().to_path_buf(),
+            ..StorageConfig::default()
+        }).unwrap());
+        let mut send = |method: Method, path: &str| {
+            router.route_request(&HttpRequest::with_headers(method, path, &[])).unwrap().status_code
+        };
+        
+        // GET and DELETE find the file where PUT stores it
+        assert_eq!(send(Method::GET, "/files/a.txt"), 200);
+        assert_eq!(send(Method::GET, "/files/../a.txt"), 404);
+        assert_eq!(send(Method::DELETE, "/files/a.txt"), 200);
+        assert!(!files_dir.path().join("a.txt").exists());
+        assert_eq!(send(Method::DELETE, "/files/a.txt"), 404);
+        
+        // Routes without an upload directory delete nothing
+        assert_eq!(send(Method::DELETE, "/static/a.txt"), 403);
+    }
+    
+    #[test]
+    fn test_sessions_and_cgi_follow_the_route() {
+        let (root, scripts) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        std::fs::write(root.path().join("tool.py"), b"print('hi')").unwrap();
+        let session_route = RouteConfig {
+            path: "/account/*".to_string(),
+            allowed_methods: [Method::GET, Method::POST].into_iter().collect(),
+            sessions: true,
+            ..Default::default()
+        };
+        let cgi_route = RouteConfig {
+            path: "/run/*".to_string(),
+            cgi_directory: Some(scripts.path().to_path_buf()),
+            ..Default::default()
+        };
+        
+        let mut router = Router::new();
+        router.add_virtual_host(VirtualHost {
+            routes: vec![session_route, cgi_route, RouteConfig::default()],
+            document_root: root.path().to_string_lossy().to_string(),
+            ..VirtualHost::default()
+        });
+        let mut get = |path: &str| router.route_request(&HttpRequest::with_headers(Method::GET, path, &[])).unwrap();
+        
+        // Session endpoints live below whichever route is a session route
+        let response = get("/account/create");
+        assert_eq!(response.status_code, 200);
+        assert!(response.headers.get("Set-Cookie").is_some());
+        assert_eq!(get("/account/unknown").status_code, 404);
+        let response = get("/session/create");
+        assert_eq!(response.status_code, 404);
+        assert!(response.headers.get("Set-Cookie").is_none());
+        
+        // Scripts run only from a CGI route's directory; elsewhere a .py
+        // file is just a file
+        assert_eq!(get("/run/tool.py").status_code, 404);
+        let response = get("/tool.py");
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body_len(), 11);
+    }
+    
+    #[test]
+    fn test_redirect_route_status() {
+        let redirect_route = RouteConfig {
+            path: "/old-page".to_string(),
+            redirect: Some("/new-page".to_string()),
+            redirect_status: Some(301),
+            ..Default::default()
+        };
+        
+        let mut vhost = VirtualHost::default();
+        vhost.routes.push(redirect_route);
+        
+        let mut router = Router::new();
+        router.add_virtual_host(vhost);
+        
+        let mut request = HttpRequest::new();
+        request.path = "/old-page".to_string();
+        
+        let response = router.route_request(&request).unwrap();
+        assert_eq!(response.status_code, 301);
+        assert_eq!(response.headers.get("Location"), Some("/new-page"));
+    }
+    
+    #[test]
+    fn test_vhost_max_body_size() {
+        let upload_route = RouteConfig {
+            path: "/upload".to_string(),
+            allowed_methods: [Method::GET, Method::HEAD, Method::POST].into_iter().collect(),
+            max_body_size: None,
+            ..Default::default()
+        };
+        
+        let mut vhost = VirtualHost {
+            max_body_size: 4,
+            ..Default::default()
+        };
+        vhost.routes.push(upload_route);
+        
+        let mut router = Router::new();
+        router.add_virtual_host(vhost);
+        
+        let mut request = HttpRequest::new();
+        request.method = Method::POST;
+        request.path = "/upload".to_string();
+        request.body = b"too large".to_vec();
+        
+        let response = router.route_request(&request).unwrap();
+        assert_eq!(response.status_code, 413);
+    }
 }
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::fmt;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime};
+use crate::http::date;
+use crate::http::headers::HeaderMap;
 
 /// SameSite attribute for cookies
 #[derive(Debug, Clone, PartialEq)]
@@ -112,10 +114,7 @@ impl Cookie {
         }
         
         if let Some(expires) = self.expires {
-            if let Ok(duration) = expires.duration_since(UNIX_EPOCH) {
-                // Format as HTTP date (RFC 7231)
-                parts.push(format!("Expires={}", format_http_date(duration.as_secs())));
-            }
+            parts.push(format!("Expires={}", date::format_system_time(expires)));
         }
         
         if let Some(max_age) = self.max_age {
@@ -171,6 +170,18 @@ impl CookieJar {
         jar
     }
     
+    /// Parse cookies from every Cookie header of a request. Clients may
+    /// split their cookies over several header lines.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut jar = CookieJar::new();
+        for header_value in headers.get_all("Cookie") {
+            for (_, cookie) in Self::parse_cookie_header(header_value).cookies {
+                jar.add_cookie(cookie);
+            }
+        }
+        jar
+    }
+    
     /// Add a cookie to the jar
     pub fn add_cookie(&mut self, cookie: Cookie) {
         self.cookies.insert(cookie.name.clone(), cookie);
@@ -213,54 +224,6 @@ impl Default for CookieJar {
     }
 }
 
-/// Format Unix timestamp as HTTP date
-fn format_http_date(timestamp: u64) -> String {
-    // Simple HTTP date formatting (RFC 7231)
-    const DAYS_IN_MONTH: [u64; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
-    const MONTH_NAMES: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
-    const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
-    
-    let mut days_since_epoch = timestamp / 86400;
-    let hours = (timestamp % 86400) / 3600;
-    let minutes = (timestamp % 3600) / 60;
-    let seconds = timestamp % 60;
-    
-    // Calculate year
-    let mut year = 1970;
-    loop {
-        let days_in_year = if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) { 366 } else { 365 };
-        if days_since_epoch >= days_in_year {
-            days_since_epoch -= days_in_year;
-            year += 1;
-        } else {
-            break;
-        }
-    }
-    
-    // Calculate month and day
-    let mut month = 0;
-    let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
-    for i in 0..12 {
-        let mut days_in_month = DAYS_IN_MONTH[i];
-        if i == 1 && is_leap {
-            days_in_month = 29;
-        }
-        if days_since_epoch >= days_in_month {
-            days_since_epoch -= days_in_month;
-            month += 1;
-        } else {
-            break;
-        }
-    }
-    let day = days_since_epoch + 1;
-    
-    // Calculate day of week (simplified)
-    let day_of_week = ((timestamp / 86400 + 4) % 7) as usize;
-    
-    format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT", 
-            DAY_NAMES[day_of_week], day, MONTH_NAMES[month], year, hours, minutes, seconds)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -304,6 +267,18 @@ mod tests {
         assert_eq!(jar.get_value("nonexistent"), None);
     }
     
+    #[test]
+    fn test_cookie_jar_from_repeated_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append("Cookie", "session_id=abc123");
+        headers.append("cookie", "theme=dark; lang=en");
+        
+        let jar = CookieJar::from_headers(&headers);
+        assert_eq!(jar.get_value("session_id"), Some("abc123"));
+        assert_eq!(jar.get_value("theme"), Some("dark"));
+        assert_eq!(jar.get_value("lang"), Some("en"));
+    }
+    
     #[test]
     fn test_cookie_jar_operations() {
         let mut jar = CookieJar::new();
@@ -2,5 +2,5 @@ pub mod cookie;
 pub mod session;
 pub mod store;
 
-pub use cookie::{Cookie, CookieJar};
+pub use cookie::CookieJar;
 pub use store::{SessionStore, SessionConfig};
@@ -230,7 +230,7 @@ mod tests {
     
     #[test]
     fn test_session_expiration() {
-        let mut data = SessionData::with_expiration(Duration::from_millis(10));
+        let data = SessionData::with_expiration(Duration::from_millis(10));
         assert!(!data.is_expired());
         
         // Wait for expiration
@@ -295,8 +295,10 @@ mod tests {
     
     #[test]
     fn test_session_expiration() {
-        let mut config = SessionConfig::default();
-        config.default_expiration = Duration::from_millis(10);
+        let config = SessionConfig {
+            default_expiration: Duration::from_millis(10),
+            ..Default::default()
+        };
         let store = SessionStore::new(config);
         
         let session = store.create_session().unwrap();
@@ -371,9 +373,11 @@ mod tests {
     
     #[test]
     fn test_session_cleanup() {
-        let mut config = SessionConfig::default();
-        config.default_expiration = Duration::from_millis(10);
-        config.cleanup_interval = Duration::from_millis(1);
+        let config = SessionConfig {
+            default_expiration: Duration::from_millis(10),
+            cleanup_interval: Duration::from_millis(1),
+            ..Default::default()
+        };
         let store = SessionStore::new(config);
         
         // Create sessions
@@ -408,8 +412,10 @@ mod tests {
     
     #[test]
     fn test_max_sessions_limit() {
-        let mut config = SessionConfig::default();
-        config.max_sessions = 2;
+        let config = SessionConfig {
+            max_sessions: 2,
+            ..Default::default()
+        };
         let store = SessionStore::new(config);
         
         let _session1 = store.create_session().unwrap();
@@ -63,6 +63,11 @@ impl FileStorage {
         Ok(FileStorage { config })
     }
     
+    /// The same settings, storing files in another directory
+    pub fn with_upload_dir(&self, upload_dir: &Path) -> FileStorage {
+        FileStorage { config: StorageConfig { upload_dir: upload_dir.to_path_buf(), ..self.config.clone() } }
+    }
+    
     /// Store uploaded file data
     pub fn store_file(
         &self,
@@ -79,21 +84,8 @@ impl FileStorage {
         }
         
         // Validate file extension if restrictions are configured
-        if let Some(ref allowed_exts) = self.config.allowed_extensions {
-            if let Some(ref filename) = original_filename {
-                let extension = Path::new(filename)
-                    .extension()
-                    .and_then(|ext| ext.to_str())
-                    .unwrap_or("")
-                    .to_lowercase();
-                
-                if !allowed_exts.iter().any(|ext| ext.to_lowercase() == extension) {
-                    return Err(io::Error::new(
-                        io::ErrorKind::PermissionDenied,
-                        format!("File extension '{}' not allowed", extension)
-                    ));
-                }
-            }
+        if let Some(ref filename) = original_filename {
+            self.check_extension(filename)?;
         }
         
         // Determine storage directory
@@ -138,6 +130,82 @@ impl FileStorage {
         })
     }
     
+    /// Store data under a client-chosen name in the upload directory,
+    /// replacing an existing file unless `create_only` is set. The data is
+    /// written to a temporary file first and moved into place, so readers
+    /// never see a partial file. Returns whether the file was newly created.
+    pub fn put_file(&self, filename: &str, data: &[u8], create_only: bool) -> io::Result<bool> {
+        if data.len() > self.config.max_file_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("File size {} exceeds maximum {}", data.len(), self.config.max_file_size)
+            ));
+        }
+        
+        let file_path = self.put_path(filename)?;
+        self.check_extension(filename)?;
+        
+        if !self.config.upload_dir.exists() {
+            fs::create_dir_all(&self.config.upload_dir)?;
+        }
+        
+        let temp_path = self.config.upload_dir.join(format!(
+            ".{}.{}.tmp",
+            filename,
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos()
+        ));
+        let mut file = File::create(&temp_path)?;
+        let written = file.write_all(data).and_then(|_| file.sync_all());
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+        
+        // A hard link fails if the target exists, which makes creation atomic
+        // when another writer races us; a rename replaces the target atomically
+        let result = if create_only {
+            fs::hard_link(&temp_path, &file_path).map(|_| true)
+        } else {
+            let existed = file_path.exists();
+            fs::rename(&temp_path, &file_path).map(|_| !existed)
+        };
+        let _ = fs::remove_file(&temp_path);
+        result
+    }
+    
+    /// Path in the upload directory for a client-chosen filename. Names that
+    /// would be altered by sanitizing, such as ones containing a path
+    /// separator or starting with a dot, are rejected rather than rewritten
+    /// so the stored file is always found at the URI the client used.
+    pub fn put_path(&self, filename: &str) -> io::Result<PathBuf> {
+        if filename.is_empty() || filename.starts_with('.') || self.sanitize_filename(filename) != filename {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid upload filename '{}'", filename)
+            ));
+        }
+        Ok(self.config.upload_dir.join(filename))
+    }
+    
+    /// Check a filename against the allowed extensions, if any are configured
+    pub fn check_extension(&self, filename: &str) -> io::Result<()> {
+        if let Some(ref allowed_exts) = self.config.allowed_extensions {
+            let extension = Path::new(filename)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("")
+                .to_lowercase();
+            
+            if !allowed_exts.iter().any(|ext| ext.trim_start_matches('.').to_lowercase() == extension) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("File extension '{}' not allowed", extension)
+                ));
+            }
+        }
+        Ok(())
+    }
+    
     /// Generate a safe filename that doesn't conflict with existing files
     fn generate_safe_filename(&self, original: &str, dir: &Path) -> io::Result<String> {
         // Sanitize the filename
@@ -297,6 +365,33 @@ mod tests {
         assert_eq!(storage.extension_from_content_type("application/unknown"), None);
     }
     
+    #[test]
+    fn test_put_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileStorage::new(StorageConfig {
+            upload_dir: dir.path().to_path_buf(),
+            max_file_size: 16,
+            allowed_extensions: Some(vec!["txt".to_string()]),
+            ..StorageConfig::default()
+        }).unwrap();
+        
+        assert!(storage.put_file("notes.txt", b"first", false).unwrap());
+        assert!(!storage.put_file("notes.txt", b"second", false).unwrap());
+        assert_eq!(fs::read(dir.path().join("notes.txt")).unwrap(), b"second");
+        
+        let err = storage.put_file("notes.txt", b"third", true).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(storage.put_file("fresh.txt", b"new", true).unwrap());
+        
+        assert_eq!(storage.put_file("script.sh", b"x", false).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(storage.put_file("../escape.txt", b"x", false).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(storage.put_file(".hidden.txt", b"x", false).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(storage.put_file("big.txt", &[b'x'; 17], false).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+    
     #[test]
     fn test_storage_config_default() {
         let config = StorageConfig::default();
@@ -212,10 +212,10 @@ impl MultipartParser {
         for part in header.split(';') {
             let part = part.trim();
             
-            if part.starts_with("name=") {
-                name = Some(self.parse_quoted_value(&part[5..])?);
-            } else if part.starts_with("filename=") {
-                filename = Some(self.parse_quoted_value(&part[9..])?);
+            if let Some(value) = part.strip_prefix("name=") {
+                name = Some(self.parse_quoted_value(value)?);
+            } else if let Some(value) = part.strip_prefix("filename=") {
+                filename = Some(self.parse_quoted_value(value)?);
             }
         }
         